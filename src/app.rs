@@ -1,53 +1,398 @@
-use crate::views::ViewKind;
+use crate::archive::Archive;
+use crate::config::{self, Config, Setting, DEFAULT_BUFFER_CAPACITY};
+use crate::export;
+use crate::histogram::FieldSample;
+use crate::keymap::Keymap;
+use crate::openai::ChatMessage;
+use crate::plugins::ToolPicker;
+use crate::query::{self, FilterMode};
+use crate::utils;
+use crate::views::{self, HighlightRule, Theme, ViewKind};
+use crossterm::event::KeyEvent;
+use ratatui::style::Color;
+use serde_json::Value;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 
+/// Ingest rate (lines per second) above which the display is sampled,
+/// unless --sample-above says otherwise
+pub const DEFAULT_SAMPLE_ABOVE: usize = 20_000;
+
+/// How often the ingest rate, and with it the sampling ratio, is re-measured
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Default number of recent lines sent to the AI for analysis
+pub const DEFAULT_AI_SAMPLE_SIZE: usize = 100;
+const AI_SAMPLE_STEP: usize = 25;
+pub const MIN_AI_SAMPLE_SIZE: usize = 25;
+pub const MAX_AI_SAMPLE_SIZE: usize = 2000;
+
+/// Field names checked, in order, when filtering by logger/module
+pub const DEFAULT_LOGGER_KEYS: &[&str] = &["logger", "module", "target", "component"];
+
+/// Field names checked, in order, for the ID tying a request's lines together
+pub const DEFAULT_TRACE_KEYS: &[&str] = &[
+    "trace_id",
+    "traceId",
+    "request_id",
+    "requestId",
+    "correlation_id",
+    "correlationId",
+    "span_trace_id",
+];
+
+/// Characters moved per Left/Right press
+const HORIZONTAL_STEP: usize = 8;
+
+/// Most lines shown either side of a filter match
+const MAX_CONTEXT_LINES: usize = 50;
+
+/// Error/warning lines kept for the error panel
+const PROBLEM_LINES_CAP: usize = 200;
+
+/// How many levels of nested JSON are rendered inline before collapsing to "…"
+pub const DEFAULT_JSON_DEPTH: usize = 2;
+const MAX_JSON_DEPTH: usize = 16;
+
+/// How long an analysis request may run before it is abandoned
+pub const ANALYSIS_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Extra time the UI waits past ANALYSIS_TIMEOUT before assuming the result was lost
+const ANALYSIS_GRACE: Duration = Duration::from_secs(5);
+
+/// Number of past analysis exchanges (request + response) kept for follow-ups
+const MAX_AI_EXCHANGES: usize = 3;
+
+/// Parsed JSON lines, keyed by absolute line number (see total_lines).
+/// JsonView renders every frame, so without this the same lines get
+/// re-parsed over and over while scrolling. An entry goes when its line is
+/// dropped, and the whole cache is emptied when the Json view is left, so
+/// the parsed values of a large buffer aren't kept around while no view
+/// needs them. Parse failures are cached too, since non-JSON lines are
+/// re-checked just as often.
+#[derive(Default)]
+pub struct JsonCache {
+    entries: HashMap<usize, Option<Rc<Value>>>,
+}
+
+impl JsonCache {
+    /// Return the parsed value for a line, parsing and caching it on a miss
+    pub fn get_or_parse(&mut self, line_number: usize, line: &str) -> Option<Rc<Value>> {
+        self.entries
+            .entry(line_number)
+            .or_insert_with(|| serde_json::from_str::<Value>(line).ok().map(Rc::new))
+            .clone()
+    }
+
+    /// Drop the cached entry for a line that left the buffer
+    pub fn invalidate(&mut self, line_number: usize) {
+        self.entries.remove(&line_number);
+    }
+
+    /// Drop every cached entry
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Which stream a log line came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineSource {
+    Stdout,
+    Stderr,
+}
+
+/// What a status bar message reports, which picks its color
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatusKind {
+    /// Done as asked
+    Info,
+    /// Under way, like an analysis waiting on the API
+    Pending,
+    /// Went wrong or couldn't be done
+    Error,
+}
+
+/// Whether keys drive the app or are typed into the bottom-bar input box
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputMode {
+    Normal,
+    /// `/`: a regex or path filter, applied as it is typed
+    FilterInput(String),
+    /// `h`: the numeric field to chart in the histogram popup
+    HistogramField(String),
+    /// Enter on the `S` screen: a new value for a setting
+    Setting(Setting, String),
+    /// `:`: a line number to jump to
+    LineNumber(String),
+    /// `o`: the file to write the displayed lines to
+    SavePath(String),
+}
+
 pub struct AppState {
-    pub log_buffer: Vec<String>,
+    pub log_buffer: VecDeque<String>,  // Oldest line first; a deque so dropping the oldest at the cap is cheap
+    pub buffer_capacity: usize,  // Lines kept before the oldest are dropped (config.toml)
     pub active_view: ViewKind,
+    pub previous_view: Option<ViewKind>,  // View to flip back to with the last-view key
     pub last_model_response: Option<String>,
-    pub log_receiver: mpsc::Receiver<String>,
+    pub status_kind: StatusKind,  // How last_model_response went, for its color
+    pub log_receiver: mpsc::Receiver<(String, LineSource)>,
     pub input_source: String,
+    pub waiting_message: Option<String>,  // Shown over the empty view until the first line arrives
     // Navigation and filtering
     pub scroll_offset: usize,  // Current scroll position
     pub selected_index: Option<usize>,  // Currently selected/highlighted line index
-    pub filter_text: Option<String>,  // Current filter text (from selected line)
+    pub filter: Option<FilterMode>,  // Current filter term (from the selected line or the `/` prompt)
     pub filtered_indices: Vec<usize>,  // Indices of logs matching the filter
+    pub context_lines: usize,  // Lines shown either side of each filter match, like grep -C
+    pub filter_field: Option<String>,  // Structured field the filter applies to (e.g. "logger")
+    pub logger_keys: Vec<String>,  // Field names treated as the logger/module, in priority order
+    pub trace_keys: Vec<String>,  // Field names treated as the trace/request ID, in priority order
+    pub dropped_lines: usize,  // Lines evicted from the front of the capped buffer
+    pub first_line: Option<String>,  // The first line kept, used as the Csv view's header row
+    pub sample_above: Option<usize>,  // Ingest rate (lines/s) above which only some lines are kept; None never samples
+    pub sample_every: usize,  // Sampling ratio: one line in this many is kept (1 keeps all)
+    pub ingest_rate: usize,  // Lines per second over the last measured window
+    pub sampled_out: usize,  // Lines counted but not kept because of sampling
+    rate_window: (Instant, usize),  // Start of the current rate window and lines seen in it
+    pub ai_sample_size: usize,  // How many recent lines analyze_logs sends to the AI
+    pub reveal_whitespace: bool,  // Render spaces/tabs/line ends visibly in PlainView
+    pub preserve_ansi: bool,  // PlainView colors lines by their own ANSI sequences instead of stripping them (A)
+    pub json_cache: RefCell<JsonCache>,  // Parsed JSON lines, reused across frames
+    pub json_tree_cache: RefCell<views::JsonTreeCache>,  // Json tree rows, until the lines shown or the folds change
+    csv_delimiter: Cell<Option<u8>>,  // Csv view separator, until the lines it was voted on change
+    pub theme: Theme,  // Selection and filter-match colors
+    pub highlight_rules: Vec<HighlightRule>,  // --highlight regexes colored in Plain rows
+    pub ai_history: Vec<ChatMessage>,  // Bounded multi-turn analysis conversation
+    pub ai_lines_seen: usize,  // Total lines received as of the last successful analysis
+    pub show_original_indices: bool,  // Prefix filtered rows with their buffer index
+    pub show_line_numbers: bool,  // Line-number gutter in the Plain and KeyValue views (#)
+    pub analysis_started: Option<Instant>,  // Set while an AI analysis is in flight
+    pub auto_analyze_every: Option<usize>,  // Analyze on its own after this many new lines (--auto-analyze)
+    pub last_auto_analyzed_count: usize,  // total_lines when the last automatic analysis started
+    pub stderr_lines: HashSet<usize>,  // Absolute line numbers (see total_lines) that came from stderr
+    pub bookmarks: BTreeSet<usize>,  // Absolute line numbers marked with `m`, shown by the Bookmarks view
+    pub json_max_depth: usize,  // Nesting levels JsonView renders before collapsing
+    pub json_flatten: bool,  // JsonView shows one row per leaf, keyed by dotted path
+    pub json_tree: bool,  // JsonView shows each line as a collapsible tree (t); scroll_offset counts tree rows
+    pub json_collapsed: HashSet<Vec<String>>,  // Tree paths folded with Space, shared by every line
+    pub reverse: bool,  // Show newest lines at the top
+    pub time_sort: bool,  // Order lines by their time field instead of arrival (s)
+    line_times: VecDeque<Option<i64>>,  // Each buffered line's time (utils::line_timestamp); an untimed line takes the one before's
+    time_order: BTreeSet<(Option<i64>, usize)>,  // Time and absolute line number of every buffered line, for time_sort
+    pub dedupe: bool,  // Plain view collapses runs of identical lines into one row with a count (d)
+    pub paused: bool,  // Incoming lines are left in log_receiver, so its readers block once it fills (p)
+    pub follow_mode: bool,  // Keep the newest line in view as lines arrive (`G`)
+    pub last_filter: Option<(FilterMode, Option<String>)>,  // Filter term and field before the last clear
+    pub range: Option<(usize, usize)>,  // Anchor and cursor of a shift+arrow range selection
+    pub raw_ai_response: Option<String>,  // Exact JSON the model returned for the last analysis
+    pub show_raw_response: bool,  // Show raw_ai_response in a popup
+    pub pinned_terms: Vec<FilterMode>,  // Earlier filter terms kept alongside filter; a line matching any is shown
+    pub ignore_case: bool,  // Filter terms match regardless of case (I)
+    pub error_count: usize,  // Error-level lines seen this session, including evicted ones
+    pub wrap_lines: bool,  // Wrap long lines in the Plain and KeyValue views instead of clipping
+    pub ai_model: String,  // OpenAI model analyses are sent to
+    pub ai_base_url: String,  // API root the model is called through
+    pub ai_allowed_keys: Option<Vec<String>>,  // Only these JSON keys are sent to the AI, if set
+    pub horizontal_offset: usize,  // Characters scrolled off the left edge of wide rows
+    pub h_scroll_max: Cell<usize>,  // Furthest useful horizontal_offset, measured by the last frame drawn
+    pub view_scroll: HashMap<String, usize>,  // Last scroll_offset of each view, by view name
+    pub problem_lines: VecDeque<(utils::Severity, String)>,  // Recent error/warning lines, independent of filters and eviction
+    pub show_error_panel: bool,  // Show problem_lines in a side panel
+    pub archive: Option<Archive>,  // Where evicted lines are written, with --archive
+    pub ai_pick: Option<(ViewKind, String)>,  // View the last analysis chose and why, shown in its title
+    pub input_mode: InputMode,  // Normal, or an open input box and the text typed so far
+    pub filter_input_error: Option<String>,  // Why the filter being typed doesn't compile, if it doesn't
+    filter_before_input: Option<(Option<FilterMode>, Option<String>, Option<usize>)>,  // Filter, field and selection to restore if the input box is cancelled
+    pub histogram: Option<FieldSample>,  // Field values shown in the histogram popup, while it is open
+    pub tool_picker: Option<ToolPicker>,  // The `T` external tool menu, while it is open
+    pub settings_screen: Option<usize>,  // The `S` settings list and its highlighted row, while open
+    pub config_path: Option<PathBuf>,  // config.toml, where the settings screen saves changes
+    pub keymap: Keymap,  // Main screen keys, with any `[keys]` rebindings
+    pub pending_keys: Vec<KeyEvent>,  // Keys pressed so far of a multi-key binding like `gg`
+    pub pending_tool: Option<String>,  // Tool picked from the menu, launched by the main loop
 }
 
 impl AppState {
-    pub fn new(log_receiver: mpsc::Receiver<String>, input_source: String) -> Self {
+    pub fn new(log_receiver: mpsc::Receiver<(String, LineSource)>, input_source: String, config: &Config) -> Self {
         Self {
-            log_buffer: Vec::with_capacity(2000),
+            log_buffer: VecDeque::with_capacity(config.buffer_capacity.min(DEFAULT_BUFFER_CAPACITY)),
+            buffer_capacity: config.buffer_capacity,
             active_view: ViewKind::Plain,
+            previous_view: None,
             last_model_response: None,
+            status_kind: StatusKind::Info,
             log_receiver,
             input_source,
+            waiting_message: None,
             scroll_offset: 0,
             selected_index: None,
-            filter_text: None,
+            filter: None,
             filtered_indices: Vec::new(),
+            context_lines: 0,
+            filter_field: None,
+            logger_keys: DEFAULT_LOGGER_KEYS.iter().map(|k| k.to_string()).collect(),
+            trace_keys: DEFAULT_TRACE_KEYS.iter().map(|k| k.to_string()).collect(),
+            dropped_lines: 0,
+            first_line: None,
+            sample_above: Some(DEFAULT_SAMPLE_ABOVE),
+            sample_every: 1,
+            ingest_rate: 0,
+            sampled_out: 0,
+            rate_window: (Instant::now(), 0),
+            ai_sample_size: DEFAULT_AI_SAMPLE_SIZE,
+            reveal_whitespace: false,
+            preserve_ansi: false,
+            json_cache: RefCell::default(),
+            json_tree_cache: RefCell::default(),
+            csv_delimiter: Cell::new(None),
+            theme: Theme::default(),
+            highlight_rules: Vec::new(),
+            ai_history: Vec::new(),
+            ai_lines_seen: 0,
+            show_original_indices: false,
+            show_line_numbers: config.line_numbers,
+            analysis_started: None,
+            auto_analyze_every: config.auto_analyze_threshold,
+            last_auto_analyzed_count: 0,
+            stderr_lines: HashSet::new(),
+            bookmarks: BTreeSet::new(),
+            json_max_depth: DEFAULT_JSON_DEPTH,
+            json_flatten: false,
+            json_tree: false,
+            json_collapsed: HashSet::new(),
+            reverse: false,
+            time_sort: false,
+            line_times: VecDeque::new(),
+            time_order: BTreeSet::new(),
+            dedupe: false,
+            paused: false,
+            follow_mode: false,
+            last_filter: None,
+            range: None,
+            raw_ai_response: None,
+            show_raw_response: false,
+            pinned_terms: Vec::new(),
+            ignore_case: false,
+            error_count: 0,
+            wrap_lines: false,
+            ai_model: crate::openai::DEFAULT_MODEL.to_string(),
+            ai_base_url: crate::openai::DEFAULT_BASE_URL.to_string(),
+            ai_allowed_keys: None,
+            horizontal_offset: 0,
+            h_scroll_max: Cell::new(0),
+            view_scroll: HashMap::new(),
+            problem_lines: VecDeque::new(),
+            show_error_panel: false,
+            archive: None,
+            ai_pick: None,
+            input_mode: InputMode::Normal,
+            filter_input_error: None,
+            filter_before_input: None,
+            histogram: None,
+            tool_picker: None,
+            settings_screen: None,
+            config_path: config.path.clone(),
+            keymap: config.keys.clone(),
+            pending_keys: Vec::new(),
+            pending_tool: None,
+        }
+    }
+
+    pub fn add_log(&mut self, line: String, source: LineSource) {
+        self.waiting_message = None;
+        // Errors and warnings are tallied before sampling, so a flood of
+        // input can't hide them from the counter and the error panel
+        if let Some(severity) = utils::line_severity(&line) {
+            if severity == utils::Severity::Error {
+                self.error_count += 1;
+            }
+            if self.problem_lines.len() == PROBLEM_LINES_CAP {
+                self.problem_lines.pop_front();
+            }
+            self.problem_lines.push_back((severity, line.clone()));
+        }
+        // Above the sampling threshold most lines are only counted, so a
+        // flood of input can't keep the UI busy churning the buffer
+        if !self.sample_line() {
+            self.sampled_out += 1;
+            return;
+        }
+        self.push_line(line, source);
+    }
+
+    /// Pause or resume taking in lines. While paused the main loop stops
+    /// reading log_receiver, so lines queue up there and, once it is full,
+    /// the readers stall the pipe or command feeding scry instead of lines
+    /// being lost. Resuming reads what queued up as if it had just come in.
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        if !self.paused {
+            // The pause isn't part of the ingest rate; start a fresh window
+            self.rate_window = (Instant::now(), 0);
         }
     }
 
-    pub fn add_log(&mut self, line: String) {
+    /// Lines queued in log_receiver and whether it is full (so the input is
+    /// stalled), shown while paused
+    pub fn queued_lines(&self) -> (usize, bool) {
+        let queued = self.log_receiver.len();
+        (queued, queued >= self.log_receiver.max_capacity())
+    }
+
+    /// Add a line to the buffer, dropping the oldest past buffer_capacity
+    fn push_line(&mut self, line: String, source: LineSource) {
         // Accept any line, even if it's empty or contains weird characters
         // The views will handle sanitization for display
-        let new_index = self.log_buffer.len();
-        self.log_buffer.push(line);
+        if self.first_line.is_none() {
+            self.first_line = Some(line.clone());
+        }
+        if source == LineSource::Stderr {
+            self.stderr_lines.insert(self.total_lines());
+        }
+        // Parsed once here rather than on every sorted frame. A line without
+        // a time, such as a stack trace line, stays beside the one before it.
+        let time = utils::line_timestamp(&line).or_else(|| self.line_times.back().copied().flatten());
+        self.time_order.insert((time, self.total_lines()));
+        self.line_times.push_back(time);
+        self.log_buffer.push_back(line);
+        if self.log_buffer.len() <= utils::DELIMITER_SAMPLE_LINES {
+            self.csv_delimiter.set(None);
+        }
         
-        // Keep buffer capped at ~2000 lines
-        if self.log_buffer.len() > 2000 {
+        // Keep buffer capped at buffer_capacity lines (more than one goes
+        // after the capacity is lowered from the settings screen)
+        while self.log_buffer.len() > self.buffer_capacity {
             let removed_index = 0;
-            self.log_buffer.remove(0);
+            let Some(removed) = self.log_buffer.pop_front() else {
+                break;
+            };
+            self.csv_delimiter.set(None);
+            self.json_cache.get_mut().invalidate(self.dropped_lines);
+            if let Some(ref mut archive) = self.archive {
+                archive.push(removed);
+            }
+            self.stderr_lines.remove(&self.dropped_lines);
+            self.bookmarks.remove(&self.dropped_lines);
+            if let Some(time) = self.line_times.pop_front() {
+                self.time_order.remove(&(time, self.dropped_lines));
+            }
+            self.dropped_lines += 1;
             
             // Update filtered_indices: remove the old index and adjust all indices
             if !self.filtered_indices.is_empty() {
-                self.filtered_indices.retain(|&idx| idx != removed_index);
+                // Indices are in order, so only the first can be the removed line
+                if self.filtered_indices.first() == Some(&removed_index) {
+                    self.filtered_indices.remove(0);
+                }
                 // Decrement all indices since we removed the first item
-                self.filtered_indices = self.filtered_indices
-                    .iter()
-                    .map(|&idx| idx.saturating_sub(1))
-                    .collect();
+                for idx in &mut self.filtered_indices {
+                    *idx -= 1;
+                }
             }
             
             // Update selected_index if it was pointing to the removed item
@@ -58,38 +403,303 @@ impl AppState {
                     self.selected_index = Some(selected - 1);
                 }
             }
+
+            // Shift the range down, dropping it once it falls off entirely
+            self.range = self.range.and_then(|(anchor, cursor)| {
+                if anchor.max(cursor) == removed_index {
+                    None
+                } else {
+                    Some((anchor.saturating_sub(1), cursor.saturating_sub(1)))
+                }
+            });
             
-            // Adjust scroll_offset if needed
-            if self.scroll_offset > 0 {
+            // Adjust scroll_offset if needed (when reversed the oldest line is
+            // at the bottom, so dropping it doesn't shift the view)
+            if self.scroll_offset > 0 && !self.reverse {
                 self.scroll_offset = self.scroll_offset.saturating_sub(1);
             }
+        }
+
+        // If there's an active filter, check if the new log matches
+        let new_index = self.log_buffer.len() - 1;
+        let matches = self.line_matches_filter(&self.log_buffer[new_index]);
+        if matches {
+            // Add to filtered_indices (it's already at the correct index)
+            self.filtered_indices.push(new_index);
+        }
+
+        // When reversed, new lines appear at the top. Stay on the newest line
+        // at offset 0; otherwise keep the same lines in view.
+        if self.reverse && self.scroll_offset > 0 && (matches || !self.has_filter()) {
+            self.scroll_offset += 1;
+        }
+        // Tree rows aren't recounted per line; follow catches up on the next key
+        if self.follow_mode && !self.json_tree_active() {
+            self.scroll_offset = self.newest_position();
+        }
+    }
+
+    /// Display position of the newest line: the bottom, or the top when reversed
+    fn newest_position(&self) -> usize {
+        if self.reverse {
+            0
         } else {
-            // New log was added at new_index
-            // If there's an active filter, check if this new log matches
-            if let Some(ref filter) = self.filter_text {
-                if !filter.is_empty() && self.log_buffer[new_index].contains(filter) {
-                    // Add to filtered_indices (it's already at the correct index)
-                    self.filtered_indices.push(new_index);
-                }
-            }
+            self.scroll_extent().saturating_sub(1)
+        }
+    }
+
+    /// Turn follow mode on (jumping to the newest line) or off
+    pub fn toggle_follow(&mut self) {
+        self.follow_mode = !self.follow_mode;
+        if self.follow_mode {
+            self.scroll_offset = self.newest_position();
+        }
+    }
+
+    /// Whether the Json view is showing its tree, where scroll_offset counts
+    /// tree rows rather than lines
+    pub fn json_tree_active(&self) -> bool {
+        self.json_tree && self.active_view == ViewKind::Json
+    }
+
+    /// How many rows the cursor keys move through: tree rows in the Json
+    /// tree, displayed lines everywhere else
+    pub fn scroll_extent(&self) -> usize {
+        if self.json_tree_active() {
+            views::json_tree_rows(self).len()
+        } else {
+            self.get_display_count()
+        }
+    }
+
+    /// Switch the Json view between its table and tree, keeping the cursor
+    /// on the same line
+    pub fn toggle_json_tree(&mut self) {
+        let current = self.current_index();
+        self.json_tree = !self.json_tree;
+        self.selected_index = None;
+        self.scroll_offset = if self.json_tree {
+            views::json_tree_rows(self)
+                .iter()
+                .position(|row| Some(row.index) == current)
+                .unwrap_or(0)
+        } else {
+            current.and_then(|idx| self.display_position(idx)).unwrap_or(0)
+        };
+    }
+
+    /// Fold or unfold the tree node under the cursor
+    pub fn toggle_json_node(&mut self) {
+        let rows = views::json_tree_rows(self);
+        let Some(row) = rows.get(self.scroll_offset.min(rows.len().saturating_sub(1))) else {
+            return;
+        };
+        if row.open.is_some() && !self.json_collapsed.remove(&row.path) {
+            self.json_collapsed.insert(row.path.clone());
+        }
+    }
+
+    /// Switch filter terms between exact and case-insensitive matching
+    pub fn toggle_ignore_case(&mut self) {
+        self.ignore_case = !self.ignore_case;
+        self.update_filter();
+    }
+
+    /// Count a line toward the ingest rate and decide whether to keep it.
+    /// Once the rate goes over `sample_above`, one line in `sample_every` is
+    /// kept, with the ratio chosen to bring the kept rate under the threshold.
+    fn sample_line(&mut self) -> bool {
+        let (started, count) = &mut self.rate_window;
+        *count += 1;
+        let keep = *count % self.sample_every == 0;
+        let elapsed = started.elapsed();
+        if elapsed >= RATE_WINDOW {
+            self.ingest_rate = (*count as f64 / elapsed.as_secs_f64()) as usize;
+            self.rate_window = (Instant::now(), 0);
+            self.sample_every = match self.sample_above {
+                Some(limit) if limit > 0 && self.ingest_rate > limit => self.ingest_rate.div_ceil(limit),
+                _ => 1,
+            };
+        }
+        keep
+    }
+
+    /// Field separator for the Csv view, voted on by the oldest buffered
+    /// lines. The vote is only rerun once those lines change.
+    pub fn csv_delimiter(&self) -> u8 {
+        if let Some(delimiter) = self.csv_delimiter.get() {
+            return delimiter;
         }
+        let delimiter = utils::detect_delimiter(self.log_buffer.iter());
+        self.csv_delimiter.set(Some(delimiter));
+        delimiter
+    }
+
+    /// Whether lines are currently being sampled. The ratio is only updated
+    /// as lines arrive, so it is ignored once input has gone quiet, but kept
+    /// (with the last rate) while paused.
+    pub fn is_sampling(&self) -> bool {
+        self.sample_every > 1 && (self.paused || self.rate_window.0.elapsed() < RATE_WINDOW * 2)
     }
 
+    /// Switch views, remembering the scroll position of the one being left
+    /// and returning to where the new one was last scrolled. A view not
+    /// shown before starts at the newest line when following, else the top.
     pub fn set_view(&mut self, view: ViewKind) {
-        self.active_view = view;
+        if view != self.active_view {
+            if self.json_tree_active() {
+                // Leave the tree, so the saved offset counts lines again
+                self.toggle_json_tree();
+            }
+            self.view_scroll.insert(self.active_view.name(), self.scroll_offset);
+            let saved = self.view_scroll.get(&view.name()).copied();
+            let old = std::mem::replace(&mut self.active_view, view);
+            if old == ViewKind::Json {
+                // Only the Json view reads the cache; it refills on return
+                self.json_cache.get_mut().clear();
+            }
+            self.previous_view = Some(old);
+            // Clamped to the new view's lines (the Bookmarks view shows fewer)
+            let offset = match saved {
+                Some(offset) => offset,
+                None if self.follow_mode => self.newest_position(),
+                None => 0,
+            };
+            self.scroll_offset = offset.min(self.get_display_count().saturating_sub(1));
+        }
+    }
+
+    /// Swap the active view with the previously active one (like tmux's last-window)
+    pub fn toggle_last_view(&mut self) {
+        if let Some(previous) = self.previous_view.take() {
+            self.set_view(previous);
+        }
+    }
+
+    /// Put a message in the status bar, colored by `kind`
+    pub fn set_status(&mut self, kind: StatusKind, message: String) {
+        self.status_kind = kind;
+        self.last_model_response = Some(message);
     }
 
-    pub fn set_model_response(&mut self, response: String) {
-        self.last_model_response = Some(response);
+    /// True once an in-flight analysis has gone well past its timeout without
+    /// a result, meaning the result was lost
+    pub fn analysis_timed_out(&self) -> bool {
+        self.analysis_started
+            .map(|started| started.elapsed() > ANALYSIS_TIMEOUT + ANALYSIS_GRACE)
+            .unwrap_or(false)
     }
 
+    /// Whether the line at a buffer index came from stderr
+    pub fn is_stderr(&self, index: usize) -> bool {
+        !self.stderr_lines.is_empty() && self.stderr_lines.contains(&(self.dropped_lines + index))
+    }
+
+    /// Whether something on screen changes with time alone (the analysis
+    /// spinner, or the count of lines queued while paused), so the UI needs
+    /// periodic redraws even when idle
+    pub fn is_animating(&self) -> bool {
+        self.analysis_started.is_some() || self.paused
+    }
+
+    /// Total lines received so far, including those dropped from the buffer
+    pub fn total_lines(&self) -> usize {
+        self.dropped_lines + self.log_buffer.len()
+    }
+
+    /// Whether enough lines have arrived since the last automatic analysis
+    /// to start another. Never while one is running or a range is selected
+    /// (pressing `a` analyzes the range, which an automatic run shouldn't);
+    /// it comes due again once those end.
+    pub fn auto_analyze_due(&self) -> bool {
+        match self.auto_analyze_every {
+            Some(every) => {
+                self.analysis_started.is_none()
+                    && self.range.is_none()
+                    && self.total_lines() >= self.last_auto_analyzed_count + every
+            }
+            None => false,
+        }
+    }
+
+    /// Lines the local heuristic picks a view from when the AI can't: the
+    /// range selection, or else the newest ai_sample_size lines. Never just
+    /// a follow-up's new lines, which may be too few to tell anything.
+    pub fn heuristic_logs(&self) -> Vec<String> {
+        self.range_logs().unwrap_or_else(|| {
+            let start = self.log_buffer.len().saturating_sub(self.ai_sample_size);
+            self.log_buffer.range(start..).cloned().collect()
+        })
+    }
+
+    /// Lines to send on the next analysis: the newest ai_sample_size for the
+    /// first request, or only what arrived since the last one (up to as
+    /// many) for follow-ups. Only these are copied out of the buffer for the
+    /// analysis task, since that is all analyze_logs sends.
+    pub fn ai_logs_to_send(&self) -> Vec<String> {
+        let count = if self.ai_history.is_empty() {
+            self.ai_sample_size
+        } else {
+            self.total_lines().saturating_sub(self.ai_lines_seen).min(self.ai_sample_size)
+        };
+        let start = self.log_buffer.len().saturating_sub(count);
+        self.log_buffer.range(start..).cloned().collect()
+    }
+
+    /// Remember a completed analysis exchange, keeping only the most recent few
+    pub fn record_ai_exchange(&mut self, exchange: Vec<ChatMessage>, lines_seen: usize) {
+        self.ai_history.extend(exchange);
+        let max_messages = MAX_AI_EXCHANGES * 2;
+        if self.ai_history.len() > max_messages {
+            let excess = self.ai_history.len() - max_messages;
+            self.ai_history.drain(..excess);
+        }
+        self.ai_lines_seen = lines_seen;
+    }
+
+    /// Scroll wide rows left or right. Scrolling right stops once the end
+    /// of the widest row is in view, as each view measures it while drawing
+    /// (see h_scroll_max); views with fixed columns don't scroll at all.
+    pub fn scroll_horizontal(&mut self, right: bool) {
+        self.horizontal_offset = if right {
+            (self.horizontal_offset + HORIZONTAL_STEP).min(self.h_scroll_max.get())
+        } else {
+            self.horizontal_offset.saturating_sub(HORIZONTAL_STEP)
+        };
+    }
+
+    /// Render one more level of nested JSON
+    pub fn increase_json_depth(&mut self) {
+        self.json_max_depth = (self.json_max_depth + 1).min(MAX_JSON_DEPTH);
+    }
+
+    /// Render one less level of nested JSON
+    pub fn decrease_json_depth(&mut self) {
+        self.json_max_depth = self.json_max_depth.saturating_sub(1);
+    }
+
+    pub fn increase_ai_sample_size(&mut self) {
+        self.ai_sample_size = (self.ai_sample_size + AI_SAMPLE_STEP).min(MAX_AI_SAMPLE_SIZE);
+    }
+
+    pub fn decrease_ai_sample_size(&mut self) {
+        self.ai_sample_size = self.ai_sample_size.saturating_sub(AI_SAMPLE_STEP).max(MIN_AI_SAMPLE_SIZE);
+    }
+
+    /// Scroll toward the top. Moving away from the newest line ends follow mode.
     pub fn scroll_up(&mut self, amount: usize) {
+        if !self.reverse {
+            self.follow_mode = false;
+        }
         if self.scroll_offset > 0 {
             self.scroll_offset = self.scroll_offset.saturating_sub(amount);
         }
     }
 
     pub fn scroll_down(&mut self, amount: usize, max_lines: usize) {
+        if self.reverse {
+            self.follow_mode = false;
+        }
         let max_scroll = max_lines.saturating_sub(1);
         if self.scroll_offset < max_scroll {
             self.scroll_offset = (self.scroll_offset + amount).min(max_scroll);
@@ -97,7 +707,9 @@ impl AppState {
     }
 
     pub fn get_display_count(&self) -> usize {
-        if !self.filtered_indices.is_empty() {
+        if self.active_view == ViewKind::Bookmarks || self.showing_context() || self.deduping() {
+            self.get_display_logs().len()
+        } else if self.has_filter() {
             self.filtered_indices.len()
         } else {
             self.log_buffer.len()
@@ -110,29 +722,405 @@ impl AppState {
             // Extract filter text from selected line
             let line = &self.log_buffer[index];
             // Try to extract meaningful text (word, value, etc.)
-            self.filter_text = extract_filter_text(line);
+            self.filter = extract_filter_text(line).map(FilterMode::literal);
+            self.filter_field = None;
             self.update_filter();
         }
     }
 
+    /// Filter to all lines sharing the logger/module of the given line.
+    /// Returns the matched `key:value`, or None if the line has no logger field.
+    pub fn filter_by_logger(&mut self, index: usize) -> Option<String> {
+        let keys = self.logger_keys.clone();
+        self.filter_by_first_field(index, &keys)
+    }
+
+    /// Filter to every line of the same request or trace as the line at
+    /// `index`: the lines carrying its trace ID (the first of trace_keys it
+    /// has). Returns a "key:value" label, or None if the line has no ID.
+    pub fn filter_by_trace(&mut self, index: usize) -> Option<String> {
+        let keys = self.trace_keys.clone();
+        self.filter_by_first_field(index, &keys)
+    }
+
+    /// Filter on the first of `keys` the line at `index` has, by its value
+    fn filter_by_first_field(&mut self, index: usize, keys: &[String]) -> Option<String> {
+        let line = self.log_buffer.get(index)?;
+        let (key, value) = keys
+            .iter()
+            .find_map(|key| utils::extract_field(line, key).map(|value| (key.clone(), value)))?;
+        self.selected_index = Some(index);
+        self.filter = Some(FilterMode::Literal(value.clone()));
+        self.filter_field = Some(key.clone());
+        self.update_filter();
+        Some(format!("{}:{}", key, value))
+    }
+
     pub fn clear_selection(&mut self) {
+        // Remember the filter so it can be re-applied
+        if let Some(filter) = self.filter.take() {
+            self.last_filter = Some((filter, self.filter_field.take()));
+        }
         self.selected_index = None;
-        self.filter_text = None;
+        self.range = None;
+        self.filter = None;
+        self.filter_field = None;
+        self.pinned_terms.clear();
         self.filtered_indices.clear();
     }
 
-    fn update_filter(&mut self) {
-        if let Some(ref filter) = self.filter_text {
-            if filter.is_empty() {
-                self.filtered_indices.clear();
-                return;
+    /// Filter by a typed term: a substring, regex, or JSON path expression
+    /// such as `$.request.status >= 500`
+    pub fn set_filter(&mut self, filter: FilterMode) {
+        self.selected_index = None;
+        self.filter = Some(filter);
+        self.filter_field = None;
+        self.update_filter();
+    }
+
+    /// Write evicted lines to the archive if the input has been quiet for
+    /// `idle` (or `force`, at exit). A failure is reported in the status bar.
+    pub fn flush_archive(&mut self, idle: Duration, force: bool) {
+        let Some(ref mut archive) = self.archive else {
+            return;
+        };
+        if !force && !archive.should_flush(idle) {
+            return;
+        }
+        if let Err(e) = archive.flush() {
+            let message = format!("Archive write to {} failed: {}", archive.path().display(), e);
+            self.set_status(StatusKind::Error, message);
+        }
+    }
+
+    /// Open the `/` input box, starting from the current filter as a regex
+    pub fn open_filter_input(&mut self) {
+        let text = match (&self.filter, &self.filter_field) {
+            (Some(FilterMode::Literal(text)), None) => utils::regex_escape(text),
+            (Some(filter), None) => filter.text().to_string(),
+            _ => String::new(),
+        };
+        self.filter_before_input = Some((self.filter.clone(), self.filter_field.clone(), self.selected_index));
+        self.input_mode = InputMode::FilterInput(text);
+        self.update_filter_input();
+    }
+
+    /// Open the `h` prompt asking which field to chart, starting from the
+    /// last one charted
+    pub fn open_histogram_prompt(&mut self) {
+        let text = self.histogram.take().map(|sample| sample.field).unwrap_or_default();
+        self.input_mode = InputMode::HistogramField(text);
+    }
+
+    /// Apply the text in the `/` input box as the filter. While it doesn't
+    /// compile the last valid filter stays in place and the error is shown.
+    pub fn update_filter_input(&mut self) {
+        let InputMode::FilterInput(ref text) = self.input_mode else {
+            return;
+        };
+        self.filter_input_error = None;
+        if text.is_empty() {
+            self.selected_index = None;
+            self.filter = None;
+            self.filter_field = None;
+            self.update_filter();
+            return;
+        }
+        match FilterMode::pattern(text) {
+            Ok(pattern) => self.set_filter(pattern),
+            Err(e) => self.filter_input_error = Some(e),
+        }
+    }
+
+    /// Close the input box and act on its text. An empty filter clears
+    /// filtering (so `u` brings the old one back); an invalid regex restores
+    /// the previous filter and is reported in the status bar.
+    pub fn commit_input(&mut self) {
+        match std::mem::replace(&mut self.input_mode, InputMode::Normal) {
+            InputMode::FilterInput(text) if text.is_empty() => {
+                self.restore_filter_before_input();
+                self.clear_selection();
             }
-            
+            InputMode::FilterInput(_) => match self.filter_input_error.take() {
+                Some(e) => {
+                    self.restore_filter_before_input();
+                    self.set_status(StatusKind::Error, e);
+                }
+                None => self.filter_before_input = None,
+            },
+            InputMode::HistogramField(field) => {
+                let field = field.trim();
+                if !field.is_empty() {
+                    self.histogram = Some(FieldSample::collect(field, self.log_buffer.iter()));
+                }
+            }
+            InputMode::LineNumber(text) => {
+                let message = match text.parse::<usize>() {
+                    Ok(number) => self.goto_line(number),
+                    Err(_) if text.is_empty() => None,
+                    Err(_) => Some(format!("Not a line number: {}", text)),
+                };
+                if let Some(message) = message {
+                    self.set_status(StatusKind::Error, message);
+                }
+            }
+            InputMode::SavePath(text) => {
+                let text = text.trim();
+                if !text.is_empty() {
+                    self.export_to(&export::expand_home(text));
+                }
+            }
+            InputMode::Setting(setting, text) => {
+                let (kind, message) = match self.apply_setting(setting, text.trim()) {
+                    Ok(value) => match self.config_path {
+                        Some(ref path) => match config::save_setting(path, setting, &value) {
+                            Ok(()) => (StatusKind::Info, format!("Saved {}: {}", setting.label(), value)),
+                            Err(e) => (
+                                StatusKind::Error,
+                                format!("Changed {} for this session, but couldn't save it: {:#}", setting.label(), e),
+                            ),
+                        },
+                        None => (
+                            StatusKind::Info,
+                            format!("Changed {} for this session (no config directory to save to)", setting.label()),
+                        ),
+                    },
+                    Err(e) => (StatusKind::Error, e),
+                };
+                self.set_status(kind, message);
+            }
+            InputMode::Normal => {}
+        }
+    }
+
+    /// Scroll to a line by its number (as shown in the line-number gutter) and
+    /// mark it as a one-line range. Numbers before the oldest kept line or
+    /// past the newest are clamped to them. Returns a warning for the status
+    /// bar if the line can't be shown as asked.
+    fn goto_line(&mut self, number: usize) -> Option<String> {
+        if self.log_buffer.is_empty() {
+            return Some("No lines yet".to_string());
+        }
+        let first = self.dropped_lines + 1;
+        let last = self.total_lines();
+        let (index, warning) = if number < first {
+            (0, Some(format!("Line {} has left the buffer; went to the oldest kept line, {}", number, first)))
+        } else if number > last {
+            (self.log_buffer.len() - 1, Some(format!("Only {} lines so far; went to the last one", last)))
+        } else {
+            (number - first, None)
+        };
+        let position = if self.json_tree_active() {
+            views::json_tree_rows(self).iter().position(|row| row.index == index)
+        } else {
+            self.display_position(index)
+        };
+        let Some(position) = position else {
+            return Some(format!("Line {} is hidden by the filter", first + index));
+        };
+        self.follow_mode = false;
+        self.scroll_offset = position;
+        self.range = Some((index, index));
+        warning
+    }
+
+    /// Open the input box for the setting highlighted on the `S` screen,
+    /// starting from its current value
+    pub fn open_setting_input(&mut self) {
+        if let Some(&setting) = self.settings_screen.and_then(|row| Setting::ALL.get(row)) {
+            self.input_mode = InputMode::Setting(setting, self.setting_value(setting));
+        }
+    }
+
+    /// A setting's current value, written the way it is typed and saved
+    pub fn setting_value(&self, setting: Setting) -> String {
+        let color_pair = |fg: Color, bg: Color| format!("{}:{}", fg, bg).to_lowercase();
+        match setting {
+            Setting::Model => self.ai_model.clone(),
+            Setting::BaseUrl => self.ai_base_url.clone(),
+            Setting::MatchColor => color_pair(self.theme.match_fg, self.theme.match_bg),
+            Setting::SelectedColor => color_pair(self.theme.selected_fg, self.theme.selected_bg),
+            Setting::BufferCapacity => self.buffer_capacity.to_string(),
+            Setting::SampleSize => self.ai_sample_size.to_string(),
+        }
+    }
+
+    /// Check and apply a value typed for a setting, returning it as it
+    /// should be saved. A smaller buffer sheds its oldest lines as the next
+    /// line arrives.
+    fn apply_setting(&mut self, setting: Setting, text: &str) -> Result<String, String> {
+        let whole_number = |min: usize, max: usize| match text.parse::<usize>() {
+            Ok(n) if (min..=max).contains(&n) => Ok(n),
+            _ if max == usize::MAX => Err(format!("{} must be a whole number of at least {}", setting.label(), min)),
+            _ => Err(format!("{} must be a whole number from {} to {}", setting.label(), min, max)),
+        };
+        match setting {
+            Setting::Model if text.is_empty() => return Err("Model can't be blank".to_string()),
+            Setting::Model => self.ai_model = text.to_string(),
+            Setting::BaseUrl if text.is_empty() => self.ai_base_url = crate::openai::DEFAULT_BASE_URL.to_string(),
+            Setting::BaseUrl => self.ai_base_url = text.to_string(),
+            Setting::MatchColor | Setting::SelectedColor => {
+                let (fg, bg) = views::parse_color_pair(text)?;
+                let (theme_fg, theme_bg) = match setting {
+                    Setting::MatchColor => (&mut self.theme.match_fg, &mut self.theme.match_bg),
+                    _ => (&mut self.theme.selected_fg, &mut self.theme.selected_bg),
+                };
+                *theme_fg = fg;
+                *theme_bg = bg.unwrap_or(*theme_bg);
+            }
+            Setting::BufferCapacity => self.buffer_capacity = whole_number(1, usize::MAX)?,
+            Setting::SampleSize => self.ai_sample_size = whole_number(MIN_AI_SAMPLE_SIZE, MAX_AI_SAMPLE_SIZE)?,
+        }
+        Ok(text.to_string())
+    }
+
+    /// Close the input box without acting on it, putting back the filter
+    /// that was active when it opened
+    pub fn cancel_input(&mut self) {
+        if matches!(self.input_mode, InputMode::FilterInput(_)) {
+            self.restore_filter_before_input();
+        }
+        self.input_mode = InputMode::Normal;
+    }
+
+    fn restore_filter_before_input(&mut self) {
+        self.filter_input_error = None;
+        if let Some((filter, field, selected)) = self.filter_before_input.take() {
+            self.filter = filter;
+            self.filter_field = field;
+            self.selected_index = selected;
+            self.update_filter();
+        }
+    }
+
+    /// Keep the current filter term as an extra include term and release the
+    /// selection, so `f` can add another term on top. Returns false if no
+    /// filter is active.
+    pub fn pin_filter(&mut self) -> bool {
+        let Some(term) = self.filter.take().filter(|t| !t.is_empty()) else {
+            return false;
+        };
+        if !self.pinned_terms.contains(&term) {
+            self.pinned_terms.push(term);
+        }
+        self.selected_index = None;
+        self.filter_field = None;
+        self.update_filter();
+        true
+    }
+
+    /// Whether any filter term (pinned or current) is active
+    pub fn has_filter(&self) -> bool {
+        !self.pinned_terms.is_empty() || self.filter.as_ref().is_some_and(|f| !f.is_empty())
+    }
+
+    /// Re-apply the filter that was active before the last clear.
+    /// Returns false if there is nothing to restore.
+    pub fn restore_last_filter(&mut self) -> bool {
+        let Some((filter, field)) = self.last_filter.take() else {
+            return false;
+        };
+        // Swap so pressing the key again after a clear keeps working
+        if let Some(current) = self.filter.take() {
+            self.last_filter = Some((current, self.filter_field.take()));
+        }
+        self.selected_index = None;
+        self.filter = Some(filter);
+        self.filter_field = field;
+        self.update_filter();
+        true
+    }
+
+    /// Human-readable description of the active filter for view titles
+    pub fn filter_label(&self) -> Option<String> {
+        let mut terms: Vec<String> = self.pinned_terms.iter().map(FilterMode::label).collect();
+        if let Some(ref filter) = self.filter {
+            terms.push(match self.filter_field {
+                Some(ref field) => format!("{}:{}", field, filter.text()),
+                None => filter.label(),
+            });
+        }
+        if terms.is_empty() {
+            None
+        } else {
+            Some(terms.join("' | '"))
+        }
+    }
+
+    /// The active filter as a ready-to-paste grep command (or jq, when a
+    /// term is a JSON path expression)
+    pub fn filter_as_grep(&self) -> Option<String> {
+        let filter = self.filter.as_ref().filter(|f| !f.is_empty());
+        let case_flag = if self.ignore_case { "-i " } else { "" };
+        if let (Some(filter), Some(field), true) = (filter, &self.filter_field, self.pinned_terms.is_empty()) {
+            // Match the field in JSON ("key": "value"), logfmt (key=value) or key: value form
+            let key = utils::regex_escape(field);
+            let value = utils::regex_escape(filter.text());
+            let pattern = format!(
+                "\"{key}\"[[:space:]]*:[[:space:]]*\"?{value}\"?|(^|[[:space:]]){key}(=|:[[:space:]]+)\"?{value}\"?",
+            );
+            return Some(format!("grep {}-E {}", case_flag, utils::shell_quote(&pattern)));
+        }
+        let terms = self.pinned_terms.iter().chain(filter);
+        if let Some(jq) = query::terms_as_jq(terms.clone(), self.ignore_case) {
+            return Some(jq);
+        }
+        let terms: Vec<&FilterMode> = terms.collect();
+        match terms.as_slice() {
+            [] => None,
+            [FilterMode::Literal(text)] => Some(format!("grep {}-F {}", case_flag, utils::shell_quote(text))),
+            [FilterMode::Regex(regex)] => Some(format!("grep {}-E {}", case_flag, utils::shell_quote(regex.as_str()))),
+            _ if terms.iter().all(|t| matches!(t, FilterMode::Literal(_))) => {
+                // Any of several terms: one fixed-string pattern per term
+                let patterns: Vec<String> = terms
+                    .iter()
+                    .map(|term| format!("-e {}", utils::shell_quote(term.text())))
+                    .collect();
+                Some(format!("grep {}-F {}", case_flag, patterns.join(" ")))
+            }
+            _ => {
+                // A mix of literals and regexes: escape the literals
+                let patterns: Vec<String> = terms
+                    .iter()
+                    .map(|term| match term {
+                        FilterMode::Regex(regex) => regex.as_str().to_string(),
+                        other => utils::regex_escape(other.text()),
+                    })
+                    .map(|pattern| format!("-e {}", utils::shell_quote(&pattern)))
+                    .collect();
+                Some(format!("grep {}-E {}", case_flag, patterns.join(" ")))
+            }
+        }
+    }
+
+    /// Whether a line matches the active filter. Plain filters match any
+    /// substring, regexes anywhere in the line, `$.path` expressions are
+    /// evaluated against JSON lines, and field filters match the value of a
+    /// structured field.
+    fn line_matches_filter(&self, line: &str) -> bool {
+        let current = match self.filter {
+            Some(ref filter) if !filter.is_empty() => match self.filter_field {
+                Some(ref field) => utils::extract_field(line, field).is_some_and(|value| {
+                    if self.ignore_case {
+                        value.to_lowercase() == filter.text().to_lowercase()
+                    } else {
+                        value == filter.text()
+                    }
+                }),
+                None => filter.is_match(line, self.ignore_case),
+            },
+            _ => false,
+        };
+        current || self.pinned_terms.iter().any(|term| term.is_match(line, self.ignore_case))
+    }
+
+    fn update_filter(&mut self) {
+        if self.has_filter() {
             self.filtered_indices = self.log_buffer
                 .iter()
                 .enumerate()
                 .filter_map(|(idx, line)| {
-                    if line.contains(filter) {
+                    if self.line_matches_filter(line) {
                         Some(idx)
                     } else {
                         None
@@ -147,11 +1135,91 @@ impl AppState {
     }
 
     pub fn get_display_logs(&self) -> Vec<(usize, &String)> {
+        if !self.deduping() {
+            return self.displayed_lines();
+        }
+        self.get_display_runs().into_iter().map(|(idx, line, _)| (idx, line)).collect()
+    }
+
+    /// Every line on screen, in the order shown, for writing to a file:
+    /// the whole buffer without a filter, the matching lines (and their
+    /// context) with one. Runs folded by dedupe are written out in full.
+    pub fn export_lines(&self) -> Vec<&String> {
+        self.displayed_lines().into_iter().map(|(_, line)| line).collect()
+    }
+
+    /// Write the lines on screen (see export_lines) to `path`, reporting how
+    /// it went in the status bar
+    pub fn export_to(&mut self, path: &Path) {
+        let lines = self.export_lines();
+        let (kind, message) = match export::write_lines(path, lines.iter().copied()) {
+            Ok(()) => (StatusKind::Info, format!("Saved {} line(s) to {}", lines.len(), path.display())),
+            Err(e) => (StatusKind::Error, format!("Save failed: {:#}", e)),
+        };
+        self.set_status(kind, message);
+    }
+
+    /// The displayed rows, each with how many identical lines in a row it
+    /// stands for. Without dedupe every count is 1; with it, a run shows as
+    /// its newest line.
+    pub fn get_display_runs(&self) -> Vec<(usize, &String, usize)> {
+        let lines = self.displayed_lines();
+        if !self.deduping() {
+            return lines.into_iter().map(|(idx, line)| (idx, line, 1)).collect();
+        }
+        let mut runs: Vec<(usize, &String, usize)> = Vec::new();
+        for (idx, line) in lines {
+            match runs.last_mut() {
+                Some((run_idx, run_line, count)) if *run_line == line => {
+                    *run_idx = (*run_idx).max(idx);
+                    *count += 1;
+                }
+                _ => runs.push((idx, line, 1)),
+            }
+        }
+        runs
+    }
+
+    /// Whether the Plain view is collapsing repeated lines
+    pub fn deduping(&self) -> bool {
+        self.dedupe && self.active_view == ViewKind::Plain
+    }
+
+    /// Turn dedupe on or off, keeping the current line in view (or, coming
+    /// out of a collapsed run, the newest line of it)
+    pub fn toggle_dedupe(&mut self) {
+        let current = self.current_index();
+        self.dedupe = !self.dedupe;
+        let position = current.and_then(|idx| self.display_position(idx).or_else(|| self.run_position(idx)));
+        if let Some(position) = position {
+            self.scroll_offset = position;
+        }
+    }
+
+    /// Row of the collapsed run a buffer index is part of
+    fn run_position(&self, index: usize) -> Option<usize> {
+        let mut row = 0;
+        let mut previous: Option<&String> = None;
+        for (idx, line) in self.displayed_lines() {
+            if previous.is_some_and(|previous| previous != line) {
+                row += 1;
+            }
+            if idx == index {
+                return Some(row);
+            }
+            previous = Some(line);
+        }
+        None
+    }
+
+    /// Every line the filter, bookmarks and ordering leave on screen,
+    /// before repeats are collapsed
+    fn displayed_lines(&self) -> Vec<(usize, &String)> {
         // Return logs with their indices, applying filter if active
-        let logs_to_show: Vec<(usize, &String)> = if !self.filtered_indices.is_empty() {
-            self.filtered_indices
-                .iter()
-                .map(|&idx| (idx, &self.log_buffer[idx]))
+        let mut logs_to_show: Vec<(usize, &String)> = if self.has_filter() {
+            self.filtered_with_context()
+                .into_iter()
+                .map(|idx| (idx, &self.log_buffer[idx]))
                 .collect()
         } else {
             self.log_buffer
@@ -160,8 +1228,322 @@ impl AppState {
                 .collect()
         };
 
+        if self.active_view == ViewKind::Bookmarks {
+            logs_to_show.retain(|&(idx, _)| self.is_bookmarked(idx));
+        }
+
+        // time_order has every line in order already; keep the ones shown
+        if self.time_sort {
+            let mut shown = vec![false; self.log_buffer.len()];
+            for &(idx, _) in &logs_to_show {
+                shown[idx] = true;
+            }
+            logs_to_show = self
+                .time_order
+                .iter()
+                .map(|&(_, line)| line - self.dropped_lines)
+                .filter(|&idx| shown[idx])
+                .map(|idx| (idx, &self.log_buffer[idx]))
+                .collect();
+        }
+
+        // Newest-at-top layout
+        if self.reverse {
+            logs_to_show.reverse();
+        }
+
         logs_to_show
     }
+
+    /// Whether display rows can't be worked out from buffer indices alone:
+    /// sorted by time, only the bookmarked lines shown, context lines mixed
+    /// in with the filter matches, or repeated lines collapsed
+    fn rows_reordered(&self) -> bool {
+        self.time_sort || self.active_view == ViewKind::Bookmarks || self.showing_context() || self.deduping()
+    }
+
+    /// Whether lines around the filter matches are being shown too
+    fn showing_context(&self) -> bool {
+        self.context_lines > 0 && self.has_filter()
+    }
+
+    /// The filter matches plus context_lines either side of each, in buffer
+    /// order. Overlapping stretches are merged so no line appears twice.
+    fn filtered_with_context(&self) -> Vec<usize> {
+        if !self.showing_context() {
+            return self.filtered_indices.clone();
+        }
+        let last = self.log_buffer.len().saturating_sub(1);
+        let mut indices: Vec<usize> = Vec::new();
+        for &idx in &self.filtered_indices {
+            let start = idx.saturating_sub(self.context_lines);
+            let start = indices.last().map_or(start, |&prev| start.max(prev + 1));
+            indices.extend(start..=(idx + self.context_lines).min(last));
+        }
+        indices
+    }
+
+    /// Whether a displayed line is only there as context for a nearby match
+    pub fn is_context(&self, index: usize) -> bool {
+        self.showing_context() && self.filtered_indices.binary_search(&index).is_err()
+    }
+
+    /// Show more (or fewer) lines around each filter match
+    pub fn change_context_lines(&mut self, more: bool) {
+        let current = self.current_index();
+        self.context_lines = if more {
+            (self.context_lines + 1).min(MAX_CONTEXT_LINES)
+        } else {
+            self.context_lines.saturating_sub(1)
+        };
+        if let Some(position) = current.and_then(|idx| self.display_position(idx)) {
+            self.scroll_offset = position;
+        }
+    }
+
+    /// Display row of a buffer index, respecting the filter and reversed order
+    pub fn display_position(&self, index: usize) -> Option<usize> {
+        if self.rows_reordered() {
+            return self.get_display_logs().iter().position(|&(i, _)| i == index);
+        }
+        let pos = if self.has_filter() {
+            self.filtered_indices.iter().position(|&i| i == index)?
+        } else if index < self.log_buffer.len() {
+            index
+        } else {
+            return None;
+        };
+        if self.reverse {
+            Some(self.get_display_count() - 1 - pos)
+        } else {
+            Some(pos)
+        }
+    }
+
+    /// Buffer index shown at a display row, respecting the filter and reversed order
+    pub fn index_at_display(&self, position: usize) -> Option<usize> {
+        if self.rows_reordered() {
+            return self.get_display_logs().get(position).map(|&(i, _)| i);
+        }
+        let count = self.get_display_count();
+        if position >= count {
+            return None;
+        }
+        let pos = if self.reverse { count - 1 - position } else { position };
+        if self.has_filter() {
+            self.filtered_indices.get(pos).copied()
+        } else {
+            Some(pos)
+        }
+    }
+
+    /// The selected line, or else the line at the current scroll position
+    pub fn current_index(&self) -> Option<usize> {
+        self.selected_index.or_else(|| {
+            if self.json_tree_active() {
+                let rows = views::json_tree_rows(self);
+                return rows.get(self.scroll_offset.min(rows.len().saturating_sub(1))).map(|row| row.index);
+            }
+            let count = self.get_display_count();
+            if count == 0 {
+                None
+            } else {
+                self.index_at_display(self.scroll_offset.min(count - 1))
+            }
+        })
+    }
+
+    /// Scroll to the next (or previous) displayed line that matches the
+    /// filter, counting from the current line (the selection, if there is
+    /// one, which moves along). Returns false if there is none that way.
+    pub fn jump_to_match(&mut self, forward: bool) -> bool {
+        if self.json_tree_active() {
+            return false;
+        }
+        let current_index = self.current_index();
+        let logs = self.get_display_logs();
+        if logs.is_empty() {
+            return false;
+        }
+        let current = current_index
+            .and_then(|idx| logs.iter().position(|&(i, _)| i == idx))
+            .unwrap_or_else(|| self.scroll_offset.min(logs.len() - 1));
+        let matches = |&(_, (_, line)): &(usize, &(usize, &String))| self.line_matches_filter(line);
+        let found = if forward {
+            logs.iter().enumerate().skip(current + 1).find(matches)
+        } else {
+            logs[..current].iter().enumerate().rev().find(matches)
+        };
+        let Some((position, &(index, _))) = found else {
+            return false;
+        };
+        // Away from the newest line, as with scrolling
+        if forward == self.reverse {
+            self.follow_mode = false;
+        }
+        if self.selected_index.is_some() {
+            self.selected_index = Some(index);
+        }
+        self.scroll_offset = position;
+        true
+    }
+
+    /// Whether the line at a buffer index is bookmarked
+    pub fn is_bookmarked(&self, index: usize) -> bool {
+        !self.bookmarks.is_empty() && self.bookmarks.contains(&(self.dropped_lines + index))
+    }
+
+    /// Bookmark the current line, or remove its bookmark. Returns its line
+    /// number (as in the gutter) and whether it is now bookmarked.
+    pub fn toggle_bookmark(&mut self) -> Option<(usize, bool)> {
+        let line = self.dropped_lines + self.current_index()?;
+        let added = self.bookmarks.insert(line);
+        if !added {
+            self.bookmarks.remove(&line);
+        }
+        Some((line + 1, added))
+    }
+
+    /// Scroll to the next (or previous) displayed bookmark, wrapping around
+    /// at either end. Returns false if no bookmarked line is displayed.
+    pub fn jump_to_bookmark(&mut self, forward: bool) -> bool {
+        if self.json_tree_active() {
+            return false;
+        }
+        let positions: Vec<usize> = self
+            .get_display_logs()
+            .iter()
+            .enumerate()
+            .filter(|(_, &(idx, _))| self.is_bookmarked(idx))
+            .map(|(position, _)| position)
+            .collect();
+        let current = self.scroll_offset;
+        let found = if forward {
+            positions.iter().find(|&&p| p > current).or(positions.first())
+        } else {
+            positions.iter().rev().find(|&&p| p < current).or(positions.last())
+        };
+        let Some(&position) = found else {
+            return false;
+        };
+        if position != self.newest_position() {
+            self.follow_mode = false;
+        }
+        self.scroll_offset = position;
+        true
+    }
+
+    /// Move the selection one row up or down on screen, keeping it in view.
+    /// In reversed mode "up" is toward newer lines.
+    pub fn move_selection(&mut self, up: bool) {
+        let Some(selected) = self.selected_index else {
+            return;
+        };
+        let target = if self.rows_reordered() {
+            // Rows next to each other on screen may be far apart in the buffer
+            if up != self.reverse {
+                self.follow_mode = false;
+            }
+            let position = self.display_position(selected);
+            let position = if up { position.and_then(|p| p.checked_sub(1)) } else { position.map(|p| p + 1) };
+            position.and_then(|p| self.index_at_display(p))
+        } else if up != self.reverse {
+            // Toward older lines, away from where follow mode keeps the view
+            self.follow_mode = false;
+            selected.checked_sub(1)
+        } else {
+            Some(selected + 1).filter(|&i| i < self.log_buffer.len())
+        };
+        if let Some(target) = target {
+            self.select_line(target);
+            // Update scroll to follow selection
+            if let Some(position) = self.display_position(target) {
+                self.scroll_offset = position;
+            }
+        }
+    }
+
+    /// Grow or shrink the range selection by one line, starting it at the
+    /// current line if there isn't one yet
+    pub fn extend_range(&mut self, up: bool) {
+        let Some((anchor, cursor)) = self
+            .range
+            .or_else(|| self.current_index().map(|idx| (idx, idx)))
+        else {
+            return;
+        };
+        let target = if up != self.reverse {
+            cursor.checked_sub(1)
+        } else {
+            Some(cursor + 1).filter(|&i| i < self.log_buffer.len())
+        };
+        let cursor = target.unwrap_or(cursor);
+        self.range = Some((anchor, cursor));
+        if let Some(position) = self.display_position(cursor) {
+            self.scroll_offset = position;
+        }
+    }
+
+    /// First and last buffer index of the range selection, in order
+    pub fn range_bounds(&self) -> Option<(usize, usize)> {
+        self.range.map(|(anchor, cursor)| (anchor.min(cursor), anchor.max(cursor)))
+    }
+
+    /// Whether a line should be drawn with the selected style
+    pub fn is_highlighted(&self, index: usize) -> bool {
+        self.selected_index == Some(index)
+            || self
+                .range_bounds()
+                .is_some_and(|(start, end)| (start..=end).contains(&index))
+    }
+
+    /// The lines covered by the range selection, if any
+    pub fn range_logs(&self) -> Option<Vec<String>> {
+        let (start, end) = self.range_bounds()?;
+        self.buffer_lines(start, end)
+    }
+
+    /// Copies of the buffered lines from start to end inclusive, or None if
+    /// they run past the end of the buffer
+    fn buffer_lines(&self, start: usize, end: usize) -> Option<Vec<String>> {
+        (start <= end && end < self.log_buffer.len()).then(|| self.log_buffer.range(start..=end).cloned().collect())
+    }
+
+    /// The lines to copy with `Y`: the range selection (or the current line)
+    /// as displayed, so with a filter active only matching (and context) lines
+    /// are included
+    pub fn copy_range_logs(&self) -> Vec<String> {
+        let Some((start, end)) = self.range_bounds().or_else(|| self.current_index().map(|idx| (idx, idx))) else {
+            return Vec::new();
+        };
+        if self.has_filter() {
+            self.filtered_with_context()
+                .into_iter()
+                .filter(|i| (start..=end).contains(i))
+                .filter_map(|i| self.log_buffer.get(i).cloned())
+                .collect()
+        } else {
+            self.buffer_lines(start, end).unwrap_or_default()
+        }
+    }
+
+    /// Switch between time and arrival order, keeping the current line in view
+    pub fn toggle_time_sort(&mut self) {
+        let current = self.current_index();
+        self.time_sort = !self.time_sort;
+        if let Some(position) = current.and_then(|idx| self.display_position(idx)) {
+            self.scroll_offset = position;
+        }
+    }
+
+    /// Toggle newest-at-top ordering, keeping the current line in view
+    pub fn toggle_reverse(&mut self) {
+        let current = self.current_index();
+        self.reverse = !self.reverse;
+        if let Some(position) = current.and_then(|idx| self.display_position(idx)) {
+            self.scroll_offset = position;
+        }
+    }
 }
 
 /// Extract meaningful text from a line for filtering
@@ -207,3 +1589,129 @@ fn extract_filter_text(line: &str) -> Option<String> {
     None
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app_with_capacity(buffer_capacity: usize) -> AppState {
+        let (_, receiver) = mpsc::channel(1);
+        let config = Config { buffer_capacity, ..Config::default() };
+        AppState::new(receiver, "test".to_string(), &config)
+    }
+
+    fn shown(app: &AppState) -> Vec<String> {
+        app.get_display_logs().into_iter().map(|(_, line)| line.clone()).collect()
+    }
+
+    #[test]
+    fn time_sort_keeps_untimed_lines_with_theirs_across_eviction() {
+        let mut app = app_with_capacity(4);
+        for line in ["ts=5 a", "ts=3 b", "trace of b", "ts=4 c", "ts=1 d"] {
+            app.add_log(line.to_string(), LineSource::Stdout);
+        }
+        app.time_sort = true;
+        // "ts=5 a" was evicted; the trace line still follows "ts=3 b"
+        assert_eq!(shown(&app), ["ts=1 d", "ts=3 b", "trace of b", "ts=4 c"]);
+
+        app.set_filter(FilterMode::Literal("b".to_string()));
+        assert_eq!(shown(&app), ["ts=3 b", "trace of b"]);
+    }
+
+    #[test]
+    fn json_tree_rows_follow_folds_and_new_lines() {
+        let mut app = app_with_capacity(10);
+        app.add_log(r#"{"a":{"b":1,"c":2}}"#.to_string(), LineSource::Stdout);
+        app.active_view = ViewKind::Json;
+        app.json_tree = true;
+        assert_eq!(views::json_tree_rows(&app).len(), 4);
+
+        // Fold `a`, the second row
+        app.scroll_offset = 1;
+        app.toggle_json_node();
+        assert_eq!(views::json_tree_rows(&app).len(), 2);
+
+        app.add_log(r#"{"a":{"d":3}}"#.to_string(), LineSource::Stdout);
+        assert_eq!(views::json_tree_rows(&app).len(), 4);
+    }
+
+    #[test]
+    fn next_match_counts_from_the_selected_line() {
+        let mut app = app_with_capacity(10);
+        for line in ["err 1", "ok", "err 2", "ok", "err 3"] {
+            app.add_log(line.to_string(), LineSource::Stdout);
+        }
+        app.filter = Some(FilterMode::Literal("err".to_string()));
+        app.context_lines = 1;
+        app.update_filter();
+        app.selected_index = Some(2);
+        assert!(app.jump_to_match(true));
+        assert_eq!(app.selected_index, Some(4));
+        assert!(!app.jump_to_match(true));
+        assert!(app.jump_to_match(false));
+        assert_eq!(app.selected_index, Some(2));
+    }
+
+    #[test]
+    fn a_new_view_starts_at_the_top_and_a_seen_one_where_it_was() {
+        let mut app = app_with_capacity(10);
+        for n in 0..5 {
+            app.add_log(format!("line {}", n), LineSource::Stdout);
+        }
+        app.scroll_offset = 3;
+        app.set_view(ViewKind::KeyValue);
+        assert_eq!(app.scroll_offset, 0);
+        app.scroll_offset = 1;
+        app.set_view(ViewKind::Plain);
+        assert_eq!(app.scroll_offset, 3);
+
+        app.follow_mode = true;
+        app.set_view(ViewKind::Logfmt);
+        assert_eq!(app.scroll_offset, 4);
+    }
+
+    #[test]
+    fn json_cache_drops_lines_with_the_buffer() {
+        let mut app = app_with_capacity(2);
+        for n in 0..2 {
+            app.add_log(format!(r#"{{"n":{}}}"#, n), LineSource::Stdout);
+        }
+        for (idx, line) in app.log_buffer.iter().enumerate() {
+            assert!(app.json_cache.borrow_mut().get_or_parse(idx, line).is_some());
+        }
+        app.add_log("not json".to_string(), LineSource::Stdout);
+        let cache = app.json_cache.borrow();
+        assert!(!cache.entries.contains_key(&0));
+        assert_eq!(cache.entries[&1].as_deref(), Some(&serde_json::json!({"n": 1})));
+    }
+
+    #[test]
+    fn leaving_the_json_view_empties_the_json_cache() {
+        let mut app = app_with_capacity(10);
+        app.add_log(r#"{"n":0}"#.to_string(), LineSource::Stdout);
+        app.set_view(ViewKind::Json);
+        assert!(app.json_cache.borrow_mut().get_or_parse(0, &app.log_buffer[0]).is_some());
+        app.set_view(ViewKind::Plain);
+        assert!(app.json_cache.borrow().entries.is_empty());
+    }
+
+    #[test]
+    fn a_failed_follow_up_guesses_from_the_whole_sample() {
+        let mut app = app_with_capacity(100);
+        for n in 0..10 {
+            app.add_log(format!(r#"{{"level":"info","n":{}}}"#, n), LineSource::Stdout);
+        }
+        let reply = ChatMessage { role: "assistant".to_string(), content: "{}".to_string() };
+        app.record_ai_exchange(vec![reply], app.total_lines());
+        app.add_log("plain text".to_string(), LineSource::Stdout);
+
+        // The follow-up only sends the new line, which alone reads as Plain
+        assert_eq!(app.ai_logs_to_send(), ["plain text"]);
+        assert!(ViewKind::guess(&app.ai_logs_to_send()) == ViewKind::Plain);
+        assert!(ViewKind::guess(&app.heuristic_logs()) == ViewKind::Json);
+
+        // A range selection is guessed from on its own
+        app.range = Some((10, 10));
+        assert_eq!(app.heuristic_logs(), ["plain text"]);
+    }
+}
@@ -0,0 +1,87 @@
+//! Archive of lines evicted from the capped buffer, so a long session can
+//! still be reviewed after the fact even though the view only keeps the
+//! most recent lines.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Archive size at which the file is rotated, unless --archive-max-size is given
+pub const DEFAULT_ARCHIVE_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// How long input must be quiet before pending lines are written, so
+/// archiving doesn't compete with a burst of incoming lines
+pub const ARCHIVE_IDLE: Duration = Duration::from_secs(2);
+
+/// Pending lines that force a write even while lines keep arriving
+const MAX_PENDING_LINES: usize = 5000;
+
+/// Evicted lines waiting to be appended to a file. When the file grows past
+/// `max_bytes` it is renamed to `<path>.1` (replacing any older one) and a
+/// new file is started.
+pub struct Archive {
+    path: PathBuf,
+    max_bytes: u64,
+    pending: Vec<String>,
+}
+
+impl Archive {
+    pub fn new(path: PathBuf, max_bytes: u64) -> Self {
+        Self {
+            path,
+            max_bytes,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Queue an evicted line for the next write
+    pub fn push(&mut self, line: String) {
+        self.pending.push(line);
+    }
+
+    /// Whether pending lines should be written now, given how long the input has been quiet
+    pub fn should_flush(&self, idle: Duration) -> bool {
+        !self.pending.is_empty() && (idle >= ARCHIVE_IDLE || self.pending.len() >= MAX_PENDING_LINES)
+    }
+
+    /// Append pending lines, rotating first if the file is full. Pending
+    /// lines are dropped even when the write fails, so a bad path can't grow
+    /// memory without bound.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let lines = std::mem::take(&mut self.pending);
+        if fs::metadata(&self.path).is_ok_and(|meta| meta.len() >= self.max_bytes) {
+            let mut rotated = self.path.clone().into_os_string();
+            rotated.push(".1");
+            fs::rename(&self.path, rotated)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        let mut writer = BufWriter::new(file);
+        for line in &lines {
+            writeln!(writer, "{}", line)?;
+        }
+        writer.flush()
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+}
+
+/// Parse a size such as `512K`, `10M`, `1G` or a plain byte count
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let (digits, multiplier) = match text.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&text[..i], 1024),
+        Some((i, 'm' | 'M')) => (&text[..i], 1024 * 1024),
+        Some((i, 'g' | 'G')) => (&text[..i], 1024 * 1024 * 1024),
+        _ => (text, 1),
+    };
+    match digits.trim().parse::<u64>() {
+        Ok(n) if n > 0 => Ok(n * multiplier),
+        _ => Err(format!("invalid size '{}' (expected e.g. 512K, 10M or 1G)", text)),
+    }
+}
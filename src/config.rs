@@ -1,6 +1,11 @@
+use crate::keymap::Keymap;
+use crate::views::{self, ViewKind};
 use anyhow::{Context, Result};
+use ratatui::style::Color;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 fn config_dir() -> Result<PathBuf> {
     let dir = dirs::config_dir()
@@ -15,20 +20,305 @@ fn key_file() -> Result<PathBuf> {
     Ok(config_dir()?.join("api_key"))
 }
 
+/// The API base URL saved in the `base_url` file in the config directory,
+/// for OpenAI-compatible servers. None if the file is missing or blank.
+pub fn get_base_url() -> Option<String> {
+    let path = config_dir().ok()?.join("base_url");
+    let url = fs::read_to_string(path).ok()?;
+    let url = url.trim();
+    (!url.is_empty()).then(|| url.to_string())
+}
+
+/// Lines kept in the buffer unless `buffer_capacity` says otherwise
+pub const DEFAULT_BUFFER_CAPACITY: usize = 2000;
+
+/// How long the main loop waits for a keypress before checking for new
+/// lines, unless `frame_rate_ms` says otherwise
+pub const DEFAULT_FRAME_RATE_MS: u64 = 50;
+
+/// Preferences from `config.toml` in the config directory (or `--config`).
+/// Every field is optional; command-line flags override them.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Lines kept in the buffer before the oldest are dropped
+    pub buffer_capacity: usize,
+    /// View for input sources without a remembered one (plain, keyvalue, logfmt, json or csv)
+    #[serde(deserialize_with = "view_name")]
+    pub default_view: Option<ViewKind>,
+    /// Longest the main loop waits for a key between checks for new lines
+    pub frame_rate_ms: u64,
+    /// Start in follow mode (as after G)
+    pub follow_on_start: bool,
+    /// Start with the line-number gutter showing (#)
+    pub line_numbers: bool,
+    /// OpenAI model, unless --model is given
+    pub model: Option<String>,
+    /// Recent lines sent with each analysis (adjustable with +/-)
+    pub sample_size: Option<usize>,
+    /// Analyze automatically every this many new lines, unless --auto-analyze is given
+    pub auto_analyze_threshold: Option<usize>,
+    pub theme: ThemeConfig,
+    /// The `[keys]` section: `action = "key"` or `action = ["key", ...]`
+    #[serde(deserialize_with = "key_bindings")]
+    pub keys: Keymap,
+    /// The file settings are read from and saved to; None without a config directory
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            buffer_capacity: DEFAULT_BUFFER_CAPACITY,
+            default_view: None,
+            frame_rate_ms: DEFAULT_FRAME_RATE_MS,
+            follow_on_start: false,
+            line_numbers: false,
+            model: None,
+            sample_size: None,
+            auto_analyze_threshold: None,
+            theme: ThemeConfig::default(),
+            keys: Keymap::default(),
+            path: None,
+        }
+    }
+}
+
+/// The `[theme]` section: colors as `FG[:BG]`, like --match-color
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    #[serde(deserialize_with = "color_pair")]
+    pub match_color: Option<(Color, Option<Color>)>,
+    #[serde(deserialize_with = "color_pair")]
+    pub selected_color: Option<(Color, Option<Color>)>,
+}
+
+impl Config {
+    /// Read the config file at `path`, or `config.toml` in the config
+    /// directory. A missing default file just means defaults; a missing
+    /// `--config` file or one that doesn't parse is an error.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match config_dir() {
+                Ok(dir) => (dir.join("config.toml"), false),
+                Err(_) => return Ok(Self::default()),
+            },
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read config file {}", path.display())),
+        };
+        let mut config: Self = toml::from_str(&text)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        if config.buffer_capacity == 0 {
+            anyhow::bail!("Invalid config file {}: buffer_capacity must be at least 1", path.display());
+        }
+        if config.auto_analyze_threshold == Some(0) {
+            anyhow::bail!("Invalid config file {}: auto_analyze_threshold must be at least 1", path.display());
+        }
+        config.path = Some(path);
+        Ok(config)
+    }
+}
+
+/// Settings listed on the `S` screen, in display order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Setting {
+    Model,
+    BaseUrl,
+    MatchColor,
+    SelectedColor,
+    BufferCapacity,
+    SampleSize,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 6] = [
+        Setting::Model,
+        Setting::BaseUrl,
+        Setting::MatchColor,
+        Setting::SelectedColor,
+        Setting::BufferCapacity,
+        Setting::SampleSize,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Setting::Model => "Model",
+            Setting::BaseUrl => "Base URL",
+            Setting::MatchColor => "Match color",
+            Setting::SelectedColor => "Selected color",
+            Setting::BufferCapacity => "Buffer capacity",
+            Setting::SampleSize => "AI sample size",
+        }
+    }
+
+    /// The setting's `[section]` (if any) and key in config.toml
+    fn toml_key(self) -> (Option<&'static str>, &'static str) {
+        match self {
+            Setting::Model => (None, "model"),
+            Setting::BaseUrl => (None, "base_url"),
+            Setting::MatchColor => (Some("theme"), "match_color"),
+            Setting::SelectedColor => (Some("theme"), "selected_color"),
+            Setting::BufferCapacity => (None, "buffer_capacity"),
+            Setting::SampleSize => (None, "sample_size"),
+        }
+    }
+}
+
+/// Save one setting, leaving the rest of the config file (comments
+/// included) as written. The base URL goes to the `base_url` file instead,
+/// where get_base_url looks for it.
+pub fn save_setting(path: &Path, setting: Setting, value: &str) -> Result<()> {
+    if setting == Setting::BaseUrl {
+        return set_base_url(value);
+    }
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read config file {}", path.display())),
+    };
+    let mut doc: toml_edit::DocumentMut = text
+        .parse()
+        .with_context(|| format!("Invalid config file {}", path.display()))?;
+    let item = match setting {
+        Setting::BufferCapacity | Setting::SampleSize => {
+            toml_edit::value(value.parse::<i64>().context("Expected a whole number")?)
+        }
+        _ => toml_edit::value(value),
+    };
+    match setting.toml_key() {
+        (Some(section), key) => {
+            doc.entry(section)
+                .or_insert(toml_edit::table())
+                .as_table_like_mut()
+                .with_context(|| format!("'{}' in {} isn't a table", section, path.display()))?
+                .insert(key, item);
+        }
+        (None, key) => doc[key] = item,
+    }
+    fs::write(path, doc.to_string())
+        .with_context(|| format!("Failed to write config file {}", path.display()))
+}
+
+/// Save the API base URL to the `base_url` file; a blank URL removes it
+fn set_base_url(url: &str) -> Result<()> {
+    let path = config_dir()?.join("base_url");
+    if url.trim().is_empty() {
+        if path.exists() {
+            fs::remove_file(&path).context("Failed to delete base URL file")?;
+        }
+        return Ok(());
+    }
+    fs::write(&path, url.trim()).context("Failed to write base URL file")
+}
+
+fn view_name<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<ViewKind>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    views::parse_builtin_view(&name).map(Some).map_err(serde::de::Error::custom)
+}
+
+fn color_pair<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<(Color, Option<Color>)>, D::Error> {
+    let spec = String::deserialize(deserializer)?;
+    views::parse_color_pair(&spec).map(Some).map_err(serde::de::Error::custom)
+}
+
+/// One key or a list of them, for an action in `[keys]`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+fn key_bindings<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Keymap, D::Error> {
+    let table = BTreeMap::<String, KeyList>::deserialize(deserializer)?;
+    let overrides = table.into_iter().map(|(action, keys)| match keys {
+        KeyList::One(key) => (action, vec![key]),
+        KeyList::Many(keys) => (action, keys),
+    });
+    Keymap::with_overrides(overrides).map_err(serde::de::Error::custom)
+}
+
+/// Environment variable checked before the key file
+const API_KEY_ENV: &str = "OPENAI_API_KEY";
+
+/// The key from OPENAI_API_KEY, if it is set and not blank
+fn env_api_key() -> Option<String> {
+    std::env::var(API_KEY_ENV)
+        .ok()
+        .map(|key| key.trim().to_string())
+        .filter(|key| !key.is_empty())
+}
+
+/// Read the API key: OPENAI_API_KEY takes precedence, then the key saved
+/// with `scry key`. Surrounding whitespace is trimmed (as in has_api_key),
+/// since a key written with `echo` ends in a newline that would otherwise
+/// end up in the Authorization header.
 pub fn get_api_key() -> Result<String> {
+    if let Some(key) = env_api_key() {
+        return Ok(key);
+    }
     let key_path = key_file()?;
-    fs::read_to_string(&key_path)
-        .context("API key not set. Run 'scry key YOUR_API_KEY' to set it.")
+    let key = fs::read_to_string(&key_path)
+        .context("API key not set. Run 'scry key YOUR_API_KEY' or set OPENAI_API_KEY.")?;
+    let key = key.trim();
+    if key.is_empty() {
+        anyhow::bail!("API key file is empty. Run 'scry key YOUR_API_KEY' or set OPENAI_API_KEY.");
+    }
+    Ok(key.to_string())
 }
 
+/// Whether a key is available from either OPENAI_API_KEY or the key file
 pub fn has_api_key() -> bool {
-    key_file()
-        .and_then(|path| {
-            fs::read_to_string(path)
-                .map(|s| !s.trim().is_empty())
-                .map_err(|e| anyhow::anyhow!("{}", e))
-        })
-        .unwrap_or(false)
+    env_api_key().is_some()
+        || key_file()
+            .and_then(|path| {
+                fs::read_to_string(path)
+                    .map(|s| !s.trim().is_empty())
+                    .map_err(|e| anyhow::anyhow!("{}", e))
+            })
+            .unwrap_or(false)
+}
+
+/// Input sources remembered in the view cache; the least recent are dropped
+const MAX_CACHED_VIEWS: usize = 50;
+
+/// One `VIEW<TAB>SOURCE` line per input source, most recently used last
+fn view_cache_file() -> Result<PathBuf> {
+    Ok(config_dir()?.join("views"))
+}
+
+/// The view name last saved for an input source, if any
+pub fn cached_view(source: &str) -> Option<String> {
+    let cache = fs::read_to_string(view_cache_file().ok()?).ok()?;
+    cache
+        .lines()
+        .rev()
+        .filter_map(|line| line.split_once('\t'))
+        .find(|(_, cached_source)| *cached_source == source)
+        .map(|(view, _)| view.to_string())
+}
+
+/// Remember the view used for an input source, replacing any older entry
+pub fn save_view(source: &str, view: &str) -> Result<()> {
+    let path = view_cache_file()?;
+    let source = source.replace(['\n', '\r'], " ");
+    let existing = fs::read_to_string(&path).unwrap_or_default();
+    let mut entries: Vec<&str> = existing
+        .lines()
+        .filter(|line| line.split_once('\t').is_some_and(|(_, cached)| cached != source))
+        .collect();
+    let entry = format!("{}\t{}", view, source);
+    entries.push(&entry);
+    let keep = entries.len().saturating_sub(MAX_CACHED_VIEWS);
+    fs::write(&path, entries[keep..].join("\n") + "\n")
+        .context("Failed to write view cache")?;
+    Ok(())
 }
 
 pub fn set_api_key(key: &str) -> Result<()> {
@@ -38,12 +328,127 @@ pub fn set_api_key(key: &str) -> Result<()> {
     Ok(())
 }
 
-pub fn delete_api_key() -> Result<()> {
+/// Whether a key file exists at all, even an empty one
+pub fn key_file_exists() -> bool {
+    key_file().map(|path| path.exists()).unwrap_or(false)
+}
+
+/// Delete the saved API key. Returns false if there was no key to delete.
+pub fn delete_api_key() -> Result<bool> {
     let key_path = key_file()?;
-    if key_path.exists() {
-        fs::remove_file(&key_path)
-            .context("Failed to delete API key file")?;
+    if !key_path.exists() {
+        return Ok(false);
     }
-    Ok(())
+    fs::remove_file(&key_path)
+        .context("Failed to delete API key file")?;
+    Ok(true)
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Mutex, MutexGuard};
+
+    /// XDG_CONFIG_HOME and OPENAI_API_KEY are process-wide, so tests that
+    /// set them take turns
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    /// An empty config directory for one test, removed when dropped
+    struct TempConfig {
+        root: PathBuf,
+        _lock: MutexGuard<'static, ()>,
+    }
+
+    impl TempConfig {
+        fn new(name: &str) -> Self {
+            let lock = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            let root = std::env::temp_dir().join(format!("scry-test-{}-{}", std::process::id(), name));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(&root).unwrap();
+            std::env::set_var("XDG_CONFIG_HOME", &root);
+            std::env::remove_var(API_KEY_ENV);
+            // Never touch the real config directory on platforms without XDG
+            assert!(config_dir().unwrap().starts_with(&root), "config dir ignores XDG_CONFIG_HOME");
+            Self { root, _lock: lock }
+        }
+    }
+
+    impl Drop for TempConfig {
+        fn drop(&mut self) {
+            std::env::remove_var(API_KEY_ENV);
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    #[test]
+    fn key_file_line_endings_are_trimmed() {
+        let _config = TempConfig::new("key-file-trim");
+        for saved in ["sk-test\n", "sk-test\r\n", "  sk-test \r\n"] {
+            fs::write(key_file().unwrap(), saved).unwrap();
+            assert_eq!(get_api_key().unwrap(), "sk-test");
+            assert!(has_api_key());
+        }
+    }
+
+    #[test]
+    fn env_key_line_endings_are_trimmed() {
+        let _config = TempConfig::new("env-key-trim");
+        for set in ["sk-env\n", "sk-env\r\n"] {
+            std::env::set_var(API_KEY_ENV, set);
+            assert_eq!(get_api_key().unwrap(), "sk-env");
+        }
+    }
+
+    #[test]
+    fn blank_key_file_is_no_key() {
+        let _config = TempConfig::new("key-file-blank");
+        fs::write(key_file().unwrap(), "\r\n").unwrap();
+        assert!(get_api_key().is_err());
+        assert!(!has_api_key());
+    }
+
+    #[test]
+    fn env_key_beats_key_file_beats_no_key() {
+        let _config = TempConfig::new("key-precedence");
+        assert!(get_api_key().is_err());
+        assert!(!has_api_key());
+
+        fs::write(key_file().unwrap(), "sk-file").unwrap();
+        assert_eq!(get_api_key().unwrap(), "sk-file");
+
+        std::env::set_var(API_KEY_ENV, "sk-env");
+        assert_eq!(get_api_key().unwrap(), "sk-env");
+
+        // A blank variable doesn't hide the saved key
+        std::env::set_var(API_KEY_ENV, "  ");
+        assert_eq!(get_api_key().unwrap(), "sk-file");
+    }
+
+    #[test]
+    fn saved_view_round_trips() {
+        let _config = TempConfig::new("view-cache");
+        assert_eq!(cached_view("tail -f app.log"), None);
+
+        save_view("tail -f app.log", "json").unwrap();
+        save_view("kubectl logs web", "logfmt").unwrap();
+        assert_eq!(cached_view("tail -f app.log").as_deref(), Some("json"));
+        assert_eq!(cached_view("kubectl logs web").as_deref(), Some("logfmt"));
+
+        // Saving again replaces the entry instead of adding a second one
+        save_view("tail -f app.log", "plain").unwrap();
+        assert_eq!(cached_view("tail -f app.log").as_deref(), Some("plain"));
+        let cache = fs::read_to_string(view_cache_file().unwrap()).unwrap();
+        assert_eq!(cache.lines().count(), 2);
+    }
+
+    #[test]
+    fn view_cache_keeps_the_most_recent_sources() {
+        let _config = TempConfig::new("view-cache-cap");
+        for n in 0..=MAX_CACHED_VIEWS {
+            save_view(&format!("source {}", n), "json").unwrap();
+        }
+        assert_eq!(cached_view("source 0"), None);
+        assert_eq!(cached_view(&format!("source {}", MAX_CACHED_VIEWS)).as_deref(), Some("json"));
+    }
+}
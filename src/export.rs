@@ -0,0 +1,43 @@
+//! Writing the lines on screen out to a file, for keeping the result of a
+//! filter after scry exits.
+
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// A new file name in the working directory, like `scry-export-20240501-134502.log`
+/// (UTC). A number is added if that name is already taken.
+pub fn export_path() -> PathBuf {
+    let seconds = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or(0);
+    let stamp = crate::utils::format_utc_compact(seconds);
+    let mut path = PathBuf::from(format!("scry-export-{}.log", stamp));
+    let mut n = 1;
+    while path.exists() {
+        n += 1;
+        path = PathBuf::from(format!("scry-export-{}-{}.log", stamp, n));
+    }
+    path
+}
+
+/// A path typed into scry, with a leading `~/` taken as the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Write `lines` to `path`, one per line, replacing any existing file
+pub fn write_lines<'a>(path: &Path, lines: impl IntoIterator<Item = &'a String>) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+    for line in lines {
+        writeln!(writer, "{}", line).with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    writer.flush().with_context(|| format!("Failed to write {}", path.display()))
+}
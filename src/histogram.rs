@@ -0,0 +1,64 @@
+//! Distribution of a numeric field across the buffer, for the histogram popup
+
+use crate::utils;
+
+/// The values of one field, snapshotted when the histogram was requested
+pub struct FieldSample {
+    pub field: String,
+    pub values: Vec<f64>,
+    /// Lines without the field, or where it isn't a number
+    pub missing: usize,
+}
+
+impl FieldSample {
+    /// Read `field` (JSON or key=value) from every line. Values with a unit
+    /// suffix such as `12.5ms` count by their leading number.
+    pub fn collect<'a>(field: &str, lines: impl Iterator<Item = &'a String>) -> Self {
+        let mut values = Vec::new();
+        let mut missing = 0;
+        for line in lines {
+            match utils::extract_field(line, field).and_then(|v| leading_number(&v)) {
+                Some(value) => values.push(value),
+                None => missing += 1,
+            }
+        }
+        Self {
+            field: field.to_string(),
+            values,
+            missing,
+        }
+    }
+
+    /// Split the value range into `count` equal-width buckets, as
+    /// (low, high, number of values). Empty if there are no values.
+    pub fn buckets(&self, count: usize) -> Vec<(f64, f64, usize)> {
+        let Some(min) = self.values.iter().copied().reduce(f64::min) else {
+            return Vec::new();
+        };
+        let max = self.values.iter().copied().fold(min, f64::max);
+        // A single distinct value gets a single bucket
+        let count = if max > min { count.max(1) } else { 1 };
+        let width = (max - min) / count as f64;
+        let mut counts = vec![0; count];
+        for &value in &self.values {
+            let slot = if width > 0.0 { ((value - min) / width) as usize } else { 0 };
+            counts[slot.min(count - 1)] += 1;
+        }
+        counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, n)| (min + width * i as f64, min + width * (i + 1) as f64, n))
+            .collect()
+    }
+}
+
+/// The number at the start of a value, e.g. 12.5 for "12.5ms"
+fn leading_number(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let end = value
+        .char_indices()
+        .find(|&(i, c)| !(c.is_ascii_digit() || c == '.' || ((c == '-' || c == '+') && i == 0) || c == 'e' || c == 'E'))
+        .map_or(value.len(), |(i, _)| i);
+    // Back off until it parses, so "1.5e" or "3." style tails don't spoil it
+    (1..=end).rev().find_map(|len| value[..len].parse::<f64>().ok()).filter(|v| v.is_finite())
+}
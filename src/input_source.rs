@@ -1,4 +1,111 @@
-use std::process;
+use crate::app::LineSource;
+use regex::Regex;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, LineWriter, Read, Write};
+use std::path::Path;
+use std::process::{self, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tokio::sync::mpsc;
+
+/// How incoming lines are cleaned up before they reach the buffer
+#[derive(Debug, Clone, Default)]
+pub struct IngestOptions {
+    /// Join JSON values pretty-printed across several lines (stdout only)
+    pub multiline_json: bool,
+    /// Noise removed from the start of every line (anchored regex)
+    pub strip_prefix: Option<Regex>,
+    /// Noise removed from the end of every line (anchored regex)
+    pub strip_suffix: Option<Regex>,
+    /// Raw copy of every line received, for reproducing problems later
+    pub record: Option<Recorder>,
+}
+
+/// Appends incoming lines to a file, shared by all reader threads. Each
+/// line is flushed as it is written so a crash still leaves a usable capture.
+#[derive(Debug, Clone)]
+pub struct Recorder(Arc<Mutex<LineWriter<File>>>);
+
+impl Recorder {
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self(Arc::new(Mutex::new(LineWriter::new(file)))))
+    }
+
+    /// Write one line; failures are ignored so recording never interrupts viewing
+    fn record(&self, line: &str) {
+        if let Ok(mut file) = self.0.lock() {
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+}
+
+impl IngestOptions {
+    /// A per-stream processor; `join_json` is false for streams like stderr
+    /// that should never be reassembled
+    pub fn processor(&self, join_json: bool) -> LineProcessor {
+        LineProcessor {
+            record: self.record.clone(),
+            strip_prefix: self.strip_prefix.clone(),
+            strip_suffix: self.strip_suffix.clone(),
+            joiner: (join_json && self.multiline_json).then(JsonJoiner::new),
+        }
+    }
+}
+
+/// Parse a --strip-prefix pattern, anchored to the start of the line
+pub fn parse_strip_prefix(pattern: &str) -> Result<Regex, String> {
+    Regex::new(&format!("^(?:{})", pattern)).map_err(|e| e.to_string())
+}
+
+/// Parse a --strip-suffix pattern, anchored to the end of the line
+pub fn parse_strip_suffix(pattern: &str) -> Result<Regex, String> {
+    Regex::new(&format!("(?:{})$", pattern)).map_err(|e| e.to_string())
+}
+
+/// Applies IngestOptions to one stream, line by line
+pub struct LineProcessor {
+    record: Option<Recorder>,
+    strip_prefix: Option<Regex>,
+    strip_suffix: Option<Regex>,
+    joiner: Option<JsonJoiner>,
+}
+
+impl LineProcessor {
+    /// Feed one physical line and get back the logical lines ready for the buffer
+    pub fn push(&mut self, line: String) -> Vec<String> {
+        if let Some(ref recorder) = self.record {
+            recorder.record(&line);
+        }
+        let line = self.strip(line);
+        match self.joiner.as_mut() {
+            Some(joiner) => joiner.push(line),
+            None => vec![line],
+        }
+    }
+
+    /// Anything still held back once the stream ends
+    pub fn finish(&mut self) -> Vec<String> {
+        match self.joiner.as_mut() {
+            Some(joiner) => joiner.finish(),
+            None => Vec::new(),
+        }
+    }
+
+    fn strip(&self, mut line: String) -> String {
+        if let Some(end) = self.strip_prefix.as_ref().and_then(|re| re.find(&line)).map(|m| m.end()) {
+            line.drain(..end);
+        }
+        if let Some(start) = self.strip_suffix.as_ref().and_then(|re| re.find(&line)).map(|m| m.start()) {
+            line.truncate(start);
+        }
+        line
+    }
+}
+
+/// Give up joining a multi-line JSON value after this many physical lines
+/// and pass them through as-is, so unbalanced braces can't stall the input
+const MAX_JSON_JOIN_LINES: usize = 1000;
 
 /// Detect the input source (file, command, or stdin)
 pub fn detect_input_source(stdin_is_tty: bool) -> String {
@@ -27,6 +134,16 @@ pub fn detect_input_source(stdin_is_tty: bool) -> String {
     "Reading from stdin".to_string()
 }
 
+/// The part of an input source description that identifies it across runs,
+/// for remembering its view. None for the generic fallbacks, which would
+/// lump unrelated inputs together.
+pub fn cache_key(input_source: &str) -> Option<&str> {
+    input_source
+        .strip_prefix("Reading from: ")
+        .or_else(|| input_source.strip_prefix("Running: "))
+        .filter(|key| !key.is_empty())
+}
+
 #[cfg(unix)]
 fn get_parent_command(ppid: i32) -> Result<String, std::io::Error> {
     // Try Linux /proc first
@@ -46,7 +163,7 @@ fn get_parent_command(ppid: i32) -> Result<String, std::io::Error> {
 
     // Fallback: try ps command (works on macOS/BSD)
     let output = process::Command::new("ps")
-        .args(&["-p", &ppid.to_string(), "-o", "command="])
+        .args(["-p", &ppid.to_string(), "-o", "command="])
         .output();
     
     if let Ok(output) = output {
@@ -90,3 +207,182 @@ fn clean_command(cmd: &str) -> String {
     }
 }
 
+
+/// Run a command and stream its stdout and stderr into the log channel,
+/// tagging each line with the stream it came from. Each stream gets its own
+/// reader thread sending into the shared channel, so lines interleave in
+/// roughly the order the command wrote them.
+pub fn spawn_command(
+    command: &[String],
+    tx: mpsc::Sender<(String, LineSource)>,
+    ingest: &IngestOptions,
+) -> std::io::Result<()> {
+    let mut child = process::Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let stdout_reader = child.stdout.take().map(|out| {
+        let tx = tx.clone();
+        let processor = ingest.processor(true);
+        thread::spawn(move || forward_lines(out, LineSource::Stdout, &tx, processor))
+    });
+    let stderr_reader = child.stderr.take().map(|err| {
+        let tx = tx.clone();
+        let processor = ingest.processor(false);
+        thread::spawn(move || forward_lines(err, LineSource::Stderr, &tx, processor))
+    });
+
+    // Report the exit status once both streams are drained
+    let name = command[0].clone();
+    thread::spawn(move || {
+        for reader in [stdout_reader, stderr_reader].into_iter().flatten() {
+            let _ = reader.join();
+        }
+        let message = match child.wait() {
+            Ok(status) => format!("[{} exited: {}]", name, status),
+            Err(e) => format!("[{} could not be waited on: {}]", name, e),
+        };
+        let _ = tx.blocking_send((message, LineSource::Stderr));
+    });
+
+    Ok(())
+}
+
+fn forward_lines<R: Read>(
+    stream: R,
+    source: LineSource,
+    tx: &mpsc::Sender<(String, LineSource)>,
+    mut processor: LineProcessor,
+) {
+    let mut reader = BufReader::new(stream);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                let line = String::from_utf8_lossy(&buf)
+                    .trim_end_matches('\n')
+                    .trim_end_matches('\r')
+                    .to_string();
+                for line in processor.push(line) {
+                    if tx.blocking_send((line, source)).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+    for line in processor.finish() {
+        if tx.blocking_send((line, source)).is_err() {
+            return;
+        }
+    }
+}
+
+/// Reassembles pretty-printed JSON (e.g. `docker inspect`) that arrives
+/// split across physical lines into one compact logical line, so JsonView
+/// can parse it. Lines that don't open a JSON value pass straight through.
+pub struct JsonJoiner {
+    pending: Vec<String>,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl JsonJoiner {
+    pub fn new() -> Self {
+        Self {
+            pending: Vec::new(),
+            depth: 0,
+            in_string: false,
+            escaped: false,
+        }
+    }
+
+    /// Feed one physical line and get back any logical lines it completes
+    pub fn push(&mut self, line: String) -> Vec<String> {
+        if self.pending.is_empty() && !opens_json(&line) {
+            return vec![line];
+        }
+
+        self.scan(&line);
+        self.pending.push(line);
+
+        if self.depth == 0 {
+            // Braces balanced: emit one compact line if it really is JSON
+            if self.pending.len() > 1 {
+                let joined = self.pending.join("\n");
+                if let Ok(value) = serde_json::from_str::<serde_json::Value>(&joined) {
+                    self.reset();
+                    return vec![value.to_string()];
+                }
+            }
+            return self.finish();
+        }
+
+        if self.pending.len() >= MAX_JSON_JOIN_LINES {
+            return self.finish();
+        }
+        Vec::new()
+    }
+
+    /// Give back whatever is still being accumulated, unjoined
+    pub fn finish(&mut self) -> Vec<String> {
+        let lines = std::mem::take(&mut self.pending);
+        self.reset();
+        lines
+    }
+
+    fn reset(&mut self) {
+        self.pending.clear();
+        self.depth = 0;
+        self.in_string = false;
+        self.escaped = false;
+    }
+
+    /// Track bracket depth, ignoring brackets inside strings
+    fn scan(&mut self, line: &str) {
+        for c in line.chars() {
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if c == '\\' {
+                    self.escaped = true;
+                } else if c == '"' {
+                    self.in_string = false;
+                }
+                continue;
+            }
+            match c {
+                '"' => self.in_string = true,
+                '{' | '[' => self.depth += 1,
+                '}' | ']' => self.depth = self.depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Default for JsonJoiner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether a line looks like the start of a JSON object or array. A bare
+/// `[` only counts when followed by something JSON-like, since many log
+/// formats start with `[INFO]` or `[2024-01-01 ...]`.
+fn opens_json(line: &str) -> bool {
+    let t = line.trim_start();
+    if t.starts_with('{') {
+        return true;
+    }
+    match t.strip_prefix('[') {
+        Some(rest) => matches!(rest.trim_start().chars().next(), None | Some('{' | '[' | '"')),
+        None => false,
+    }
+}
@@ -2,160 +2,245 @@ use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use std::fs::File;
 use std::io::{self, Read};
 use std::os::unix::io::AsRawFd;
-use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
 use std::thread;
 
-// Read keyboard input from /dev/tty when stdin is piped
-pub fn spawn_keyboard_reader(tx: mpsc::Sender<Event>) -> io::Result<thread::JoinHandle<()>> {
+/// How long to wait for the rest of an escape sequence. Terminals send a
+/// sequence in one write, so an ESC with nothing after it is the Esc key.
+const ESCAPE_TIMEOUT_MS: i32 = 50;
+
+/// How often an idle reader checks whether it has been asked to stop
+const STOP_CHECK_MS: i32 = 100;
+
+/// The /dev/tty reader thread. Stopping it (or dropping it) ends the thread
+/// and restores the terminal settings it changed, even mid escape sequence.
+pub struct KeyboardReader {
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl KeyboardReader {
+    /// Stop the thread and wait for it to put the terminal back. Call this
+    /// before leaving raw mode, which restores the settings from before both.
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for KeyboardReader {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Puts back the tty's original settings when the reader thread ends,
+/// however it ends
+struct TermiosGuard {
+    fd: i32,
+    original: libc::termios,
+}
+
+impl Drop for TermiosGuard {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(self.fd, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+/// One poll of the tty
+enum Input {
+    Byte(u8),
+    /// Nothing arrived in time
+    Idle,
+    /// The tty is gone or failed
+    Closed,
+}
+
+/// Wait up to `timeout_ms` for the next byte from the tty
+fn next_byte(tty: &mut File, timeout_ms: i32) -> Input {
+    let mut poll_fd = libc::pollfd {
+        fd: tty.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    match unsafe { libc::poll(&mut poll_fd, 1, timeout_ms) } {
+        0 => Input::Idle,
+        n if n < 0 => {
+            // A signal (e.g. a resize) interrupting the wait isn't an error
+            if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                Input::Idle
+            } else {
+                Input::Closed
+            }
+        }
+        _ => {
+            let mut byte = [0u8; 1];
+            match tty.read(&mut byte) {
+                Ok(1) => Input::Byte(byte[0]),
+                _ => Input::Closed,
+            }
+        }
+    }
+}
+
+/// The next byte of an escape sequence, or None if the sequence stops short
+fn sequence_byte(tty: &mut File) -> Option<u8> {
+    match next_byte(tty, ESCAPE_TIMEOUT_MS) {
+        Input::Byte(byte) => Some(byte),
+        Input::Idle | Input::Closed => None,
+    }
+}
+
+/// The rest of a multi-byte UTF-8 character after its `lead` byte. Each
+/// continuation byte gets the same short wait as an escape sequence, so a
+/// cut-off character is dropped instead of blocking the reader (and the
+/// stop flag) until the next keypress.
+fn read_utf8(tty: &mut File, lead: u8) -> Option<char> {
+    let len = match lead {
+        0xc2..=0xdf => 2,
+        0xe0..=0xef => 3,
+        _ => 4,
+    };
+    let mut encoded = [lead, 0, 0, 0];
+    for slot in &mut encoded[1..len] {
+        let byte = sequence_byte(tty)?;
+        if !(0x80..=0xbf).contains(&byte) {
+            return None;
+        }
+        *slot = byte;
+    }
+    std::str::from_utf8(&encoded[..len]).ok()?.chars().next()
+}
+
+fn send_key(tx: &mpsc::Sender<Event>, code: KeyCode, modifiers: KeyModifiers) {
+    let _ = tx.send(Event::Key(KeyEvent {
+        code,
+        modifiers,
+        kind: KeyEventKind::Press,
+        state: crossterm::event::KeyEventState::empty(),
+    }));
+}
+
+/// Decode what follows an ESC. A lone ESC is the Esc key; sequences that
+/// aren't recognized (or are cut off) are dropped.
+fn read_escape(tty: &mut File) -> Option<(KeyCode, KeyModifiers)> {
+    let none = KeyModifiers::empty();
+    let Some(second) = sequence_byte(tty) else {
+        return Some((KeyCode::Esc, none));
+    };
+    match second {
+        b'[' => {}
+        // SS3 form, sent in application cursor mode (and for Home/End by some terminals)
+        b'O' => {
+            return match sequence_byte(tty)? {
+                b'A' => Some((KeyCode::Up, none)),
+                b'B' => Some((KeyCode::Down, none)),
+                b'C' => Some((KeyCode::Right, none)),
+                b'D' => Some((KeyCode::Left, none)),
+                b'H' => Some((KeyCode::Home, none)),
+                b'F' => Some((KeyCode::End, none)),
+                _ => None,
+            };
+        }
+        _ => return None,
+    }
+    match sequence_byte(tty)? {
+        b'A' => Some((KeyCode::Up, none)),
+        b'B' => Some((KeyCode::Down, none)),
+        b'C' => Some((KeyCode::Right, none)),
+        b'D' => Some((KeyCode::Left, none)),
+        b'H' => Some((KeyCode::Home, none)),
+        b'F' => Some((KeyCode::End, none)),
+        b'1' => match sequence_byte(tty)? {
+            // Home as [1~ (linux console, tmux)
+            b'~' => Some((KeyCode::Home, none)),
+            // Shift+arrow is [1;2A / [1;2B
+            b';' => match [sequence_byte(tty)?, sequence_byte(tty)?] {
+                [b'2', b'A'] => Some((KeyCode::Up, KeyModifiers::SHIFT)),
+                [b'2', b'B'] => Some((KeyCode::Down, KeyModifiers::SHIFT)),
+                _ => None,
+            },
+            _ => None,
+        },
+        // End as [4~, and Home/End as [7~/[8~ (rxvt)
+        b'4' | b'8' => (sequence_byte(tty)? == b'~').then_some((KeyCode::End, none)),
+        b'7' => (sequence_byte(tty)? == b'~').then_some((KeyCode::Home, none)),
+        // PageUp is [5~, PageDown [6~
+        b'5' => (sequence_byte(tty)? == b'~').then_some((KeyCode::PageUp, none)),
+        b'6' => (sequence_byte(tty)? == b'~').then_some((KeyCode::PageDown, none)),
+        _ => None,
+    }
+}
+
+/// Read keyboard input from /dev/tty when stdin is piped
+pub fn spawn_keyboard_reader(tx: mpsc::Sender<Event>) -> io::Result<KeyboardReader> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_flag = Arc::clone(&stop);
     let handle = thread::spawn(move || {
         // Open /dev/tty to read from the terminal device directly
-        let tty = match File::open("/dev/tty") {
-            Ok(f) => f,
-            Err(_) => {
-                // If /dev/tty doesn't work, we can't read keyboard
-                return;
-            }
+        let Ok(mut tty) = File::open("/dev/tty") else {
+            // If /dev/tty doesn't work, we can't read keyboard
+            return;
         };
-
         let fd = tty.as_raw_fd();
-        
-        // Set terminal to raw mode for this file descriptor
-        unsafe {
+
+        // Turn off line buffering and echo for this terminal, until the guard drops
+        let _guard = unsafe {
             use libc::{tcgetattr, tcsetattr, termios, ECHO, ICANON, TCSANOW};
             let mut termios: termios = std::mem::zeroed();
-            if tcgetattr(fd, &mut termios) == 0 {
-                let original_termios = termios;
-                termios.c_lflag &= !(ICANON | ECHO);
-                if tcsetattr(fd, TCSANOW, &termios) != 0 {
-                    return;
+            if tcgetattr(fd, &mut termios) != 0 {
+                return;
+            }
+            let guard = TermiosGuard { fd, original: termios };
+            termios.c_lflag &= !(ICANON | ECHO);
+            if tcsetattr(fd, TCSANOW, &termios) != 0 {
+                return;
+            }
+            guard
+        };
+
+        loop {
+            let byte = match next_byte(&mut tty, STOP_CHECK_MS) {
+                Input::Byte(byte) => byte,
+                Input::Idle if stop_flag.load(Ordering::Relaxed) => break,
+                Input::Idle => continue,
+                Input::Closed => break,
+            };
+            let none = KeyModifiers::empty();
+            match byte {
+                // Escape sequences (arrow keys and the like) start with ESC
+                0x1b => {
+                    if let Some((code, modifiers)) = read_escape(&mut tty) {
+                        send_key(&tx, code, modifiers);
+                    }
                 }
-                
-                // Read bytes from /dev/tty
-                let mut tty_reader = tty;
-                let mut single_byte = [0u8; 1];
-                
-                loop {
-                    // Read first byte
-                    match tty_reader.read_exact(&mut single_byte) {
-                        Ok(_) => {
-                            let byte = single_byte[0];
-                            
-                            // Check for escape sequences (arrow keys start with 0x1b = ESC)
-                            if byte == 0x1b {
-                                // Read next byte (should be '[')
-                                let mut second_byte = [0u8; 1];
-                                if tty_reader.read_exact(&mut second_byte).is_ok() && second_byte[0] == 0x5b {
-                                    // Read third byte to determine which arrow key
-                                    let mut third_byte = [0u8; 1];
-                                    if tty_reader.read_exact(&mut third_byte).is_ok() {
-                                        match third_byte[0] {
-                                            0x41 => { // Up arrow [A
-                                                let _ = tx.send(Event::Key(KeyEvent {
-                                                    code: KeyCode::Up,
-                                                    modifiers: KeyModifiers::empty(),
-                                                    kind: KeyEventKind::Press,
-                                                    state: crossterm::event::KeyEventState::empty(),
-                                                }));
-                                            }
-                                            0x42 => { // Down arrow [B
-                                                let _ = tx.send(Event::Key(KeyEvent {
-                                                    code: KeyCode::Down,
-                                                    modifiers: KeyModifiers::empty(),
-                                                    kind: KeyEventKind::Press,
-                                                    state: crossterm::event::KeyEventState::empty(),
-                                                }));
-                                            }
-                                            0x35 => { // PageUp starts with [5, need one more byte
-                                                let mut fourth_byte = [0u8; 1];
-                                                if tty_reader.read_exact(&mut fourth_byte).is_ok() && fourth_byte[0] == 0x7e {
-                                                    let _ = tx.send(Event::Key(KeyEvent {
-                                                        code: KeyCode::PageUp,
-                                                        modifiers: KeyModifiers::empty(),
-                                                        kind: KeyEventKind::Press,
-                                                        state: crossterm::event::KeyEventState::empty(),
-                                                    }));
-                                                }
-                                            }
-                                            0x36 => { // PageDown starts with [6, need one more byte
-                                                let mut fourth_byte = [0u8; 1];
-                                                if tty_reader.read_exact(&mut fourth_byte).is_ok() && fourth_byte[0] == 0x7e {
-                                                    let _ = tx.send(Event::Key(KeyEvent {
-                                                        code: KeyCode::PageDown,
-                                                        modifiers: KeyModifiers::empty(),
-                                                        kind: KeyEventKind::Press,
-                                                        state: crossterm::event::KeyEventState::empty(),
-                                                    }));
-                                                }
-                                            }
-                                            _ => {}
-                                        }
-                                    }
-                                }
-                            } else {
-                                // Parse simple keypresses
-                                match byte {
-                                    b'q' | b'Q' => {
-                                        let _ = tx.send(Event::Key(KeyEvent {
-                                            code: KeyCode::Char('q'),
-                                            modifiers: KeyModifiers::empty(),
-                                            kind: KeyEventKind::Press,
-                                            state: crossterm::event::KeyEventState::empty(),
-                                        }));
-                                    }
-                                    b'a' | b'A' => {
-                                        let _ = tx.send(Event::Key(KeyEvent {
-                                            code: KeyCode::Char('a'),
-                                            modifiers: KeyModifiers::empty(),
-                                            kind: KeyEventKind::Press,
-                                            state: crossterm::event::KeyEventState::empty(),
-                                        }));
-                                    }
-                                    b'f' | b'F' => {
-                                        let _ = tx.send(Event::Key(KeyEvent {
-                                            code: KeyCode::Char('f'),
-                                            modifiers: KeyModifiers::empty(),
-                                            kind: KeyEventKind::Press,
-                                            state: crossterm::event::KeyEventState::empty(),
-                                        }));
-                                    }
-                                    b'c' | b'C' => {
-                                        let _ = tx.send(Event::Key(KeyEvent {
-                                            code: KeyCode::Char('c'),
-                                            modifiers: KeyModifiers::empty(),
-                                            kind: KeyEventKind::Press,
-                                            state: crossterm::event::KeyEventState::empty(),
-                                        }));
-                                    }
-                                    27 => { // ESC
-                                        let _ = tx.send(Event::Key(KeyEvent {
-                                            code: KeyCode::Esc,
-                                            modifiers: KeyModifiers::empty(),
-                                            kind: KeyEventKind::Press,
-                                            state: crossterm::event::KeyEventState::empty(),
-                                        }));
-                                    }
-                                    3 => { // Ctrl+C (ETX)
-                                        let _ = tx.send(Event::Key(KeyEvent {
-                                            code: KeyCode::Char('c'),
-                                            modifiers: KeyModifiers::CONTROL,
-                                            kind: KeyEventKind::Press,
-                                            state: crossterm::event::KeyEventState::empty(),
-                                        }));
-                                    }
-                                    _ => {}
-                                }
-                            }
-                        }
-                        Err(_) => break,
+                b'\r' | b'\n' => send_key(&tx, KeyCode::Enter, none),
+                // Backspace (DEL, or ^H on some terminals)
+                0x7f | 0x08 => send_key(&tx, KeyCode::Backspace, none),
+                // Ctrl+C (ETX)
+                3 => send_key(&tx, KeyCode::Char('c'), KeyModifiers::CONTROL),
+                // Ctrl+U (NAK): clears an input box
+                0x15 => send_key(&tx, KeyCode::Char('u'), KeyModifiers::CONTROL),
+                // First byte of a multi-byte UTF-8 character
+                0xc2..=0xf4 => {
+                    if let Some(c) = read_utf8(&mut tty, byte) {
+                        send_key(&tx, KeyCode::Char(c), none);
                     }
                 }
-                
-                // Restore terminal
-                let _ = tcsetattr(fd, TCSANOW, &original_termios);
+                // Any printable ASCII key
+                0x20..=0x7e => send_key(&tx, KeyCode::Char(byte as char), none),
+                _ => {}
             }
         }
     });
-    
-    Ok(handle)
-}
 
+    Ok(KeyboardReader {
+        stop,
+        handle: Some(handle),
+    })
+}
@@ -0,0 +1,370 @@
+//! Keys for the main screen, rebindable from the `[keys]` section of
+//! config.toml. Popups and input boxes keep their fixed keys. A binding can
+//! be a sequence of keys pressed one after another, like vim's `gg`.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Everything a key can do on the main screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Analyze,
+    Filter,
+    RegexFilter,
+    Histogram,
+    Settings,
+    Tools,
+    ExtendUp,
+    ExtendDown,
+    ScrollUp,
+    ScrollDown,
+    ScrollLeft,
+    ScrollRight,
+    PageUp,
+    PageDown,
+    NextMatch,
+    PreviousMatch,
+    GotoLine,
+    Top,
+    Bottom,
+    Follow,
+    Pause,
+    JsonTree,
+    ToggleNode,
+    IgnoreCase,
+    Wrap,
+    ResetErrors,
+    ErrorPanel,
+    PinFilter,
+    MoreFilterContext,
+    LessFilterContext,
+    MoreContext,
+    LessContext,
+    Whitespace,
+    AnsiColors,
+    Indices,
+    LineNumbers,
+    Bookmark,
+    NextBookmark,
+    PreviousBookmark,
+    BookmarkView,
+    FilterLogger,
+    FilterTrace,
+    CopyFilter,
+    CopyLines,
+    CopyAll,
+    Export,
+    Save,
+    Flatten,
+    Dedupe,
+    Expand,
+    Collapse,
+    Reverse,
+    TimeSort,
+    RestoreFilter,
+    RawResponse,
+    LastView,
+    Clear,
+}
+
+/// Each action's name in `[keys]` and its default keys
+const DEFAULT_BINDINGS: &[(Action, &str, &[&str])] = &[
+    (Action::Quit, "quit", &["q", "ctrl+c"]),
+    (Action::Analyze, "analyze", &["a"]),
+    (Action::Filter, "filter", &["f"]),
+    (Action::RegexFilter, "regex_filter", &["/"]),
+    (Action::Histogram, "histogram", &["h"]),
+    (Action::Settings, "settings", &["S"]),
+    (Action::Tools, "tools", &["T"]),
+    (Action::ExtendUp, "extend_up", &["shift+up"]),
+    (Action::ExtendDown, "extend_down", &["shift+down"]),
+    (Action::ScrollUp, "scroll_up", &["up", "k"]),
+    (Action::ScrollDown, "scroll_down", &["down", "j"]),
+    (Action::ScrollLeft, "scroll_left", &["left"]),
+    (Action::ScrollRight, "scroll_right", &["right"]),
+    // Ahead of page_down, which shares Space outside the Json tree
+    (Action::ToggleNode, "toggle_node", &["space"]),
+    (Action::PageUp, "page_up", &["pageup", "b"]),
+    (Action::PageDown, "page_down", &["pagedown", "space"]),
+    (Action::NextMatch, "next_match", &["n"]),
+    (Action::PreviousMatch, "previous_match", &["N"]),
+    (Action::GotoLine, "goto_line", &[":"]),
+    (Action::Top, "top", &["home", "g g"]),
+    (Action::Bottom, "bottom", &["end", "G"]),
+    // Bottom turns follow on too, so this is only for toggling it in place
+    (Action::Follow, "follow", &[]),
+    (Action::Pause, "pause", &["p"]),
+    (Action::JsonTree, "json_tree", &["t"]),
+    (Action::IgnoreCase, "ignore_case", &["I"]),
+    (Action::Wrap, "wrap", &["w"]),
+    (Action::ResetErrors, "reset_errors", &["E"]),
+    (Action::ErrorPanel, "error_panel", &["e"]),
+    (Action::PinFilter, "pin_filter", &["F"]),
+    // Ahead of more_context/less_context, which share +/- when there's no filter
+    (Action::MoreFilterContext, "more_filter_context", &["+", "="]),
+    (Action::LessFilterContext, "less_filter_context", &["-"]),
+    (Action::MoreContext, "more_context", &["+", "="]),
+    (Action::LessContext, "less_context", &["-"]),
+    (Action::Whitespace, "whitespace", &["v"]),
+    (Action::AnsiColors, "ansi_colors", &["A"]),
+    (Action::Indices, "indices", &["i"]),
+    (Action::LineNumbers, "line_numbers", &["#"]),
+    (Action::Bookmark, "bookmark", &["m"]),
+    (Action::NextBookmark, "next_bookmark", &["] b"]),
+    (Action::PreviousBookmark, "previous_bookmark", &["[ b"]),
+    (Action::BookmarkView, "bookmark_view", &["B"]),
+    (Action::FilterLogger, "filter_logger", &["l"]),
+    (Action::FilterTrace, "filter_trace", &["x"]),
+    (Action::CopyFilter, "copy_filter", &["y"]),
+    (Action::CopyLines, "copy_lines", &["Y"]),
+    (Action::CopyAll, "copy_all", &["C"]),
+    (Action::Export, "export", &["W"]),
+    (Action::Save, "save", &["o"]),
+    // Flatten only applies in the Json view and dedupe in Plain, so they share d
+    (Action::Flatten, "flatten", &["d"]),
+    (Action::Dedupe, "dedupe", &["d"]),
+    (Action::Expand, "expand", &[">"]),
+    (Action::Collapse, "collapse", &["<"]),
+    (Action::Reverse, "reverse", &["r"]),
+    (Action::TimeSort, "time_sort", &["s"]),
+    (Action::RestoreFilter, "restore_filter", &["u"]),
+    (Action::RawResponse, "raw_response", &["M"]),
+    (Action::LastView, "last_view", &["`"]),
+    (Action::Clear, "clear", &["c", "esc"]),
+];
+
+impl Action {
+    fn from_name(name: &str) -> Option<Action> {
+        DEFAULT_BINDINGS
+            .iter()
+            .find(|(_, action_name, _)| *action_name == name)
+            .map(|(action, _, _)| *action)
+    }
+
+    fn name(self) -> &'static str {
+        DEFAULT_BINDINGS
+            .iter()
+            .find(|(action, _, _)| *action == self)
+            .map_or("", |(_, name, _)| name)
+    }
+}
+
+/// A key and the modifiers held with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    /// Shift is part of the character itself ('G', '>'), and crossterm
+    /// reports it for those while the /dev/tty reader doesn't, so drop it
+    /// from character keys before comparing
+    fn new(code: KeyCode, mut modifiers: KeyModifiers) -> Self {
+        if let KeyCode::Char(_) = code {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self { code, modifiers }
+    }
+
+    /// Parse a key such as `q`, `G`, `ctrl+c`, `shift+up`, `pagedown` or `space`
+    fn parse(spec: &str) -> Result<Self, String> {
+        // A trailing '+' is the plus key itself: "+" or "ctrl++"
+        let (prefix, key) = match spec.strip_suffix('+') {
+            Some(rest) if rest.is_empty() || rest.ends_with('+') => (rest.strip_suffix('+').unwrap_or(""), "+"),
+            _ => spec.rsplit_once('+').unwrap_or(("", spec)),
+        };
+        let mut modifiers = KeyModifiers::NONE;
+        for name in prefix.split('+').filter(|name| !name.is_empty()) {
+            modifiers |= match name.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(format!("unknown modifier '{}' in key '{}'", name, spec)),
+            };
+        }
+        let code = match key.to_ascii_lowercase().as_str() {
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "esc" => KeyCode::Esc,
+            "enter" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "space" => KeyCode::Char(' '),
+            _ => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    // shift+g means 'G'
+                    (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::Char(c.to_ascii_uppercase()),
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return Err(format!("unknown key '{}'", spec)),
+                }
+            }
+        };
+        Ok(Self::new(code, modifiers))
+    }
+
+    /// How the key is shown in the top bar, e.g. `q` or `ctrl+c`
+    fn label(&self) -> String {
+        let mut label = String::new();
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "ctrl+"),
+            (KeyModifiers::ALT, "alt+"),
+            (KeyModifiers::SHIFT, "shift+"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                label.push_str(name);
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => label.push_str("space"),
+            KeyCode::Char(c) => label.push(c),
+            code => label.push_str(&format!("{:?}", code).to_ascii_lowercase()),
+        }
+        label
+    }
+}
+
+/// Parse a binding: one key, or several separated by spaces (`g g`)
+fn parse_sequence(spec: &str) -> Result<Vec<KeyBinding>, String> {
+    let keys = spec.split_whitespace().map(KeyBinding::parse).collect::<Result<Vec<_>, _>>()?;
+    if keys.is_empty() {
+        return Err("empty key".to_string());
+    }
+    Ok(keys)
+}
+
+/// A binding as written in the config, e.g. `g g`
+fn sequence_label(binding: &[KeyBinding]) -> String {
+    binding.iter().map(KeyBinding::label).collect::<Vec<_>>().join(" ")
+}
+
+/// Which action each key on the main screen triggers
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(Vec<KeyBinding>, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = DEFAULT_BINDINGS
+            .iter()
+            .flat_map(|(action, _, keys)| {
+                keys.iter().map(|key| (parse_sequence(key).expect("default key binding"), *action))
+            })
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    /// The defaults with some actions rebound, from `action = keys` pairs.
+    /// Rebinding an action replaces all of its default keys (an empty list
+    /// unbinds it), and a key taken over from another action's defaults is
+    /// removed from that action. A key that starts a longer binding (`g`
+    /// beside `g g`) is refused, since it would only ever wait for the rest.
+    pub fn with_overrides(overrides: impl IntoIterator<Item = (String, Vec<String>)>) -> Result<Self, String> {
+        let mut keymap = Self::default();
+        let mut rebound: Vec<(Vec<KeyBinding>, Action)> = Vec::new();
+        for (name, keys) in overrides {
+            let action = Action::from_name(&name).ok_or_else(|| format!("unknown action '{}'", name))?;
+            keymap.bindings.retain(|(_, a)| *a != action);
+            for key in keys {
+                let binding = parse_sequence(&key)?;
+                if let Some((_, other)) = rebound.iter().find(|(b, a)| *b == binding && *a != action) {
+                    return Err(format!("key '{}' is bound to both {} and {}", key, other.name(), name));
+                }
+                rebound.push((binding, action));
+            }
+        }
+        keymap.bindings.retain(|(binding, _)| !rebound.iter().any(|(b, _)| b == binding));
+        keymap.bindings.extend(rebound);
+        for (short, short_action) in &keymap.bindings {
+            let longer = keymap.bindings.iter().find(|(long, _)| long.len() > short.len() && long.starts_with(short));
+            if let Some((long, long_action)) = longer {
+                return Err(format!(
+                    "key '{}' ({}) is the start of '{}' ({}), so it would never fire",
+                    sequence_label(short),
+                    short_action.name(),
+                    sequence_label(long),
+                    long_action.name()
+                ));
+            }
+        }
+        Ok(keymap)
+    }
+
+    /// The action for a key press, if the key is bound. A key bound to
+    /// several actions (Space folds in the Json tree and pages elsewhere)
+    /// goes to the first one `applies` accepts.
+    ///
+    /// `pending` holds the keys pressed so far of a longer binding. While
+    /// they start some binding, the press is kept there and gives no action;
+    /// once they can't, they're dropped and the last key is looked up alone.
+    pub fn action_for(
+        &self,
+        pending: &mut Vec<KeyEvent>,
+        key: &KeyEvent,
+        applies: impl Fn(Action) -> bool,
+    ) -> Option<Action> {
+        pending.push(*key);
+        let pressed: Vec<KeyBinding> = pending.iter().map(|key| KeyBinding::new(key.code, key.modifiers)).collect();
+        let candidates = || {
+            self.bindings
+                .iter()
+                .filter(|(binding, action)| binding.starts_with(&pressed) && applies(*action))
+        };
+        if candidates().any(|(binding, _)| binding.len() > pressed.len()) {
+            return None;
+        }
+        let action = candidates().map(|(_, action)| *action).next();
+        if action.is_none() && pending.len() > 1 {
+            pending.clear();
+            return self.action_for(pending, key, applies);
+        }
+        pending.clear();
+        action
+    }
+
+    /// The first key bound to an action, for hints like `[q] quit`; empty if
+    /// the action is unbound
+    pub fn label(&self, action: Action) -> String {
+        self.bindings
+            .iter()
+            .find(|(_, a)| *a == action)
+            .map(|(binding, _)| binding.iter().map(KeyBinding::label).collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rebind(pairs: &[(&str, &[&str])]) -> Result<Keymap, String> {
+        Keymap::with_overrides(
+            pairs.iter().map(|(action, keys)| (action.to_string(), keys.iter().map(|key| key.to_string()).collect())),
+        )
+    }
+
+    #[test]
+    fn a_key_starting_a_longer_binding_is_refused() {
+        let err = rebind(&[("bottom", &["g"])]).unwrap_err();
+        assert!(err.contains("'g g'"), "{}", err);
+        assert!(rebind(&[("reverse", &["]"])]).is_err());
+        assert!(rebind(&[("bottom", &["x"]), ("reverse", &["x y"])]).is_err());
+    }
+
+    #[test]
+    fn a_prefix_is_fine_once_the_longer_binding_is_gone() {
+        assert!(rebind(&[("top", &["home"]), ("bottom", &["g"])]).is_ok());
+        assert!(rebind(&[("top", &["g g"]), ("bottom", &["G"])]).is_ok());
+    }
+}
@@ -1,32 +1,106 @@
 mod app;
+mod archive;
+mod clipboard;
 mod config;
+mod export;
+mod histogram;
 mod input_source;
 mod keyboard;
+mod keymap;
 mod openai;
 mod plugins;
+mod query;
 mod utils;
 mod views;
 
-use app::AppState;
+use app::{AppState, InputMode, LineSource, StatusKind};
+use keymap::Action;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Layout},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{Block, Borders, Clear as ClearWidget, List, ListItem, Paragraph, Wrap},
     Frame, Terminal,
 };
 use clap::Parser;
 use std::io::{self, BufRead, BufReader};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{mpsc as sync_mpsc, Arc};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 
+/// Result of a background analysis: the view to switch to, a status summary,
+/// and (on success) the exchange to append to the AI conversation along with
+/// the total line count it covered, plus the model's raw reply if one arrived
+/// and, when the model picked the view, its note on why
+type AnalysisResult = (
+    views::ViewKind,
+    String,
+    Option<(Vec<openai::ChatMessage>, usize)>,
+    Option<String>,
+    Option<String>,
+);
+
+/// Longest the main loop spends taking in lines before it redraws and
+/// checks for keys, so a flood of input can't freeze the UI
+const INGEST_BUDGET: Duration = Duration::from_millis(50);
+
+/// How often the screen is redrawn for animations (the analysis spinner)
+/// while nothing else changes
+const ANIMATION_TICK: Duration = Duration::from_millis(250);
+
+/// Frames of the spinner shown while an analysis is running
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// Whether the alternate screen was successfully entered. Minimal terminals
+/// may not support it, in which case scry draws inline in the normal buffer
+/// and cleanup must not try to leave a screen it never entered.
+static ALT_SCREEN_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Whether mouse capture was turned on, so cleanup (including the panic hook)
+/// only turns it off when `--no-mouse` wasn't given
+static MOUSE_CAPTURE_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Leave the alternate screen (only if it was entered) and stop mouse capture
+/// (only if it was started)
+fn leave_screen() {
+    if ALT_SCREEN_ACTIVE.load(Ordering::Relaxed) {
+        let _ = execute!(io::stdout(), LeaveAlternateScreen);
+    }
+    if MOUSE_CAPTURE_ACTIVE.swap(false, Ordering::Relaxed) {
+        let _ = execute!(io::stdout(), DisableMouseCapture);
+    }
+}
+
+/// Whether to draw on the alternate screen: not with `--no-alt-screen`, and
+/// not when TERM is unset or `dumb`, since such terminals usually accept the
+/// escape sequence without switching screens
+fn wants_alt_screen(no_alt_screen: bool) -> bool {
+    !no_alt_screen && std::env::var("TERM").is_ok_and(|term| !term.is_empty() && term != "dumb")
+}
+
+/// Enter the alternate screen if wanted, otherwise (or if the terminal
+/// reports an error) draw inline after clearing the normal buffer, and start
+/// mouse capture if asked
+fn enter_screen(alt_screen: bool, mouse_capture: bool) {
+    let mut stdout = io::stdout();
+    let entered = alt_screen && execute!(stdout, EnterAlternateScreen).is_ok();
+    ALT_SCREEN_ACTIVE.store(entered, Ordering::Relaxed);
+    if !entered {
+        let _ = execute!(stdout, Clear(ClearType::All));
+    }
+    if mouse_capture {
+        let captured = execute!(stdout, EnableMouseCapture).is_ok();
+        MOUSE_CAPTURE_ACTIVE.store(captured, Ordering::Relaxed);
+    }
+}
+
 #[derive(Parser)]
 #[command(name = "scry")]
 #[command(about = "A magical TUI log viewer that uses AI to pick the best layout")]
@@ -44,6 +118,144 @@ struct Cli {
     /// Delete the existing API key
     #[arg(short = 'd', long = "delete")]
     delete: bool,
+
+    /// Don't ask for confirmation before deleting the API key
+    #[arg(short = 'y', long = "yes", requires = "delete")]
+    yes: bool,
+
+    /// Filter match highlight color as FG[:BG] (e.g. "black:yellow", "#ff8800")
+    #[arg(long = "match-color", value_name = "FG[:BG]", value_parser = views::parse_color_pair)]
+    match_color: Option<(Color, Option<Color>)>,
+
+    /// Selected line color as FG[:BG]
+    #[arg(long = "selected-color", value_name = "FG[:BG]", value_parser = views::parse_color_pair)]
+    selected_color: Option<(Color, Option<Color>)>,
+
+    /// Color regex matches, as COLOR=REGEX; with capture groups only the captured text is colored (repeatable)
+    #[arg(long = "highlight", value_name = "COLOR=REGEX", value_parser = views::parse_highlight_rule)]
+    highlight: Vec<views::HighlightRule>,
+
+    /// Above this many lines per second only a sample of lines is displayed (0 never samples)
+    #[arg(long = "sample-above", value_name = "LINES_PER_SEC", default_value_t = app::DEFAULT_SAMPLE_ABOVE)]
+    sample_above: usize,
+
+    /// Start in this built-in view (plain, keyvalue, logfmt, json, csv) instead of Plain, without calling the API
+    #[arg(long = "view", value_name = "VIEW", value_parser = views::parse_builtin_view, conflicts_with = "render")]
+    view: Option<views::ViewKind>,
+
+    /// Print stdin formatted with a built-in view (plain, keyvalue, logfmt, json, csv) and exit, without the TUI
+    #[arg(long = "render", value_name = "VIEW", value_parser = views::parse_builtin_view)]
+    render: Option<views::ViewKind>,
+
+    /// With --render, only print the last N input lines
+    #[arg(long = "tail", value_name = "N", requires = "render")]
+    tail: Option<usize>,
+
+    /// Lines of scrollback kept in memory (default 2000, or `buffer_capacity` in config.toml)
+    #[arg(long = "buffer-size", value_name = "N", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    buffer_size: Option<usize>,
+
+    /// Levels of nested JSON rendered inline before collapsing to "…"
+    #[arg(long = "json-depth", value_name = "N", default_value_t = app::DEFAULT_JSON_DEPTH)]
+    json_depth: usize,
+
+    /// Comma-separated field names used by the filter-by-logger key, in priority order
+    #[arg(long = "logger-keys", value_name = "KEYS", value_delimiter = ',')]
+    logger_keys: Option<Vec<String>>,
+
+    /// Comma-separated field names holding a trace or request ID, used by the filter-by-trace key, in priority order
+    #[arg(long = "trace-keys", value_name = "KEYS", value_delimiter = ',')]
+    trace_keys: Option<Vec<String>>,
+
+    /// OpenAI model used for analysis (default gpt-4o-mini, or `model` in config.toml)
+    #[arg(short = 'm', long = "model", value_name = "MODEL")]
+    model: Option<String>,
+
+    /// API root for OpenAI-compatible servers (default https://api.openai.com/v1, or the `base_url` config file)
+    #[arg(long = "base-url", value_name = "URL")]
+    base_url: Option<String>,
+
+    /// Analyze automatically once N lines have arrived, then again every N more (or `auto_analyze_threshold` in config.toml)
+    #[arg(long = "auto-analyze", value_name = "N", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    auto_analyze: Option<usize>,
+
+    /// Comma-separated JSON keys that may be sent to the AI; all other keys are dropped
+    #[arg(long = "ai-keys", value_name = "KEYS", value_delimiter = ',')]
+    ai_keys: Option<Vec<String>>,
+
+    /// Start with a filter applied: a substring, or a JSON path expression such as '$.request.status >= 500'
+    #[arg(long = "filter", value_name = "EXPR", value_parser = query::parse_filter)]
+    filter: Option<query::FilterMode>,
+
+    /// Join JSON pretty-printed across several lines (e.g. docker inspect) into one entry
+    #[arg(long = "multiline-json")]
+    multiline_json: bool,
+
+    /// Regex stripped from the start of every incoming line (e.g. a container ID prefix)
+    #[arg(long = "strip-prefix", value_name = "REGEX", value_parser = input_source::parse_strip_prefix)]
+    strip_prefix: Option<regex::Regex>,
+
+    /// Regex stripped from the end of every incoming line
+    #[arg(long = "strip-suffix", value_name = "REGEX", value_parser = input_source::parse_strip_suffix)]
+    strip_suffix: Option<regex::Regex>,
+
+    /// Append every line received to FILE as it arrives, for replaying later with `scry < FILE`
+    #[arg(long = "record", value_name = "FILE")]
+    record: Option<std::path::PathBuf>,
+
+    /// Write the whole buffer to FILE on exit
+    #[arg(long = "output", value_name = "FILE")]
+    output: Option<std::path::PathBuf>,
+
+    /// Append lines that scroll out of the buffer to FILE, written when the input goes quiet
+    #[arg(long = "archive", value_name = "FILE")]
+    archive: Option<std::path::PathBuf>,
+
+    /// With --archive, rotate FILE to FILE.1 once it reaches SIZE (e.g. 512K, 10M)
+    #[arg(long = "archive-max-size", value_name = "SIZE", requires = "archive", value_parser = archive::parse_size)]
+    archive_max_size: Option<u64>,
+
+    /// Start with the error/warning side panel open (toggle with 'e')
+    #[arg(long = "error-panel")]
+    error_panel: bool,
+
+    /// Run the TUI even when stdout doesn't look like a terminal (for terminals that aren't detected)
+    #[arg(long = "force-tui")]
+    force_tui: bool,
+
+    /// Don't capture the mouse, leaving text selection and scrolling to the terminal
+    #[arg(long = "no-mouse")]
+    no_mouse: bool,
+
+    /// Draw inline in the normal screen buffer instead of the alternate screen
+    #[arg(long = "no-alt-screen")]
+    no_alt_screen: bool,
+
+    /// Read preferences from FILE instead of config.toml in the config directory
+    #[arg(long, value_name = "FILE")]
+    config: Option<std::path::PathBuf>,
+
+    /// Run a command and view its output, with stderr lines tagged (scry -- CMD ARGS...)
+    #[arg(last = true, value_name = "CMD")]
+    command: Vec<String>,
+}
+
+impl Cli {
+    /// Options applied to lines as they are read, before they enter the buffer
+    fn ingest_options(&self) -> io::Result<input_source::IngestOptions> {
+        let record = match self.record {
+            Some(ref path) => Some(input_source::Recorder::open(path).map_err(|e| {
+                io::Error::new(e.kind(), format!("Failed to open {}: {}", path.display(), e))
+            })?),
+            None => None,
+        };
+        Ok(input_source::IngestOptions {
+            multiline_json: self.multiline_json,
+            strip_prefix: self.strip_prefix.clone(),
+            strip_suffix: self.strip_suffix.clone(),
+            record,
+        })
+    }
 }
 
 #[tokio::main]
@@ -53,11 +265,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     std::panic::set_hook(Box::new(move |panic_info| {
         // Restore terminal on panic
         let _ = disable_raw_mode();
-        let _ = execute!(
-            io::stdout(),
-            LeaveAlternateScreen,
-            DisableMouseCapture
-        );
+        leave_screen();
         original_hook(panic_info);
     }));
 
@@ -65,8 +273,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Handle API key commands
     if cli.delete {
-        config::delete_api_key()?;
-        println!("API key deleted successfully!");
+        if !config::key_file_exists() {
+            println!("No API key is set; nothing to delete.");
+            return Ok(());
+        }
+        // Ask first when someone is at the keyboard, so a stray -d can't lose the key
+        if !cli.yes && atty::is(atty::Stream::Stdin) && !confirm("Delete the saved OpenAI API key?")? {
+            println!("Kept the API key.");
+            return Ok(());
+        }
+        if config::delete_api_key()? {
+            println!("API key deleted successfully!");
+        } else {
+            println!("No API key is set; nothing to delete.");
+        }
         return Ok(());
     }
     
@@ -76,11 +296,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
 
+    // Non-interactive render: format stdin and print it, no TUI or API key needed
+    if let Some(ref view) = cli.render {
+        render_to_stdout(view, cli.tail, cli.json_depth, &cli.ingest_options()?)?;
+        return Ok(());
+    }
+
     // Check if stdin is piped
     let stdin_is_tty = atty::is(atty::Stream::Stdin);
     
     // If no stdin and no --start flag, show help
-    if stdin_is_tty && !cli.start {
+    if stdin_is_tty && !cli.start && cli.command.is_empty() {
         // Show usage information with ASCII art
         println!();
         println!(" .::::::.   .,-::::: :::::::...-:.     ::-.");
@@ -99,48 +325,202 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("    tail -f app.log | scry          # View streaming logs");
         println!("    journalctl -f | scry            # View systemd logs");
         println!("    scry < app.log                  # View a log file");
-        println!("    scry --start                    # Start TUI (waiting for input)\n");
+        println!("    scry --start                    # Start TUI (waiting for input)");
+        println!("    scry -- make test               # Run a command, tagging its stderr\n");
         println!("COMMANDS:");
         println!("    -k, --key <API_KEY>             Set OpenAI API key");
-        println!("    -d, --delete                    Delete existing API key\n");
+        println!("    -d, --delete                    Delete existing API key (asks first; -y to skip)\n");
         println!("OPTIONS:");
         println!("    -h, --help                      Print help information");
         println!("    -V, --version                   Print version information");
-        println!("    -s, --start                     Start TUI even without piped input\n");
+        println!("    -s, --start                     Start TUI even without piped input");
+        println!("        --sample-above <N>          Display only a sample of lines above N lines/s (default 20000, 0 = never)");
+        println!("        --buffer-size <N>           Lines of scrollback kept in memory (default 2000)");
+        println!("        --view <VIEW>               Start in the plain/keyvalue/logfmt/json/csv view");
+        println!("        --render <VIEW>             Print stdin formatted as plain/keyvalue/logfmt/json/csv and exit");
+        println!("        --tail <N>                  With --render, only print the last N lines");
+        println!("        --multiline-json            Join pretty-printed JSON spread over several lines");
+        println!("        --strip-prefix <REGEX>      Strip a pattern from the start of every line");
+        println!("        --strip-suffix <REGEX>      Strip a pattern from the end of every line");
+        println!("        --record <FILE>             Append every received line to FILE");
+        println!("        --filter <EXPR>             Start filtered (substring or '$.path >= 500')");
+        println!("        --archive <FILE>            Append lines evicted from the buffer to FILE");
+        println!("        --output <FILE>             Write the buffer to FILE on exit");
+        println!("        --archive-max-size <SIZE>   Rotate the archive to FILE.1 at SIZE (default 10M)");
+        println!("    -m, --model <MODEL>             OpenAI model used for analysis (default gpt-4o-mini)");
+        println!("        --base-url <URL>            API root for OpenAI-compatible servers (default https://api.openai.com/v1)");
+        println!("        --ai-keys <KEYS>            Only send these JSON keys to the AI (comma-separated)");
+        println!("        --auto-analyze <N>          Analyze automatically every N new lines");
+        println!("        --error-panel               Open the error/warning side panel at startup");
+        println!("        --force-tui                 Start the TUI even if stdout isn't detected as a terminal");
+        println!("        --no-mouse                  Leave mouse selection/scrolling to the terminal");
+        println!("        --no-alt-screen             Draw inline instead of on the alternate screen");
+        println!("        --match-color <FG[:BG]>     Filter match highlight color");
+        println!("        --selected-color <FG[:BG]>  Selected line color");
+        println!("        --highlight <COLOR=REGEX>   Color regex matches (only capture groups, if any)");
+        println!("        --config <FILE>             Read preferences from FILE instead of ~/.config/scry/config.toml\n");
         println!("GitHub: https://github.com/williamkehl/scry");
         println!("License: Unlicense (Public Domain)");
         println!("\nFor more information, run: scry --help");
         return Ok(());
     }
 
-    // Check if API key is set before starting TUI
+    // Preferences from config.toml (or --config), reported before the TUI starts if broken
+    let mut settings = match config::Config::load(cli.config.as_deref()) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            return Err(e.into());
+        }
+    };
+    if let Some(size) = cli.buffer_size {
+        settings.buffer_capacity = size;
+    }
+    if let Some(every) = cli.auto_analyze {
+        settings.auto_analyze_threshold = Some(every);
+    }
+
+    // Without a key the TUI still works: `a` falls back to the local
+    // heuristic, and --view never needed the API
     if let Err(e) = config::get_api_key() {
-        eprintln!("Error: {}", e);
-        eprintln!("\nTo set your API key, run: scry key YOUR_API_KEY");
-        return Err(e.into());
+        eprintln!("Warning: {}", e);
+        eprintln!("Starting without AI analysis; 'a' will pick a view with the local heuristic.");
     }
 
     // Run TUI with proper cleanup
-    let result = run_tui(stdin_is_tty).await;
+    let result = run_tui(stdin_is_tty, &cli, &settings).await;
 
     // Ensure terminal is restored even on error/panic
     let _ = disable_raw_mode();
-    let _ = execute!(
-        io::stdout(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    );
+    leave_screen();
 
     result
 }
 
-async fn run_tui(stdin_is_tty: bool) -> Result<(), Box<dyn std::error::Error>> {
+/// Ask a yes/no question on the terminal; anything but y/yes means no
+fn confirm(question: &str) -> io::Result<bool> {
+    use std::io::Write;
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Read all of stdin, keep the last `tail` lines if given, and print them
+/// formatted with a built-in view
+fn render_to_stdout(
+    view: &views::ViewKind,
+    tail: Option<usize>,
+    json_max_depth: usize,
+    ingest: &input_source::IngestOptions,
+) -> io::Result<()> {
+    use std::collections::VecDeque;
+    use std::io::Write;
+
+    let stdin = io::stdin();
+    let mut reader = BufReader::new(stdin.lock());
+    let mut lines = VecDeque::new();
+    let mut processor = ingest.processor(true);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        let eof = reader.read_until(b'\n', &mut buf)? == 0;
+        let complete = if eof {
+            processor.finish()
+        } else {
+            // Invalid UTF-8 is converted lossily rather than rejected
+            let line = String::from_utf8_lossy(&buf)
+                .trim_end_matches('\n')
+                .trim_end_matches('\r')
+                .to_string();
+            processor.push(line)
+        };
+        for line in complete {
+            lines.push_back(line);
+            if let Some(n) = tail {
+                if lines.len() > n {
+                    lines.pop_front();
+                }
+            }
+        }
+        if eof {
+            break;
+        }
+    }
+
+    let lines: Vec<String> = lines.into();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for line in views::render_plain_text(view, &lines, json_max_depth) {
+        match writeln!(out, "{}", line) {
+            Ok(()) => {}
+            // Downstream closed (e.g. `| head`) - not an error
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Where log lines are read from
+enum LineInput {
+    /// The output of `scry -- CMD`
+    Command,
+    /// Piped stdin
+    Stdin,
+    /// Nothing yet: stdin is the terminal (`scry --start`)
+    Waiting,
+}
+
+/// Where keypresses are read from
+#[derive(Clone, Copy, PartialEq)]
+enum KeySource {
+    /// stdin is the terminal, so crossterm reads it directly
+    Crossterm,
+    /// stdin carries log data, so keys come from /dev/tty instead
+    DevTty,
+}
+
+/// Pick the line and key sources. `--start` only decides whether the TUI runs
+/// at all when stdin is a terminal; it never changes where input comes from:
+///
+/// | stdin | --start | lines   | keys                        |
+/// |-------|---------|---------|-----------------------------|
+/// | tty   | no      | -       | - (usage is printed instead) |
+/// | tty   | yes     | waiting | crossterm                   |
+/// | pipe  | no      | pipe    | /dev/tty                    |
+/// | pipe  | yes     | pipe    | /dev/tty                    |
+///
+/// `scry -- CMD` reads the command's output instead, with keys chosen by the
+/// same stdin rule (a piped stdin is simply left unread).
+fn input_paths(stdin_is_tty: bool, cli: &Cli) -> (LineInput, KeySource) {
+    let lines = if !cli.command.is_empty() {
+        LineInput::Command
+    } else if stdin_is_tty {
+        LineInput::Waiting
+    } else {
+        LineInput::Stdin
+    };
+    let keys = if stdin_is_tty {
+        KeySource::Crossterm
+    } else {
+        KeySource::DevTty
+    };
+    (lines, keys)
+}
+
+async fn run_tui(stdin_is_tty: bool, cli: &Cli, settings: &config::Config) -> Result<(), Box<dyn std::error::Error>> {
     // stdin_is_tty is passed as parameter to avoid re-checking
     // Check if stdout is a TTY (needed for terminal)
     let stdout_is_tty = atty::is(atty::Stream::Stdout);
 
-    if !stdout_is_tty {
-        return Err("stdout is not a TTY. scry requires a terminal to display the TUI.".into());
+    // Some terminals (certain multiplexers, unusual ptys) aren't detected,
+    // so --force-tui skips the check rather than locking those users out
+    if !stdout_is_tty && !cli.force_tui {
+        return Err("stdout is not a TTY. scry requires a terminal to display the TUI \
+                    (use --force-tui if this is a terminal that isn't detected)."
+            .into());
     }
 
     // Set up signal handler for Ctrl+C (works even when stdin is piped)
@@ -151,49 +531,66 @@ async fn run_tui(stdin_is_tty: bool) -> Result<(), Box<dyn std::error::Error>> {
     })?;
 
     // Create channel for log lines
-    let (log_tx, log_rx) = mpsc::channel::<String>(1000);
+    let (log_tx, log_rx) = mpsc::channel::<(String, LineSource)>(1000);
 
     // Spawn stdin reader task BEFORE terminal setup
+    let (line_input, key_source) = input_paths(stdin_is_tty, cli);
+    let ingest = cli.ingest_options()?;
     let log_tx_clone = log_tx.clone();
-    if stdin_is_tty {
-        // No stdin, send a waiting message
-        tokio::spawn(async move {
-            let _ = log_tx_clone.send("Waiting for log input on stdin...".to_string()).await;
-        });
-    } else {
-        // Read stdin in a blocking task
-        // Accept ANY input - binary data, invalid UTF-8, control chars, etc.
-        let tx = log_tx_clone.clone();
-        tokio::task::spawn_blocking(move || {
-            let stdin = io::stdin();
-            let mut reader = BufReader::new(stdin.lock());
-            let mut line = String::new();
-            loop {
-                line.clear();
-                match reader.read_line(&mut line) {
-                    Ok(0) => break, // EOF
-                    Ok(_) => {
-                        // Accept the line as-is, even if it contains:
-                        // - Control characters
-                        // - Binary data (will be lossy converted to UTF-8)
-                        // - Very long lines
-                        // - Empty lines
-                        // - Special unicode characters
-                        // The views will handle sanitization for display
-                        let trimmed = line.trim_end_matches('\n').trim_end_matches('\r').to_string();
-                        // Accept even empty lines - they're valid log input
-                        if tx.blocking_send(trimmed).is_err() {
+    match line_input {
+        LineInput::Command => {
+            // Run the command ourselves so stdout and stderr can be told apart
+            input_source::spawn_command(&cli.command, log_tx_clone, &ingest)?;
+        }
+        LineInput::Waiting => {
+            // Nothing to read; the UI says it's waiting until a line arrives
+        }
+        LineInput::Stdin => {
+            // Read stdin in a blocking task
+            // Accept ANY input - binary data, invalid UTF-8, control chars, etc.
+            let tx = log_tx_clone.clone();
+            let mut processor = ingest.processor(true);
+            tokio::task::spawn_blocking(move || {
+                let stdin = io::stdin();
+                let mut reader = BufReader::new(stdin.lock());
+                let mut line = String::new();
+                'read: loop {
+                    line.clear();
+                    match reader.read_line(&mut line) {
+                        Ok(0) => break, // EOF
+                        Ok(_) => {
+                            // Accept the line as-is, even if it contains:
+                            // - Control characters
+                            // - Binary data (will be lossy converted to UTF-8)
+                            // - Very long lines
+                            // - Empty lines
+                            // - Special unicode characters
+                            // The views will handle sanitization for display
+                            let trimmed = line.trim_end_matches('\n').trim_end_matches('\r').to_string();
+                            // Prefix/suffix noise is stripped here, and multi-line JSON
+                            // is held back until the value is complete
+                            // Accept even empty lines - they're valid log input
+                            for line in processor.push(trimmed) {
+                                if tx.blocking_send((line, LineSource::Stdout)).is_err() {
+                                    break 'read;
+                                }
+                            }
+                        }
+                        Err(_) => {
+                            // On read error, try to continue or break gracefully
+                            // This handles cases like broken pipes, etc.
                             break;
                         }
                     }
-                    Err(_) => {
-                        // On read error, try to continue or break gracefully
-                        // This handles cases like broken pipes, etc.
+                }
+                // Pass through a value that never closed before EOF
+                for line in processor.finish() {
+                    if tx.blocking_send((line, LineSource::Stdout)).is_err() {
                         break;
                     }
                 }
-            }
-        });
+            });
+        }
     }
 
     // Setup terminal AFTER stdin reader is spawned
@@ -201,308 +598,216 @@ async fn run_tui(stdin_is_tty: bool) -> Result<(), Box<dyn std::error::Error>> {
     // Note: enable_raw_mode operates on stdout, so it should work even when stdin is piped
     let raw_mode_enabled = enable_raw_mode().is_ok();
     
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
+    enter_screen(wants_alt_screen(cli.no_alt_screen), !cli.no_mouse);
+    let backend = CrosstermBackend::new(io::stdout());
     let mut terminal = Terminal::new(backend)?;
 
     // Detect input source
-    let input_source = input_source::detect_input_source(stdin_is_tty);
+    let input_source = if cli.command.is_empty() {
+        input_source::detect_input_source(stdin_is_tty)
+    } else {
+        format!("Running: {}", cli.command.join(" "))
+    };
     
     // Initialize app state
-    let mut app_state = AppState::new(log_rx, input_source);
+    let mut app_state = AppState::new(log_rx, input_source, settings);
+    app_state.follow_mode = settings.follow_on_start;
+    if let LineInput::Waiting = line_input {
+        app_state.waiting_message = Some("Waiting for log input on stdin...".to_string());
+    }
+
+    // Apply any color overrides to the highlight theme (flags over config.toml)
+    if let Some((fg, bg)) = cli.match_color.or(settings.theme.match_color) {
+        app_state.theme.match_fg = fg;
+        app_state.theme.match_bg = bg.unwrap_or(app_state.theme.match_bg);
+    }
+    if let Some((fg, bg)) = cli.selected_color.or(settings.theme.selected_color) {
+        app_state.theme.selected_fg = fg;
+        app_state.theme.selected_bg = bg.unwrap_or(app_state.theme.selected_bg);
+    }
+    app_state.highlight_rules = cli.highlight.clone();
+    if let Some(ref keys) = cli.logger_keys {
+        app_state.logger_keys = keys.clone();
+    }
+    if let Some(ref keys) = cli.trace_keys {
+        app_state.trace_keys = keys.clone();
+    }
+    app_state.json_max_depth = cli.json_depth;
+    app_state.ai_allowed_keys = cli.ai_keys.clone();
+    app_state.ai_model = cli
+        .model
+        .clone()
+        .or_else(|| settings.model.clone())
+        .unwrap_or_else(|| openai::DEFAULT_MODEL.to_string());
+    if let Some(size) = settings.sample_size {
+        app_state.ai_sample_size = size.clamp(app::MIN_AI_SAMPLE_SIZE, app::MAX_AI_SAMPLE_SIZE);
+    }
+    app_state.sample_above = (cli.sample_above > 0).then_some(cli.sample_above);
+    // --view wins; otherwise start where this input source was left last time
+    let cache_key = input_source::cache_key(&app_state.input_source).map(str::to_string);
+    if let Some(ref view) = cli.view {
+        app_state.active_view = view.clone();
+    } else if let Some(view) = cache_key
+        .as_deref()
+        .and_then(config::cached_view)
+        .and_then(|name| views::ViewKind::from_name(&name))
+    {
+        app_state.set_status(StatusKind::Info, format!("Using the {} view from last time (press 'a' to re-analyze)", view.name()));
+        app_state.active_view = view;
+    } else if let Some(ref view) = settings.default_view {
+        app_state.active_view = view.clone();
+    }
+    if let Some(url) = cli.base_url.clone().or_else(config::get_base_url) {
+        app_state.ai_base_url = url;
+    }
+    app_state.show_error_panel = cli.error_panel;
+    if let Some(ref path) = cli.archive {
+        let max_bytes = cli.archive_max_size.unwrap_or(archive::DEFAULT_ARCHIVE_MAX_BYTES);
+        app_state.archive = Some(archive::Archive::new(path.clone(), max_bytes));
+    }
+    if let Some(ref filter) = cli.filter {
+        app_state.set_filter(filter.clone());
+    }
 
     // Channel for analysis results
-    let (analysis_tx, mut analysis_rx) = mpsc::channel::<(views::ViewKind, String)>(10);
+    let (analysis_tx, mut analysis_rx) = mpsc::channel::<AnalysisResult>(10);
 
-    // When stdin is piped, use /dev/tty for keyboard input
-    let keyboard_rx = if !stdin_is_tty {
-        let (tx, rx) = sync_mpsc::channel();
-        let _handle = keyboard::spawn_keyboard_reader(tx)?;
-        Some(rx)
-    } else {
-        None
+    // When stdin is piped, use /dev/tty for keyboard input (see input_paths)
+    let (keyboard_rx, mut keyboard_reader) = match key_source {
+        KeySource::DevTty => {
+            let (tx, rx) = sync_mpsc::channel();
+            let reader = keyboard::spawn_keyboard_reader(tx)?;
+            (Some(rx), Some(reader))
+        }
+        KeySource::Crossterm => (None, None),
     };
 
-    // Main event loop
+    // How long the main loop waits for a keypress before checking for new lines
+    let poll_interval = Duration::from_millis(settings.frame_rate_ms);
+
+    // Main event loop. The screen is only redrawn when something changed:
+    // new lines, a keypress, an analysis result, a resize, or an animation tick.
     let mut should_quit = false;
+    let mut needs_redraw = true;
+    let mut last_draw = Instant::now();
+    let mut last_size = terminal.size().ok();
+    let mut last_line_at = Instant::now();
     while !should_quit && !should_quit_signal.load(Ordering::Relaxed) {
-        // Process incoming log lines
-        while let Ok(line) = app_state.log_receiver.try_recv() {
-            app_state.add_log(line);
+        // Process incoming log lines (left queued while paused)
+        let ingest_started = Instant::now();
+        while !app_state.paused {
+            let Ok((line, source)) = app_state.log_receiver.try_recv() else {
+                break;
+            };
+            app_state.add_log(line, source);
+            needs_redraw = true;
+            last_line_at = Instant::now();
+            if ingest_started.elapsed() >= INGEST_BUDGET {
+                break;
+            }
+        }
+        app_state.flush_archive(last_line_at.elapsed(), false);
+
+        // If a result never arrived, don't leave the status stuck on "Calling OpenAI API..."
+        if app_state.analysis_timed_out() {
+            app_state.analysis_started = None;
+            app_state.set_status(StatusKind::Error, "Analysis result was lost. Press 'a' to try again.".to_string());
+            needs_redraw = true;
+        }
+
+        // With --auto-analyze, analyze again once enough new lines are in. Without
+        // a key this would only keep swapping in the heuristic's guess, so skip it.
+        if app_state.auto_analyze_due() {
+            app_state.last_auto_analyzed_count = app_state.total_lines();
+            if config::has_api_key() {
+                handle_action(Action::Analyze, &mut app_state, &analysis_tx);
+                needs_redraw = true;
+            }
         }
 
         // Process analysis results
-        while let Ok((view_kind, summary)) = analysis_rx.try_recv() {
+        while let Ok((view_kind, summary, exchange, raw_response, note)) = analysis_rx.try_recv() {
+            needs_redraw = true;
+            app_state.analysis_started = None;
+            app_state.raw_ai_response = raw_response;
+            if let Some((exchange, lines_seen)) = exchange {
+                app_state.record_ai_exchange(exchange, lines_seen);
+            }
             app_state.set_view(view_kind.clone());
-            app_state.set_model_response(summary);
+            // Only the model's own pick comes with a note; without one it fell back
+            let kind = if note.is_some() { StatusKind::Info } else { StatusKind::Error };
+            app_state.ai_pick = note.map(|note| (view_kind.clone(), note));
+            app_state.set_status(kind, summary);
             
             // If external tool is selected, launch it
             if let views::ViewKind::ExternalTool(tool_name) = &view_kind {
-                // Restore terminal before launching external tool
-                let _ = disable_raw_mode();
-                let _ = execute!(
-                    io::stdout(),
-                    LeaveAlternateScreen,
-                    DisableMouseCapture
-                );
-                terminal.show_cursor().ok();
-                
-                // Launch external tool
-                let registry = plugins::ToolRegistry::new();
-                if let Some(tool) = registry.get(tool_name) {
-                    if tool.is_available() {
-                        let logs = app_state.log_buffer.clone();
-                        match tool.spawn_with_logs(&logs).await {
-                            Ok(_) => {
-                                // Tool exited successfully, return to scry
-                            }
-                            Err(e) => {
-                                eprintln!("\nError launching {}: {}\nPress Enter to continue...", tool_name, e);
-                                let mut buf = String::new();
-                                let _ = io::stdin().read_line(&mut buf);
-                            }
-                        }
-                    } else {
-                        eprintln!("\n{} is not installed. Falling back to built-in view.\nPress Enter to continue...", tool_name);
-                        let mut buf = String::new();
-                        let _ = io::stdin().read_line(&mut buf);
-                        // Fallback to Json view
-                        app_state.set_view(views::ViewKind::Json);
-                    }
+                let launched = launch_external_tool(&mut terminal, &mut app_state, tool_name, keyboard_rx.as_ref(), wants_alt_screen(cli.no_alt_screen), !cli.no_mouse).await;
+                if !launched {
+                    // Fallback to Json view
+                    app_state.set_view(views::ViewKind::Json);
                 }
-                
-                // Re-enter alternate screen and re-enable raw mode
-                let _ = enable_raw_mode();
-                let _ = execute!(
-                    io::stdout(),
-                    EnterAlternateScreen,
-                    EnableMouseCapture
-                );
             }
         }
 
+        // A tool picked from the `T` menu
+        if let Some(tool_name) = app_state.pending_tool.take() {
+            launch_external_tool(&mut terminal, &mut app_state, &tool_name, keyboard_rx.as_ref(), wants_alt_screen(cli.no_alt_screen), !cli.no_mouse).await;
+            needs_redraw = true;
+        }
+
+        // Resizes aren't reported on the /dev/tty path, so check the size directly
+        let size = terminal.size().ok();
+        if size != last_size {
+            last_size = size;
+            needs_redraw = true;
+        }
+
+        // Animations only need a redraw every tick, not every loop
+        if app_state.is_animating() && last_draw.elapsed() >= ANIMATION_TICK {
+            needs_redraw = true;
+        }
+
         // Draw UI
-        terminal.draw(|f| ui(f, &app_state))?;
+        if needs_redraw {
+            terminal.draw(|f| ui(f, &app_state))?;
+            needs_redraw = false;
+            last_draw = Instant::now();
+        }
 
         // Handle events
         // When stdin is piped, read from /dev/tty channel; otherwise use crossterm
         if let Some(ref kb_rx) = keyboard_rx {
-            // Read from /dev/tty keyboard channel
-            while let Ok(event) = kb_rx.try_recv() {
+            // Read from /dev/tty keyboard channel, waiting briefly for the
+            // first key so an idle loop doesn't spin
+            let first = match kb_rx.recv_timeout(poll_interval) {
+                Ok(event) => Some(event),
+                Err(sync_mpsc::RecvTimeoutError::Timeout) => None,
+                Err(sync_mpsc::RecvTimeoutError::Disconnected) => {
+                    // No keyboard (e.g. /dev/tty unavailable); still pace the loop
+                    std::thread::sleep(poll_interval);
+                    None
+                }
+            };
+            for event in first.into_iter().chain(std::iter::from_fn(|| kb_rx.try_recv().ok())) {
                 if let Event::Key(key) = event {
                     if key.kind == KeyEventKind::Press {
-                        match key.code {
-                            KeyCode::Char('q') => {
-                                should_quit = true;
-                            }
-                            KeyCode::Char('a') => {
-                                // Check if API key is set before analyzing
-                                if !config::has_api_key() {
-                                    app_state.set_model_response("API key not set. Run 'scry key YOUR_API_KEY' to set it.".to_string());
-                                } else {
-                                    // Show API call status
-                                    app_state.set_model_response("Calling OpenAI API (gpt-4o-mini) to analyze logs...".to_string());
-                                    
-                                    // Trigger analysis
-                                    let logs = app_state.log_buffer.clone();
-                                    let tx = analysis_tx.clone();
-                                    
-                                    tokio::spawn(async move {
-                                        match openai::analyze_logs(&logs).await {
-                                            Ok((view_kind, summary)) => {
-                                                let _ = tx.send((view_kind, summary)).await;
-                                            }
-                                            Err(e) => {
-                                                let _ = tx.send((
-                                                    views::ViewKind::Plain,
-                                                    format!("OpenAI API error: {}", e),
-                                                )).await;
-                                            }
-                                        }
-                                    });
-                                }
-                            }
-                            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                                should_quit = true;
-                            }
-                            KeyCode::Up => {
-                                // Scroll up or move selection up
-                                if let Some(selected) = app_state.selected_index {
-                                    if selected > 0 {
-                                        app_state.select_line(selected - 1);
-                                        // Update scroll to follow selection
-                                        let display_count = app_state.get_display_count();
-                                        if let Some(display_idx) = app_state.filtered_indices.iter().position(|&i| i == selected - 1) {
-                                            app_state.scroll_offset = display_idx;
-                                        } else if app_state.filtered_indices.is_empty() {
-                                            app_state.scroll_offset = (selected - 1).min(display_count.saturating_sub(1));
-                                        }
-                                    }
-                                } else {
-                                    app_state.scroll_up(1);
-                                }
-                            }
-                            KeyCode::Down => {
-                                // Scroll down or move selection down
-                                if let Some(selected) = app_state.selected_index {
-                                    if selected < app_state.log_buffer.len().saturating_sub(1) {
-                                        app_state.select_line(selected + 1);
-                                        // Update scroll to follow selection
-                                        let display_count = app_state.get_display_count();
-                                        if let Some(display_idx) = app_state.filtered_indices.iter().position(|&i| i == selected + 1) {
-                                            app_state.scroll_offset = display_idx;
-                                        } else if app_state.filtered_indices.is_empty() {
-                                            app_state.scroll_offset = (selected + 1).min(display_count.saturating_sub(1));
-                                        }
-                                    }
-                                } else {
-                                    let display_count = app_state.get_display_count();
-                                    app_state.scroll_down(1, display_count);
-                                }
-                            }
-                            KeyCode::PageUp => {
-                                app_state.scroll_up(10);
-                            }
-                            KeyCode::PageDown => {
-                                let display_count = app_state.get_display_count();
-                                app_state.scroll_down(10, display_count);
-                            }
-                            KeyCode::Home => {
-                                app_state.scroll_offset = 0;
-                                app_state.selected_index = None;
-                            }
-                            KeyCode::End => {
-                                let display_count = app_state.get_display_count();
-                                if display_count > 0 {
-                                    app_state.scroll_offset = display_count.saturating_sub(1);
-                                }
-                            }
-                            KeyCode::Char('f') => {
-                                // Toggle filter mode - select current line
-                                if app_state.selected_index.is_some() {
-                                    app_state.clear_selection();
-                                } else if !app_state.log_buffer.is_empty() {
-                                    let idx = app_state.scroll_offset.min(app_state.log_buffer.len().saturating_sub(1));
-                                    app_state.select_line(idx);
-                                }
-                            }
-                            KeyCode::Char('c') if !key.modifiers.contains(KeyModifiers::CONTROL) => {
-                                // 'c' alone clears selection/filter
-                                app_state.clear_selection();
-                            }
-                            KeyCode::Esc => {
-                                app_state.clear_selection();
-                            }
-                            _ => {}
+                        needs_redraw = true;
+                        if handle_key(key, &mut app_state, &analysis_tx) {
+                            should_quit = true;
                         }
                     }
                 }
             }
         } else if raw_mode_enabled {
             // Use crossterm's event system when stdin is not piped
-            match crossterm::event::poll(std::time::Duration::from_millis(50)) {
+            match crossterm::event::poll(poll_interval) {
                 Ok(true) => {
                     match event::read() {
                         Ok(Event::Key(key)) => {
                             if key.kind == KeyEventKind::Press {
-                                match key.code {
-                                    KeyCode::Char('q') => {
-                                        should_quit = true;
-                                    }
-                                    KeyCode::Char('a') => {
-                                        // Check if API key is set before analyzing
-                                        if !config::has_api_key() {
-                                            app_state.set_model_response("API key not set. Run 'scry key YOUR_API_KEY' to set it.".to_string());
-                                        } else {
-                                            // Show API call status
-                                            app_state.set_model_response("Calling OpenAI API (gpt-4o-mini) to analyze logs...".to_string());
-                                            
-                                            // Trigger analysis
-                                            let logs = app_state.log_buffer.clone();
-                                            let tx = analysis_tx.clone();
-                                            
-                                            tokio::spawn(async move {
-                                                match openai::analyze_logs(&logs).await {
-                                                    Ok((view_kind, summary)) => {
-                                                        let _ = tx.send((view_kind, summary)).await;
-                                                    }
-                                                    Err(e) => {
-                                                        let _ = tx.send((
-                                                            views::ViewKind::Plain,
-                                                            format!("OpenAI API error: {}", e),
-                                                        )).await;
-                                                    }
-                                                }
-                                            });
-                                        }
-                                    }
-                                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                                        should_quit = true;
-                                    }
-                                    KeyCode::Up => {
-                                        if let Some(selected) = app_state.selected_index {
-                                            if selected > 0 {
-                                                app_state.select_line(selected - 1);
-                                                let display_count = app_state.get_display_count();
-                                                if let Some(display_idx) = app_state.filtered_indices.iter().position(|&i| i == selected - 1) {
-                                                    app_state.scroll_offset = display_idx;
-                                                } else if app_state.filtered_indices.is_empty() {
-                                                    app_state.scroll_offset = (selected - 1).min(display_count.saturating_sub(1));
-                                                }
-                                            }
-                                        } else {
-                                            app_state.scroll_up(1);
-                                        }
-                                    }
-                                    KeyCode::Down => {
-                                        if let Some(selected) = app_state.selected_index {
-                                            if selected < app_state.log_buffer.len().saturating_sub(1) {
-                                                app_state.select_line(selected + 1);
-                                                let display_count = app_state.get_display_count();
-                                                if let Some(display_idx) = app_state.filtered_indices.iter().position(|&i| i == selected + 1) {
-                                                    app_state.scroll_offset = display_idx;
-                                                } else if app_state.filtered_indices.is_empty() {
-                                                    app_state.scroll_offset = (selected + 1).min(display_count.saturating_sub(1));
-                                                }
-                                            }
-                                        } else {
-                                            let display_count = app_state.get_display_count();
-                                            app_state.scroll_down(1, display_count);
-                                        }
-                                    }
-                                    KeyCode::PageUp => {
-                                        app_state.scroll_up(10);
-                                    }
-                                    KeyCode::PageDown => {
-                                        let display_count = app_state.get_display_count();
-                                        app_state.scroll_down(10, display_count);
-                                    }
-                                    KeyCode::Home => {
-                                        app_state.scroll_offset = 0;
-                                        app_state.selected_index = None;
-                                    }
-                                    KeyCode::End => {
-                                        let display_count = app_state.get_display_count();
-                                        if display_count > 0 {
-                                            app_state.scroll_offset = display_count.saturating_sub(1);
-                                        }
-                                    }
-                                    KeyCode::Char('f') => {
-                                        if app_state.selected_index.is_some() {
-                                            app_state.clear_selection();
-                                        } else if !app_state.log_buffer.is_empty() {
-                                            let idx = app_state.scroll_offset.min(app_state.log_buffer.len().saturating_sub(1));
-                                            app_state.select_line(idx);
-                                        }
-                                    }
-                                    KeyCode::Char('c') if !key.modifiers.contains(KeyModifiers::CONTROL) => {
-                                        app_state.clear_selection();
-                                    }
-                                    KeyCode::Esc => {
-                                        app_state.clear_selection();
-                                    }
-                                    _ => {}
+                                needs_redraw = true;
+                                if handle_key(key, &mut app_state, &analysis_tx) {
+                                    should_quit = true;
                                 }
                             }
                         }
@@ -513,23 +818,630 @@ async fn run_tui(stdin_is_tty: bool) -> Result<(), Box<dyn std::error::Error>> {
                 Ok(false) => {} // No event available
                 Err(_) => {} // Error polling, continue
             }
+        } else {
+            std::thread::sleep(poll_interval);
         }
     }
 
-    // Restore terminal
+    // Write out whatever is still waiting to be archived
+    app_state.flush_archive(Duration::ZERO, true);
+
+    // Remember the view for this input source. External tools aren't saved,
+    // since whether they are installed can change between runs, and neither
+    // are bookmarks, which only last the session.
+    if let Some(ref key) = cache_key {
+        if !matches!(app_state.active_view, views::ViewKind::ExternalTool(_) | views::ViewKind::Bookmarks) {
+            let _ = config::save_view(key, &app_state.active_view.name());
+        }
+    }
+
+    // Restore terminal. The tty reader goes first, since it saved its
+    // settings after raw mode was turned on.
+    if let Some(ref mut reader) = keyboard_reader {
+        reader.stop();
+    }
     if raw_mode_enabled {
         let _ = disable_raw_mode(); // Ignore errors on cleanup
     }
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
+    leave_screen();
     terminal.show_cursor()?;
 
+    // With --output, keep the buffer (unfiltered) once the screen is back
+    if let Some(ref path) = cli.output {
+        match export::write_lines(path, app_state.log_buffer.iter()) {
+            Ok(()) => eprintln!("Saved {} line(s) to {}", app_state.log_buffer.len(), path.display()),
+            Err(e) => eprintln!("Error: {:#}", e),
+        }
+    }
+
     Ok(())
 }
 
+/// Hand the terminal to an external tool with the buffered lines piped in,
+/// then take it back. Returns false if the tool isn't installed.
+async fn launch_external_tool(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app_state: &mut AppState,
+    tool_name: &str,
+    keyboard_rx: Option<&sync_mpsc::Receiver<Event>>,
+    alt_screen: bool,
+    mouse_capture: bool,
+) -> bool {
+    // Restore terminal before launching external tool
+    let _ = disable_raw_mode();
+    leave_screen();
+    terminal.show_cursor().ok();
+
+    let registry = plugins::ToolRegistry::new();
+    let mut launched = true;
+    if let Some(tool) = registry.get(tool_name) {
+        if tool.is_available() {
+            // Lines are piped straight from the buffer, without copying it
+            if let Err(e) = tool.spawn_with_logs(app_state.log_buffer.make_contiguous()).await {
+                eprintln!("\nError launching {}: {}\nPress Enter to continue...", tool_name, e);
+                wait_for_enter(keyboard_rx);
+            }
+        } else {
+            eprintln!("\n{} is not installed. Falling back to built-in view.\nPress Enter to continue...", tool_name);
+            wait_for_enter(keyboard_rx);
+            launched = false;
+        }
+    }
+
+    // Re-enter alternate screen and re-enable raw mode
+    let _ = enable_raw_mode();
+    enter_screen(alt_screen, mouse_capture);
+    let _ = terminal.clear();
+    launched
+}
+
+/// Block until the user presses Enter. When stdin is piped it holds log data,
+/// so the keypress comes from the /dev/tty reader instead; reading it directly
+/// here would both eat log lines and race the reader thread for the tty.
+fn wait_for_enter(keyboard_rx: Option<&sync_mpsc::Receiver<Event>>) {
+    match keyboard_rx {
+        Some(rx) => {
+            // Ignore anything typed before the prompt appeared
+            while rx.try_recv().is_ok() {}
+            while let Ok(event) = rx.recv() {
+                if let Event::Key(KeyEvent { code: KeyCode::Enter, .. }) = event {
+                    break;
+                }
+            }
+        }
+        None => {
+            let mut buf = String::new();
+            let _ = io::stdin().read_line(&mut buf);
+        }
+    }
+}
+
+/// Handle a single key press, from either /dev/tty or crossterm.
+/// Returns true if scry should quit.
+fn handle_key(
+    key: KeyEvent,
+    app_state: &mut AppState,
+    analysis_tx: &mpsc::Sender<AnalysisResult>,
+) -> bool {
+    // While the raw response popup is open, Esc only closes it
+    if app_state.show_raw_response && key.code == KeyCode::Esc {
+        app_state.show_raw_response = false;
+        return false;
+    }
+
+    // While the histogram popup is open, Esc only closes it
+    if app_state.histogram.is_some() && key.code == KeyCode::Esc {
+        app_state.histogram = None;
+        return false;
+    }
+
+    // While the tool picker is open, keys drive the menu
+    if app_state.tool_picker.is_some() {
+        return handle_tool_picker_key(key, app_state);
+    }
+
+    // While an input box is open, keys edit its text
+    if app_state.input_mode != InputMode::Normal {
+        return handle_input_key(key, app_state);
+    }
+
+    // While the settings screen is open (and no value is being typed), keys drive the list
+    if app_state.settings_screen.is_some() {
+        return handle_settings_key(key, app_state);
+    }
+
+    // Taken out while looking up the key, since the lookup reads app_state
+    let mut pending = std::mem::take(&mut app_state.pending_keys);
+    let action = app_state.keymap.action_for(&mut pending, &key, |action| action_applies(action, app_state));
+    app_state.pending_keys = pending;
+    match action {
+        Some(action) => handle_action(action, app_state, analysis_tx),
+        None => false,
+    }
+}
+
+/// Whether an action means anything in the current view, so that a key
+/// shared with another action (Space, +/-, d) goes to the one that does
+fn action_applies(action: Action, app_state: &AppState) -> bool {
+    match action {
+        Action::JsonTree => app_state.active_view == views::ViewKind::Json,
+        Action::ToggleNode => app_state.json_tree_active(),
+        Action::Flatten => app_state.active_view == views::ViewKind::Json,
+        Action::Dedupe => app_state.active_view == views::ViewKind::Plain,
+        Action::MoreFilterContext | Action::LessFilterContext => app_state.has_filter(),
+        _ => true,
+    }
+}
+
+/// Carry out a main screen action, whichever key it is bound to.
+/// Returns true if scry should quit.
+fn handle_action(
+    action: Action,
+    app_state: &mut AppState,
+    analysis_tx: &mpsc::Sender<AnalysisResult>,
+) -> bool {
+    match action {
+        Action::Quit => {
+            return true;
+        }
+        Action::RegexFilter => {
+            // Type a regex filter, applied as it is typed
+            app_state.open_filter_input();
+        }
+        Action::Histogram => {
+            // Ask for a numeric field, then chart its distribution
+            app_state.open_histogram_prompt();
+        }
+        Action::Settings => {
+            // Settings screen: view and change config.toml values
+            app_state.settings_screen = Some(0);
+        }
+        Action::Tools => {
+            // Pick an external tool to open the buffer in
+            app_state.tool_picker = Some(plugins::ToolPicker::new());
+        }
+        Action::Analyze => {
+            // Check if API key is set before analyzing
+            if !config::has_api_key() {
+                let view_kind = views::ViewKind::guess(&app_state.heuristic_logs());
+                app_state.set_status(StatusKind::Error, format!(
+                    "AI unavailable — used heuristic ({}). API key not set: run 'scry key YOUR_API_KEY' or set OPENAI_API_KEY.",
+                    view_kind.name()
+                ));
+                app_state.set_view(view_kind);
+                app_state.ai_pick = None;
+            } else if app_state.analysis_started.is_some() {
+                // Only one analysis at a time, so results can't pile up in the channel
+                app_state.set_status(StatusKind::Pending, "Analysis already in progress...".to_string());
+            } else {
+                app_state.analysis_started = Some(Instant::now());
+                
+                // Trigger analysis
+                // A range selection is analyzed on its own, as a fresh request that
+                // doesn't become part of the follow-up history. Otherwise follow-ups
+                // only send lines that arrived since the last analysis.
+                let range_logs = app_state.range_logs();
+                let is_range = range_logs.is_some();
+                let (logs, sample_size, history) = match range_logs {
+                    Some(logs) => {
+                        app_state.set_status(StatusKind::Pending, format!(
+                            "Calling OpenAI API ({}) to analyze {} selected lines...",
+                            app_state.ai_model,
+                            logs.len()
+                        ));
+                        let sample_size = logs.len();
+                        (logs, sample_size, Vec::new())
+                    }
+                    None => {
+                        app_state.set_status(StatusKind::Pending, format!("Calling OpenAI API ({}) to analyze logs...", app_state.ai_model));
+                        (app_state.ai_logs_to_send(), app_state.ai_sample_size, app_state.ai_history.clone())
+                    }
+                };
+                let lines_seen = app_state.total_lines();
+                let heuristic_logs = app_state.heuristic_logs();
+                let allowed_keys = app_state.ai_allowed_keys.clone();
+                let model = app_state.ai_model.clone();
+                let base_url = app_state.ai_base_url.clone();
+                let tx = analysis_tx.clone();
+                
+                tokio::spawn(async move {
+                    let mut raw_response = None;
+                    let analysis = openai::analyze_logs(
+                        &logs,
+                        &model,
+                        &base_url,
+                        sample_size,
+                        &history,
+                        allowed_keys.as_deref(),
+                        &mut raw_response,
+                    );
+                    let outcome = tokio::time::timeout(app::ANALYSIS_TIMEOUT, analysis).await;
+                    let result = match outcome {
+                        Ok(Ok((view_kind, summary, note, exchange))) => {
+                            let exchange = (!is_range).then_some((exchange, lines_seen));
+                            (view_kind, summary, exchange, raw_response, Some(note))
+                        }
+                        // Without the AI, still switch to whatever the lines look like
+                        Ok(Err(e)) => {
+                            let view_kind = views::ViewKind::guess(&heuristic_logs);
+                            let summary = format!("AI unavailable — used heuristic ({}). OpenAI API error: {}", view_kind.name(), e);
+                            (view_kind, summary, None, raw_response, None)
+                        }
+                        Err(_) => {
+                            let view_kind = views::ViewKind::guess(&heuristic_logs);
+                            let summary = format!(
+                                "AI unavailable — used heuristic ({}). OpenAI API timed out after {}s",
+                                view_kind.name(),
+                                app::ANALYSIS_TIMEOUT.as_secs()
+                            );
+                            (view_kind, summary, None, None, None)
+                        }
+                    };
+                    // A failed send means the main loop has exited and dropped the
+                    // receiver; there's no UI left to update, so just let the task end
+                    let _ = tx.send(result).await;
+                });
+            }
+        }
+        Action::ExtendUp => {
+            // Grow the range selection
+            app_state.extend_range(true);
+        }
+        Action::ExtendDown => {
+            app_state.extend_range(false);
+        }
+        Action::ScrollUp => {
+            // Scroll up or move selection up (the Json tree always moves by row)
+            if app_state.selected_index.is_some() && !app_state.json_tree_active() {
+                app_state.move_selection(true);
+            } else {
+                app_state.scroll_up(1);
+            }
+        }
+        Action::ScrollDown => {
+            // Scroll down or move selection down
+            if app_state.selected_index.is_some() && !app_state.json_tree_active() {
+                app_state.move_selection(false);
+            } else {
+                let display_count = app_state.scroll_extent();
+                app_state.scroll_down(1, display_count);
+            }
+        }
+        Action::ScrollLeft => {
+            app_state.scroll_horizontal(false);
+        }
+        Action::ScrollRight => {
+            app_state.scroll_horizontal(true);
+        }
+        Action::PageUp => {
+            app_state.scroll_up(10);
+        }
+        Action::PageDown => {
+            let display_count = app_state.scroll_extent();
+            app_state.scroll_down(10, display_count);
+        }
+        Action::GotoLine => {
+            // Type a line number (as in the # gutter) to jump to
+            app_state.input_mode = InputMode::LineNumber(String::new());
+        }
+        Action::NextMatch | Action::PreviousMatch => {
+            // Step between lines matching the filter, like n/N in less
+            if !app_state.has_filter() {
+                app_state.set_status(StatusKind::Error, "No filter to search for. Press '/' first.".to_string());
+            } else if !app_state.jump_to_match(action == Action::NextMatch) {
+                app_state.set_status(StatusKind::Error, "No more matches".to_string());
+            }
+        }
+        Action::Top => {
+            if !app_state.reverse {
+                app_state.follow_mode = false;
+            }
+            app_state.scroll_offset = 0;
+            app_state.horizontal_offset = 0;
+            app_state.selected_index = None;
+        }
+        Action::Bottom => {
+            let display_count = app_state.scroll_extent();
+            if display_count > 0 {
+                app_state.scroll_offset = display_count.saturating_sub(1);
+            }
+            // The last line is the newest one unless reversed, so stay on it as lines arrive
+            if !app_state.reverse {
+                app_state.follow_mode = true;
+            }
+        }
+        Action::Filter => {
+            // Toggle filter mode - select current line
+            if app_state.selected_index.is_some() {
+                app_state.clear_selection();
+            } else if let Some(idx) = app_state.current_index() {
+                app_state.select_line(idx);
+            }
+        }
+        Action::Pause => {
+            // Hold incoming lines so the view stays put; resuming catches up
+            app_state.toggle_pause();
+        }
+        Action::Follow => {
+            // Follow mode: keep the newest line in view as lines arrive
+            app_state.toggle_follow();
+        }
+        Action::JsonTree => {
+            // Json view: switch between the key/value table and a collapsible tree
+            app_state.toggle_json_tree();
+        }
+        Action::ToggleNode => {
+            app_state.toggle_json_node();
+        }
+        Action::IgnoreCase => {
+            // Case-insensitive matching for every filter term
+            app_state.toggle_ignore_case();
+        }
+        Action::Wrap => {
+            // Wrap long lines (KeyValue wraps between pairs)
+            app_state.wrap_lines = !app_state.wrap_lines;
+        }
+        Action::ResetErrors => {
+            // Start the error tally over
+            app_state.error_count = 0;
+        }
+        Action::ErrorPanel => {
+            // Side panel collecting error and warning lines
+            app_state.show_error_panel = !app_state.show_error_panel;
+        }
+        Action::PinFilter => {
+            // Keep the current term and free `f` to add another one
+            let pinned = app_state.pin_filter();
+            if !pinned {
+                app_state.set_status(StatusKind::Error, "No filter to pin. Press 'f' on a line first.".to_string());
+            }
+        }
+        Action::MoreFilterContext | Action::LessFilterContext => {
+            // Lines around each filter match, like grep -C
+            app_state.change_context_lines(action == Action::MoreFilterContext);
+        }
+        Action::MoreContext => {
+            // Send more context to the AI on the next analysis
+            app_state.increase_ai_sample_size();
+        }
+        Action::LessContext => {
+            app_state.decrease_ai_sample_size();
+        }
+        Action::Whitespace => {
+            // Show spaces, tabs and line ends (PlainView)
+            app_state.reveal_whitespace = !app_state.reveal_whitespace;
+        }
+        Action::AnsiColors => {
+            // Color lines by their own ANSI codes instead of stripping them (PlainView)
+            app_state.preserve_ansi = !app_state.preserve_ansi;
+        }
+        Action::Indices => {
+            // Prefix filtered rows with their position in the full buffer
+            app_state.show_original_indices = !app_state.show_original_indices;
+        }
+        Action::LineNumbers => {
+            // Gutter with each line's number in the whole stream (Plain and KeyValue)
+            app_state.show_line_numbers = !app_state.show_line_numbers;
+        }
+        Action::Bookmark => {
+            // Mark the current line to come back to with [b / ]b
+            match app_state.toggle_bookmark() {
+                Some((line, true)) => app_state.set_status(StatusKind::Info, format!("Bookmarked line {}", line)),
+                Some((line, false)) => app_state.set_status(StatusKind::Info, format!("Removed the bookmark on line {}", line)),
+                None => {}
+            }
+        }
+        Action::NextBookmark | Action::PreviousBookmark => {
+            if app_state.bookmarks.is_empty() {
+                app_state.set_status(StatusKind::Error, "No bookmarks yet. Press 'm' on a line first.".to_string());
+            } else if !app_state.jump_to_bookmark(action == Action::NextBookmark) {
+                app_state.set_status(StatusKind::Error, "No bookmarked lines shown".to_string());
+            }
+        }
+        Action::BookmarkView => {
+            // Show only the bookmarked lines, or go back to the view from before
+            if app_state.active_view == views::ViewKind::Bookmarks {
+                app_state.toggle_last_view();
+            } else {
+                app_state.set_view(views::ViewKind::Bookmarks);
+            }
+        }
+        Action::FilterLogger => {
+            // Filter to all lines from the same logger/module as the current line
+            let found = app_state.current_index().and_then(|idx| app_state.filter_by_logger(idx));
+            if found.is_none() && !app_state.log_buffer.is_empty() {
+                app_state.set_status(StatusKind::Error, format!(
+                    "No logger field found (looked for: {})",
+                    app_state.logger_keys.join(", ")
+                ));
+            }
+        }
+        Action::FilterTrace => {
+            // Filter to every line of the current line's request/trace
+            let found = app_state.current_index().and_then(|idx| app_state.filter_by_trace(idx));
+            if found.is_none() && !app_state.log_buffer.is_empty() {
+                app_state.set_status(StatusKind::Error, format!(
+                    "No trace ID found (looked for: {})",
+                    app_state.trace_keys.join(", ")
+                ));
+            }
+        }
+        Action::CopyFilter => {
+            // Copy the active filter as a shell command that reproduces it
+            match app_state.filter_as_grep() {
+                Some(command) => match clipboard::copy(&command) {
+                    Ok(via) => app_state.set_status(StatusKind::Info, format!("Copied via {}: {}", via, command)),
+                    Err(e) => app_state.set_status(StatusKind::Error, format!("Copy failed: {}", e)),
+                },
+                None => app_state.set_status(StatusKind::Error, "No active filter to copy".to_string()),
+            }
+        }
+        Action::CopyLines => {
+            // Copy the raw lines of the range selection (or the current line)
+            let lines = app_state.copy_range_logs();
+            copy_lines(app_state, &lines);
+        }
+        Action::CopyAll => {
+            // Copy every line on screen, as W would write them
+            let lines: Vec<String> = app_state.export_lines().into_iter().cloned().collect();
+            copy_lines(app_state, &lines);
+        }
+        Action::Export => {
+            // Write the lines on screen to scry-export-<time>.log
+            app_state.export_to(&export::export_path());
+        }
+        Action::Save => {
+            // Ask where to write the lines on screen, suggesting an export name
+            app_state.input_mode = InputMode::SavePath(export::export_path().display().to_string());
+        }
+        Action::Flatten => {
+            // Flatten nested JSON into dotted-path rows (request.headers.host)
+            app_state.json_flatten = !app_state.json_flatten;
+        }
+        Action::Dedupe => {
+            // Collapse runs of identical lines in the Plain view
+            app_state.toggle_dedupe();
+        }
+        Action::Expand => {
+            // Expand nested JSON one level deeper
+            app_state.increase_json_depth();
+        }
+        Action::Collapse => {
+            app_state.decrease_json_depth();
+        }
+        Action::Reverse => {
+            // Newest lines at the top instead of the bottom
+            app_state.toggle_reverse();
+        }
+        Action::TimeSort => {
+            // Order by each line's time field, for out-of-order or merged input
+            app_state.toggle_time_sort();
+        }
+        Action::RestoreFilter => {
+            // Undo the last clear by re-applying the previous filter
+            let restored = app_state.restore_last_filter();
+            if !restored {
+                app_state.set_status(StatusKind::Error, "No previous filter to re-apply".to_string());
+            }
+        }
+        Action::RawResponse => {
+            // Show the model's raw JSON reply from the last analysis
+            app_state.show_raw_response = !app_state.show_raw_response;
+        }
+        Action::LastView => {
+            // Flip back to the previously active view
+            app_state.toggle_last_view();
+        }
+        Action::Clear => {
+            // Clear selection/filter
+            app_state.clear_selection();
+        }
+    }
+    false
+}
+
+/// Color of the status bar message: yellow while something is under way,
+/// red for errors and things that couldn't be done, green otherwise
+fn message_color(kind: StatusKind) -> Color {
+    match kind {
+        StatusKind::Info => Color::Green,
+        StatusKind::Pending => Color::Yellow,
+        StatusKind::Error => Color::Red,
+    }
+}
+
+/// Keys while the tool picker is open. Returns true if the app should quit.
+fn handle_tool_picker_key(key: KeyEvent, app_state: &mut AppState) -> bool {
+    let Some(ref mut picker) = app_state.tool_picker else {
+        return false;
+    };
+    match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return true,
+        KeyCode::Up => picker.move_selection(true),
+        KeyCode::Down => picker.move_selection(false),
+        KeyCode::Char('d') | KeyCode::Tab => picker.show_details = !picker.show_details,
+        KeyCode::Enter => match picker.current() {
+            Some((tool, true)) => {
+                app_state.pending_tool = Some(tool.name.clone());
+                app_state.tool_picker = None;
+            }
+            Some((tool, false)) => {
+                let message = format!("{} is not installed (looked for '{}')", tool.name, tool.check_cmd);
+                app_state.set_status(StatusKind::Error, message);
+            }
+            None => {}
+        },
+        KeyCode::Esc | KeyCode::Char('T') => app_state.tool_picker = None,
+        _ => {}
+    }
+    false
+}
+
+/// Keys on the settings screen. Returns true if the app should quit.
+fn handle_settings_key(key: KeyEvent, app_state: &mut AppState) -> bool {
+    let Some(ref mut row) = app_state.settings_screen else {
+        return false;
+    };
+    match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return true,
+        KeyCode::Up => *row = row.saturating_sub(1),
+        KeyCode::Down => *row = (*row + 1).min(config::Setting::ALL.len() - 1),
+        KeyCode::Enter => app_state.open_setting_input(),
+        KeyCode::Esc | KeyCode::Char('S') => app_state.settings_screen = None,
+        _ => {}
+    }
+    false
+}
+
+/// Put raw lines on the clipboard, one per line, and say how it went
+fn copy_lines(app_state: &mut AppState, lines: &[String]) {
+    if lines.is_empty() {
+        app_state.set_status(StatusKind::Error, "No lines to copy".to_string());
+        return;
+    }
+    let text = lines.join("\n");
+    let (kind, message) = match clipboard::copy(&text) {
+        Ok(via) => (StatusKind::Info, format!("Copied {} line(s), {} bytes, via {}", lines.len(), text.len(), via)),
+        Err(e) => (StatusKind::Error, format!("Copy failed: {}", e)),
+    };
+    app_state.set_status(kind, message);
+}
+
+/// Keys while an input box is open. Returns true if the app should quit.
+fn handle_input_key(key: KeyEvent, app_state: &mut AppState) -> bool {
+    let digits_only = matches!(app_state.input_mode, InputMode::LineNumber(_));
+    let (InputMode::FilterInput(ref mut input)
+    | InputMode::HistogramField(ref mut input)
+    | InputMode::Setting(_, ref mut input)
+    | InputMode::LineNumber(ref mut input)
+    | InputMode::SavePath(ref mut input)) = app_state.input_mode
+    else {
+        return false;
+    };
+    match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return true,
+        KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            // Clear the box, e.g. to replace a prefilled value
+            input.clear();
+            app_state.update_filter_input();
+        }
+        KeyCode::Char(_) if key.modifiers.contains(KeyModifiers::CONTROL) => {}
+        KeyCode::Char(c) if digits_only && !c.is_ascii_digit() => {}
+        KeyCode::Char(c) => {
+            input.push(c);
+            app_state.update_filter_input();
+        }
+        KeyCode::Backspace => {
+            input.pop();
+            app_state.update_filter_input();
+        }
+        KeyCode::Enter => app_state.commit_input(),
+        KeyCode::Esc => app_state.cancel_input(),
+        _ => {}
+    }
+    false
+}
+
 fn ui(f: &mut Frame, app_state: &AppState) {
     let chunks = Layout::default()
         .constraints([
@@ -543,25 +1455,79 @@ fn ui(f: &mut Frame, app_state: &AppState) {
     let mut top_text = vec![
         Span::styled("scry", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
         Span::raw(" | "),
-        Span::raw(format!("View: {}", app_state.active_view.name())),
+        Span::raw(format!(
+            "View: {}{}{}",
+            app_state.active_view.name(),
+            if app_state.time_sort { " (by time)" } else { "" },
+            if app_state.reverse { " (newest first)" } else { "" }
+        )),
         Span::raw(" | "),
-        Span::styled("[a]", Style::default().fg(Color::Yellow)),
-        Span::raw(" analyze "),
     ];
+    if app_state.horizontal_offset > 0 && !app_state.wrap_lines {
+        // Rows are scrolled sideways; ← brings the start back
+        top_text.push(Span::styled(
+            format!("←→ {}", app_state.horizontal_offset),
+            Style::default().fg(Color::Yellow),
+        ));
+        top_text.push(Span::raw(" | "));
+    }
+    if app_state.error_count > 0 {
+        top_text.push(Span::styled(
+            format!("✗ {} errors", app_state.error_count),
+            Style::default().fg(Color::White).bg(Color::Red).add_modifier(Modifier::BOLD),
+        ));
+        top_text.push(Span::raw(" | "));
+    }
+    // Hints show whatever the keys are bound to, and nothing for unbound ones
+    let keys = &app_state.keymap;
+    let hint = |action| match keys.label(action) {
+        label if label.is_empty() => String::new(),
+        label => format!("[{}] ", label),
+    };
+    top_text.extend(vec![
+        Span::styled(hint(Action::Analyze), Style::default().fg(Color::Yellow)),
+        Span::raw("analyze "),
+    ]);
     
-    if app_state.filter_text.is_some() {
-        top_text.push(Span::styled("[f]", Style::default().fg(Color::Green)));
-        top_text.push(Span::raw(" filter "));
-    } else {
-        top_text.push(Span::styled("[f]", Style::default().fg(Color::Yellow)));
-        top_text.push(Span::raw(" filter "));
+    let filter_color = if app_state.has_filter() { Color::Green } else { Color::Yellow };
+    top_text.push(Span::styled(hint(Action::Filter), Style::default().fg(filter_color)));
+    top_text.push(Span::raw("filter "));
+
+    if app_state.ignore_case {
+        top_text.push(Span::styled(
+            format!("{}ignore case", hint(Action::IgnoreCase)),
+            Style::default().fg(Color::Black).bg(Color::Green).add_modifier(Modifier::BOLD),
+        ));
+        top_text.push(Span::raw(" "));
     }
     
+    if app_state.paused {
+        let (queued, full) = app_state.queued_lines();
+        let label = if full {
+            format!("{}PAUSED: {}+ waiting, input held", hint(Action::Pause), queued)
+        } else {
+            format!("{}PAUSED: {} waiting", hint(Action::Pause), queued)
+        };
+        top_text.push(Span::styled(
+            label,
+            Style::default().fg(Color::White).bg(Color::Red).add_modifier(Modifier::BOLD),
+        ));
+        top_text.push(Span::raw(" "));
+    }
+
+    if app_state.follow_mode {
+        top_text.push(Span::styled(
+            format!("{}follow", hint(Action::Follow)),
+            Style::default().fg(Color::Black).bg(Color::Green).add_modifier(Modifier::BOLD),
+        ));
+        top_text.push(Span::raw(" "));
+    }
+
     top_text.extend(vec![
         Span::styled("[↑↓]", Style::default().fg(Color::Yellow)),
         Span::raw(" nav "),
-        Span::styled("[q]", Style::default().fg(Color::Yellow)),
-        Span::raw(" quit"),
+        Span::styled(hint(Action::Quit), Style::default().fg(Color::Yellow)),
+        Span::raw("quit"),
     ]);
     let top_paragraph = Paragraph::new(Line::from(top_text))
         .block(Block::default().borders(Borders::ALL))
@@ -569,7 +1535,20 @@ fn ui(f: &mut Frame, app_state: &AppState) {
     f.render_widget(top_paragraph, chunks[0]);
 
     // Main area - render active view
-    app_state.active_view.render(f, chunks[1], app_state);
+    let main_area = if app_state.show_error_panel {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
+            .split(chunks[1]);
+        render_error_panel(f, columns[1], app_state);
+        columns[0]
+    } else {
+        chunks[1]
+    };
+    app_state.active_view.render(f, main_area, app_state);
+    if let Some(ref message) = app_state.waiting_message {
+        render_waiting_message(f, main_area, message);
+    }
 
     // Bottom bar - show input source, API key status, and last model response
     let api_key_status = if config::has_api_key() {
@@ -578,27 +1557,350 @@ fn ui(f: &mut Frame, app_state: &AppState) {
         "API: ✗"
     };
     
-    let status_parts = vec![
+    let mut status_parts = vec![
         app_state.input_source.clone(),
         api_key_status.to_string(),
+        format!("Sample: {}", app_state.ai_sample_size),
     ];
+    if app_state.dropped_lines > 0 {
+        status_parts.push(format!("Dropped: {}", app_state.dropped_lines));
+    }
+    if app_state.is_sampling() {
+        status_parts.push(format!(
+            "Sampling 1/{} ({}/s, {} skipped)",
+            app_state.sample_every,
+            app_state.ingest_rate,
+            app_state.sampled_out
+        ));
+    }
+    if let Some((start, end)) = app_state.range_bounds() {
+        status_parts.push(format!("Range: {} lines", end - start + 1));
+    }
     
-    let status_text = if let Some(ref response) = app_state.last_model_response {
-        format!("{} | {}", status_parts.join(" | "), response)
-    } else {
-        format!("{} | Ready", status_parts.join(" | "))
-    };
-    
+    // The fixed parts are colored by whether a key is set, the message by how it went
     let status_color = if config::has_api_key() {
         Color::Green
     } else {
         Color::Yellow
     };
+    let message = match app_state.last_model_response {
+        Some(ref response) => match app_state.analysis_started {
+            Some(started) => {
+                let frame = (started.elapsed().as_millis() / ANIMATION_TICK.as_millis()) as usize;
+                format!("{} {}", SPINNER[frame % SPINNER.len()], response)
+            }
+            None => response.clone(),
+        },
+        None => "Ready".to_string(),
+    };
+    let status_text = Line::from(vec![
+        Span::raw(format!("{} | ", status_parts.join(" | "))),
+        Span::styled(message, Style::default().fg(message_color(app_state.status_kind))),
+    ]);
     
-    let bottom_paragraph = Paragraph::new(status_text)
-        .block(Block::default().borders(Borders::ALL).title("Status"))
-        .style(Style::default().fg(status_color));
+    let bottom_paragraph = match app_state.input_mode {
+        InputMode::Normal => Paragraph::new(status_text)
+            .block(Block::default().borders(Borders::ALL).title("Status"))
+            .style(Style::default().fg(status_color)),
+        // An input box takes over the status bar while it is open
+        InputMode::FilterInput(ref input)
+        | InputMode::HistogramField(ref input)
+        | InputMode::Setting(_, ref input)
+        | InputMode::LineNumber(ref input)
+        | InputMode::SavePath(ref input) => {
+            let (marker, hint, title) = match app_state.input_mode {
+                InputMode::FilterInput(_) => {
+                    let hint = match app_state.filter_input_error {
+                        Some(ref e) => format!("  {}", e),
+                        None if input.is_empty() => String::new(),
+                        None => format!("  {} matching lines", app_state.filtered_indices.len()),
+                    };
+                    ("/".to_string(), hint, "Regex filter [Enter: keep, Esc: cancel]")
+                }
+                InputMode::Setting(setting, _) => {
+                    let hint = match setting {
+                        config::Setting::Model => "  e.g. gpt-4o-mini",
+                        config::Setting::BaseUrl => "  blank for https://api.openai.com/v1",
+                        config::Setting::MatchColor | config::Setting::SelectedColor => "  FG[:BG], e.g. black:yellow",
+                        config::Setting::BufferCapacity => "  lines kept in memory",
+                        config::Setting::SampleSize => "  lines per analysis, 25 to 2000",
+                    };
+                    (format!("{}: ", setting.label()), hint.to_string(), "Setting [Enter: save, Esc: cancel]")
+                }
+                InputMode::LineNumber(_) => (
+                    ":".to_string(),
+                    format!("  line {} to {}", app_state.dropped_lines + 1, app_state.total_lines()),
+                    "Go to line [Enter: jump, Esc: cancel]",
+                ),
+                InputMode::SavePath(_) => (
+                    "file: ".to_string(),
+                    format!("  {} line(s); Ctrl+U clears", app_state.export_lines().len()),
+                    "Save lines [Enter: write, Esc: cancel]",
+                ),
+                _ => (
+                    "field: ".to_string(),
+                    "  e.g. latency_ms".to_string(),
+                    "Histogram [Enter: chart, Esc: cancel]",
+                ),
+            };
+            let prompt = Line::from(vec![
+                Span::styled(marker, Style::default().fg(Color::Yellow)),
+                Span::raw(input.clone()),
+                Span::styled("█", Style::default().fg(Color::Yellow)),
+                Span::styled(hint, Style::default().fg(Color::DarkGray)),
+            ]);
+            Paragraph::new(prompt)
+                .block(Block::default().borders(Borders::ALL).title(title))
+                .style(Style::default().fg(Color::White))
+        }
+    };
     f.render_widget(bottom_paragraph, chunks[2]);
+
+    if app_state.show_raw_response {
+        render_raw_response(f, main_area, app_state);
+    }
+    if let Some(ref sample) = app_state.histogram {
+        render_histogram(f, main_area, sample);
+    }
+    if let Some(ref picker) = app_state.tool_picker {
+        render_tool_picker(f, main_area, picker, app_state);
+    }
+    if let Some(row) = app_state.settings_screen {
+        render_settings(f, main_area, row, app_state);
+    }
+}
+
+/// Side panel with the most recent error and warning lines, newest at the
+/// bottom. It ignores the active filter so problems never go unnoticed.
+fn render_error_panel(f: &mut Frame, area: Rect, app_state: &AppState) {
+    let visible = area.height.saturating_sub(2) as usize;
+    let skip = app_state.problem_lines.len().saturating_sub(visible);
+    let items: Vec<ListItem> = app_state
+        .problem_lines
+        .iter()
+        .skip(skip)
+        .map(|(severity, line)| {
+            let color = match severity {
+                utils::Severity::Error => Color::Red,
+                utils::Severity::Warning => Color::Yellow,
+            };
+            ListItem::new(Span::styled(utils::safe_string_display(line), Style::default().fg(color)))
+        })
+        .collect();
+
+    let title = format!("Errors & warnings ({}) [e]", app_state.problem_lines.len());
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(list, area);
+}
+
+/// Popup over the main area charting a numeric field as horizontal bars,
+/// one per equal-width bucket of its value range
+fn render_histogram(f: &mut Frame, area: Rect, sample: &histogram::FieldSample) {
+    let width = area.width.saturating_sub(4).min(80);
+    let height = area.height.saturating_sub(2).min(14);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let buckets = sample.buckets(height.saturating_sub(2).max(1) as usize);
+    let lines: Vec<Line> = if buckets.is_empty() {
+        vec![Line::from(format!("No numeric values for '{}'", sample.field))]
+    } else {
+        let labels: Vec<String> = buckets
+            .iter()
+            .map(|(low, high, _)| format!("{} – {}", format_number(*low), format_number(*high)))
+            .collect();
+        let label_width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let most = buckets.iter().map(|(_, _, n)| *n).max().unwrap_or(0).max(1);
+        // Room left for the bar after the label, separator and count
+        let bar_room = (width as usize).saturating_sub(label_width + 12).max(1);
+        buckets
+            .iter()
+            .zip(labels)
+            .map(|((_, _, n), label)| {
+                let bar = "█".repeat((n * bar_room).div_ceil(most));
+                Line::from(vec![
+                    Span::raw(format!("{:>w$} │ ", label, w = label_width)),
+                    Span::styled(bar, Style::default().fg(Color::Cyan)),
+                    Span::raw(format!(" {}", n)),
+                ])
+            })
+            .collect()
+    };
+
+    let mut title = format!("{} ({} values", sample.field, sample.values.len());
+    if sample.missing > 0 {
+        title.push_str(&format!(", {} without the field", sample.missing));
+    }
+    title.push_str(") [h: other field, Esc: close]");
+    let paragraph = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .style(Style::default().fg(Color::White));
+    f.render_widget(ClearWidget, popup);
+    f.render_widget(paragraph, popup);
+}
+
+/// Note across the middle of the (empty) main area until the first line
+/// arrives. It is drawn over the view rather than added to the buffer, so it
+/// never turns up in filters, copies or analyses.
+fn render_waiting_message(f: &mut Frame, area: Rect, message: &str) {
+    let inner = Block::default().borders(Borders::ALL).inner(area);
+    if inner.height == 0 {
+        return;
+    }
+    let row = Rect { y: inner.y + inner.height / 2, height: 1, ..inner };
+    let paragraph = Paragraph::new(message)
+        .alignment(Alignment::Center)
+        .style(Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC));
+    f.render_widget(ClearWidget, row);
+    f.render_widget(paragraph, row);
+}
+
+/// Popup listing the external tools, installed ones first. With details
+/// on, the selected tool's full description and command are shown below.
+fn render_tool_picker(f: &mut Frame, area: Rect, picker: &plugins::ToolPicker, app_state: &AppState) {
+    let width = area.width.saturating_sub(4).min(76);
+    let inner_width = width.saturating_sub(2) as usize;
+    let mut lines: Vec<Line> = picker
+        .tools
+        .iter()
+        .enumerate()
+        .map(|(i, (tool, available))| {
+            let (mark, mark_style) = if *available {
+                ("✓", Style::default().fg(Color::Green))
+            } else {
+                ("✗", Style::default().fg(Color::DarkGray))
+            };
+            let style = if i == picker.selected {
+                app_state.theme.selected_style()
+            } else if *available {
+                Style::default().fg(Color::White)
+            } else {
+                Style::default().fg(Color::DarkGray)
+            };
+            let summary = utils::sanitize_for_display(&tool.description, inner_width.saturating_sub(18));
+            Line::from(vec![
+                Span::styled(format!("{} ", mark), mark_style),
+                Span::styled(format!("{:<12} {}", tool.name, summary), style),
+            ])
+        })
+        .collect();
+    if picker.show_details {
+        if let Some((tool, available)) = picker.current() {
+            let mut command = tool.run_cmd.clone();
+            for arg in &tool.args {
+                command.push(' ');
+                command.push_str(arg);
+            }
+            lines.push(Line::from(""));
+            lines.push(Line::from(Span::styled(tool.name.clone(), Style::default().add_modifier(Modifier::BOLD))));
+            lines.push(Line::from(tool.description.clone()));
+            lines.push(Line::from(format!("Installed: {}", if *available { "yes" } else { "no" })));
+            lines.push(Line::from(format!("Runs: {}", command)));
+        }
+    }
+
+    // Long descriptions wrap, so count rows rather than lines
+    let rows: usize = lines.iter().map(|line| line.width().div_ceil(inner_width.max(1)).max(1)).sum();
+    let height = (rows as u16 + 2).min(area.height);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    let title = "External tools [Enter: open, d: details, Esc: close]";
+    let paragraph = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .wrap(Wrap { trim: false })
+        .style(Style::default().fg(Color::White));
+    f.render_widget(ClearWidget, popup);
+    f.render_widget(paragraph, popup);
+}
+
+/// Popup listing the settings and their current values, with the
+/// highlighted one edited in the status bar input box
+fn render_settings(f: &mut Frame, area: Rect, row: usize, app_state: &AppState) {
+    let width = area.width.saturating_sub(4).min(76);
+    let inner_width = width.saturating_sub(2) as usize;
+    let mut lines: Vec<Line> = config::Setting::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, &setting)| {
+            let style = if i == row {
+                app_state.theme.selected_style()
+            } else {
+                Style::default().fg(Color::White)
+            };
+            let value = utils::sanitize_for_display(&app_state.setting_value(setting), inner_width.saturating_sub(18));
+            Line::from(Span::styled(format!("{:<16} {}", setting.label(), value), style))
+        })
+        .collect();
+    let saved_to = match app_state.config_path {
+        Some(ref path) => format!("Saved to {}", path.display()),
+        None => "No config directory: changes last for this session".to_string(),
+    };
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(saved_to, Style::default().fg(Color::DarkGray))));
+
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    let title = "Settings [Enter: edit, Esc: close]";
+    let paragraph = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .wrap(Wrap { trim: false })
+        .style(Style::default().fg(Color::White));
+    f.render_widget(ClearWidget, popup);
+    f.render_widget(paragraph, popup);
+}
+
+/// Compact number for histogram labels: integers without decimals
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+/// Popup over the main area showing the model's last raw reply
+fn render_raw_response(f: &mut Frame, area: Rect, app_state: &AppState) {
+    let text = match app_state.raw_ai_response {
+        // Pretty-print when it parses so nested fields are easy to spot
+        Some(ref raw) => serde_json::from_str::<serde_json::Value>(raw)
+            .ok()
+            .and_then(|value| serde_json::to_string_pretty(&value).ok())
+            .unwrap_or_else(|| raw.clone()),
+        None => "No model response yet. Press 'a' to analyze.".to_string(),
+    };
+
+    let width = area.width.saturating_sub(4).min(80);
+    let height = area.height.saturating_sub(2).min(text.lines().count() as u16 + 2);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let paragraph = Paragraph::new(text)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Model response [m/Esc to close]"),
+        )
+        .wrap(Wrap { trim: false })
+        .style(Style::default().fg(Color::White));
+    f.render_widget(ClearWidget, popup);
+    f.render_widget(paragraph, popup);
 }
 
 
@@ -10,9 +10,66 @@ struct ModelResponse {
     view: String,
     #[serde(default)]
     tool: Option<String>,
+    #[serde(default)]
+    confidence: Option<f64>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+impl ModelResponse {
+    /// Short note on the choice for the view title, e.g. "AI (0.9): structured objects"
+    fn note(&self) -> String {
+        let mut note = "AI".to_string();
+        if let Some(confidence) = self.confidence {
+            note.push_str(&format!(" ({:.1})", confidence.clamp(0.0, 1.0)));
+        }
+        if let Some(reason) = self.reason.as_deref().map(str::trim).filter(|r| !r.is_empty()) {
+            note.push_str(": ");
+            note.push_str(&utils::sanitize_for_display(reason, 60));
+        }
+        note
+    }
 }
 
-pub async fn analyze_logs(logs: &[String]) -> Result<(ViewKind, String)> {
+/// Model used unless --model says otherwise
+pub const DEFAULT_MODEL: &str = "gpt-4o-mini";
+
+/// API root used unless --base-url or the `base_url` config file says otherwise
+pub const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// The chat completions endpoint under an API root; a trailing slash on
+/// the root doesn't double up
+fn chat_completions_url(base_url: &str) -> String {
+    format!("{}/chat/completions", base_url.trim_end_matches('/'))
+}
+
+/// A single chat turn, kept on AppState so follow-up analyses can build on
+/// the previous request/response instead of starting from scratch
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+/// Analyze logs and pick a view. With an empty `history` this is a fresh
+/// analysis of the last `sample_size` lines; otherwise `logs` should hold only
+/// the lines that arrived since the last analysis and they are sent as a
+/// follow-up turn. Requests go to `base_url`, an OpenAI-compatible API root
+/// such as DEFAULT_BASE_URL. If `allowed_keys` is set, JSON object lines are
+/// cut down to just those keys before they leave the machine. Returns the
+/// chosen view, a summary, a short note on why the model chose it, and the new
+/// user/assistant exchange to append to the history. The model's raw reply is
+/// written to `raw_response` as soon as it arrives, so it is available even
+/// when it can't be turned into a view.
+pub async fn analyze_logs(
+    logs: &[String],
+    model_name: &str,
+    base_url: &str,
+    sample_size: usize,
+    history: &[ChatMessage],
+    allowed_keys: Option<&[String]>,
+    raw_response: &mut Option<String>,
+) -> Result<(ViewKind, String, String, Vec<ChatMessage>)> {
     let api_key = config::get_api_key()?;
 
     // Log what we're doing (this will be shown in status bar via the caller)
@@ -21,34 +78,42 @@ pub async fn analyze_logs(logs: &[String]) -> Result<(ViewKind, String)> {
     // Get available external tools
     let registry = ToolRegistry::new();
     let available_tools = registry.get_available();
-    let tool_descriptions = if !available_tools.is_empty() {
-        format!("\n\nExternal tools (if installed):\n{}", registry.get_available_descriptions())
+    // Only mention external tools when at least one is installed; otherwise the
+    // examples alone can nudge the model into suggesting a tool that can't run
+    let (tool_section, tool_format, tool_examples) = if !available_tools.is_empty() {
+        (
+            format!(
+                "External tools (if installed):\n{}\n\nIf an external tool would provide a better viewing experience (e.g., jless for complex JSON, visidata for tabular data, lnav for log files with timestamps), prefer it over built-in views. Otherwise, use a built-in view.\n\n",
+                registry.get_available_descriptions()
+            ),
+            " OR\n{ \"view\": \"ExternalTool\", \"tool\": \"tool_name\" }",
+            "\n{ \"view\": \"ExternalTool\", \"tool\": \"jless\" }\n{ \"view\": \"ExternalTool\", \"tool\": \"visidata\" }",
+        )
     } else {
-        String::new()
+        (String::new(), "", "")
     };
 
     let system_prompt = format!(r#"You are selecting the best terminal UI layout for viewing incoming logs. Respond ONLY with JSON. Available layouts:
 
 Built-in views:
 - Plain: good for freeform unstructured lines.
-- KeyValue: good for lines with key=value pairs.
+- KeyValue: good for lines with key=value or key: value pairs.
+- Logfmt: good for logfmt lines (Go, Heroku) like level=info msg="started up" dur=3ms, especially with quoted values holding spaces; shown as columns per key.
 - Json: good for structured JSON logs.
+- Csv: good for comma- or tab-separated rows (psql, csvkit output), with a header line.
 
-{}
-
-If an external tool would provide a better viewing experience (e.g., jless for complex JSON, visidata for tabular data, lnav for log files with timestamps), prefer it over built-in views. Otherwise, use a built-in view.
-
-Respond with JSON:
+{}Respond with JSON:
 {{ "view": "Plain" }} OR
 {{ "view": "KeyValue" }} OR
+{{ "view": "Logfmt" }} OR
 {{ "view": "Json" }} OR
-{{ "view": "ExternalTool", "tool": "tool_name" }}
+{{ "view": "Csv" }}{}
+
+Also include "confidence" (0 to 1) and "reason" (a few words on what in the logs decided it).
 
 Examples:
-{{ "view": "Plain" }}
-{{ "view": "Json" }}
-{{ "view": "ExternalTool", "tool": "jless" }}
-{{ "view": "ExternalTool", "tool": "visidata" }}"#, tool_descriptions);
+{{ "view": "Plain", "confidence": 0.7, "reason": "freeform text lines" }}
+{{ "view": "Json", "confidence": 0.9, "reason": "structured objects" }}{}"#, tool_section, tool_format, tool_examples);
 
     // Safely prepare logs for OpenAI API
     // Sanitize and truncate to avoid issues with:
@@ -58,9 +123,12 @@ Examples:
     let sample_logs: String = logs
         .iter()
         .rev()
-        .take(100)
+        .take(sample_size)
         .rev()
         .map(|s| {
+            // Drop any JSON keys that aren't allowlisted
+            let allowed = allowed_keys.and_then(|keys| utils::retain_json_keys(s, keys));
+            let s = allowed.as_deref().unwrap_or(s);
             // Sanitize each line for safe API transmission
             // Remove control chars, truncate very long lines
             let sanitized = utils::sanitize_for_display(s, 500); // Max 500 chars per line for API
@@ -69,31 +137,33 @@ Examples:
         .collect::<Vec<_>>()
         .join("\n");
 
+    let prompt = if history.is_empty() {
+        "Analyze these log lines and select the best view:"
+    } else if sample_logs.is_empty() {
+        "The user is still viewing these logs. No new lines have arrived; has the best view changed?"
+    } else {
+        "The user is still viewing these logs; has the best view changed given these new lines?"
+    };
+
     // Truncate total message if it's too long (OpenAI has token limits)
     let max_message_len = 10000; // Reasonable limit
     let user_message = if sample_logs.len() > max_message_len {
         format!(
-            "Analyze these log lines and select the best view:\n\n{}...\n[truncated {} chars]",
+            "{}\n\n{}...\n[truncated {} chars]",
+            prompt,
             &sample_logs[..max_message_len],
             sample_logs.len() - max_message_len
         )
+    } else if sample_logs.is_empty() {
+        prompt.to_string()
     } else {
-        format!(
-            "Analyze these log lines and select the best view:\n\n{}",
-            sample_logs
-        )
+        format!("{}\n\n{}", prompt, sample_logs)
     };
 
-    #[derive(Serialize)]
-    struct Message {
-        role: String,
-        content: String,
-    }
-
     #[derive(Serialize)]
     struct RequestBody {
         model: String,
-        messages: Vec<Message>,
+        messages: Vec<ChatMessage>,
         response_format: ResponseFormat,
     }
 
@@ -103,20 +173,22 @@ Examples:
         type_field: String,
     }
 
-    let model_name = "gpt-4o-mini"; // Using gpt-4o-mini as gpt-5.1-mini doesn't exist yet
-    
+    let user_turn = ChatMessage {
+        role: "user".to_string(),
+        content: user_message,
+    };
+
+    // System prompt, then any earlier turns, then this request
+    let mut messages = vec![ChatMessage {
+        role: "system".to_string(),
+        content: system_prompt.to_string(),
+    }];
+    messages.extend(history.iter().cloned());
+    messages.push(user_turn.clone());
+
     let request_body = RequestBody {
         model: model_name.to_string(),
-        messages: vec![
-            Message {
-                role: "system".to_string(),
-                content: system_prompt.to_string(),
-            },
-            Message {
-                role: "user".to_string(),
-                content: user_message,
-            },
-        ],
+        messages,
         response_format: ResponseFormat {
             type_field: "json_object".to_string(),
         },
@@ -124,7 +196,7 @@ Examples:
 
     // Make the API call to OpenAI
     let response = client
-        .post("https://api.openai.com/v1/chat/completions")
+        .post(chat_completions_url(base_url))
         .header("Authorization", format!("Bearer {}", api_key))
         .header("Content-Type", "application/json")
         .json(&request_body)
@@ -150,14 +222,18 @@ Examples:
     let content = json_response["choices"][0]["message"]["content"]
         .as_str()
         .context("No content in OpenAI response")?;
+    *raw_response = Some(content.to_string());
 
     let model_response: ModelResponse = serde_json::from_str(content)
         .context("Failed to parse model response as JSON")?;
+    let note = model_response.note();
 
     let (view_kind, view_name) = match model_response.view.as_str() {
         "Plain" => (ViewKind::Plain, "Plain".to_string()),
         "KeyValue" => (ViewKind::KeyValue, "KeyValue".to_string()),
+        "Logfmt" => (ViewKind::Logfmt, "Logfmt".to_string()),
         "Json" => (ViewKind::Json, "Json".to_string()),
+        "Csv" => (ViewKind::Csv, "Csv".to_string()),
         "ExternalTool" => {
             let tool_name = model_response.tool
                 .ok_or_else(|| anyhow::anyhow!("ExternalTool view requires 'tool' field"))?;
@@ -183,8 +259,16 @@ Examples:
         }
     };
 
+    let exchange = vec![
+        user_turn,
+        ChatMessage {
+            role: "assistant".to_string(),
+            content: content.to_string(),
+        },
+    ];
+
     // Return summary with API call details
     let summary = format!("OpenAI API ({}) → Selected view: {}", model_name, view_name);
-    Ok((view_kind, summary))
+    Ok((view_kind, summary, note, exchange))
 }
 
@@ -1,8 +1,18 @@
 use anyhow::{Context, Result};
 use std::collections::HashMap;
 use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
 use tokio::process::Command as TokioCommand;
 
+/// How much of a tool's stderr is kept for error reports
+const STDERR_CAPTURE_BYTES: usize = 4096;
+
+/// Number of trailing stderr lines included in an error report
+const STDERR_SNIPPET_LINES: usize = 5;
+
+/// A tool that fails faster than this almost certainly never got going
+const IMMEDIATE_EXIT: Duration = Duration::from_secs(2);
+
 /// Represents an external TUI tool that can be used to view logs
 #[derive(Debug, Clone)]
 pub struct ExternalTool {
@@ -146,35 +156,143 @@ impl ExternalTool {
         cmd.args(&self.args);
         cmd.stdin(Stdio::piped());
         cmd.stdout(Stdio::inherit());
-        cmd.stderr(Stdio::inherit());
+        // stderr is captured (and passed through) so failures can be explained
+        cmd.stderr(Stdio::piped());
 
+        let started = Instant::now();
         let mut child = cmd.spawn()
             .context(format!("Failed to spawn {}", self.name))?;
 
-        // Write logs to stdin
+        // Forward the tool's stderr to ours while keeping the tail of it
+        let stderr_task = child.stderr.take().map(|mut stderr| {
+            tokio::spawn(async move {
+                use tokio::io::{AsyncReadExt, AsyncWriteExt};
+                let mut captured = Vec::new();
+                let mut buf = [0u8; 4096];
+                let mut our_stderr = tokio::io::stderr();
+                while let Ok(n) = stderr.read(&mut buf).await {
+                    if n == 0 {
+                        break;
+                    }
+                    let _ = our_stderr.write_all(&buf[..n]).await;
+                    captured.extend_from_slice(&buf[..n]);
+                    if captured.len() > STDERR_CAPTURE_BYTES {
+                        captured.drain(..captured.len() - STDERR_CAPTURE_BYTES);
+                    }
+                }
+                captured
+            })
+        });
+
+        // Write logs to stdin. A tool that dies immediately closes its stdin,
+        // so hold on to a write error until we know how the tool exited.
+        let mut write_result = Ok(());
         if let Some(mut stdin) = child.stdin.take() {
+            use std::io::Write;
             use tokio::io::AsyncWriteExt;
+
+            // The TUI is already torn down, so large buffers would otherwise
+            // look like a hang before the tool draws its first screen
+            eprint!("Piping {} lines to {}...", logs.len(), self.name);
+            let _ = std::io::stderr().flush();
+
             let log_text = logs.join("\n");
-            stdin.write_all(log_text.as_bytes()).await
-                .context("Failed to write logs to external tool")?;
-            stdin.flush().await
-                .context("Failed to flush logs to external tool")?;
+            write_result = match stdin.write_all(log_text.as_bytes()).await {
+                Ok(()) => stdin.flush().await
+                    .context("Failed to flush logs to external tool"),
+                Err(e) => Err(e).context("Failed to write logs to external tool"),
+            };
             drop(stdin); // Close stdin so tool knows input is done
+
+            // Clear the progress line
+            eprint!("\r\x1b[2K");
+            let _ = std::io::stderr().flush();
         }
 
         // Wait for tool to exit
         let status = child.wait().await
             .context(format!("Failed to wait for {}", self.name))?;
+        let elapsed = started.elapsed();
+        let captured = match stderr_task {
+            Some(task) => task.await.unwrap_or_default(),
+            None => Vec::new(),
+        };
 
         if !status.success() {
-            return Err(anyhow::anyhow!(
-                "{} exited with status: {:?}",
-                self.name,
-                status.code()
-            ));
+            let mut message = format!("{} exited with status: {:?}", self.name, status.code());
+            if elapsed < IMMEDIATE_EXIT {
+                message.push_str(&format!(
+                    " after {}ms (it likely rejected the input format or is missing a dependency)",
+                    elapsed.as_millis()
+                ));
+            }
+            let stderr_text = String::from_utf8_lossy(&captured);
+            let snippet: Vec<&str> = stderr_text
+                .lines()
+                .filter(|l| !l.trim().is_empty())
+                .rev()
+                .take(STDERR_SNIPPET_LINES)
+                .collect();
+            if snippet.is_empty() {
+                message.push_str("\n(no output on stderr)");
+            } else {
+                message.push_str("\nstderr:");
+                for line in snippet.iter().rev() {
+                    message.push_str(&format!("\n  {}", line));
+                }
+            }
+            return Err(anyhow::anyhow!(message));
+        }
+
+        write_result
+    }
+}
+
+/// The `T` tool picker: every registered tool with whether it was installed
+/// when the picker opened (checking runs each tool, so it isn't redone on
+/// every frame)
+pub struct ToolPicker {
+    pub tools: Vec<(ExternalTool, bool)>,
+    pub selected: usize,
+    /// Show the selected tool's full description and command
+    pub show_details: bool,
+}
+
+impl ToolPicker {
+    pub fn new() -> Self {
+        let mut tools: Vec<(ExternalTool, bool)> = ExternalTool::all_tools()
+            .into_iter()
+            .map(|tool| {
+                let available = tool.is_available();
+                (tool, available)
+            })
+            .collect();
+        // Installed tools first, then by name
+        tools.sort_by(|(a, a_ok), (b, b_ok)| b_ok.cmp(a_ok).then_with(|| a.name.cmp(&b.name)));
+        Self {
+            tools,
+            selected: 0,
+            show_details: false,
         }
+    }
 
-        Ok(())
+    pub fn move_selection(&mut self, up: bool) {
+        self.selected = if up {
+            self.selected.saturating_sub(1)
+        } else {
+            (self.selected + 1).min(self.tools.len().saturating_sub(1))
+        };
+    }
+
+    /// The highlighted tool and whether it is installed
+    pub fn current(&self) -> Option<&(ExternalTool, bool)> {
+        self.tools.get(self.selected)
+    }
+}
+
+impl Default for ToolPicker {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
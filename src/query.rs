@@ -0,0 +1,499 @@
+//! Filter terms: plain substrings, regexes, and JSON path expressions (a
+//! small subset of JSONPath such as `$.request.status >= 500`,
+//! `$.user.name == "bob"` or just `$.error`, meaning the field exists and
+//! isn't null).
+
+use crate::utils;
+use regex::{Regex, RegexBuilder};
+use serde_json::Value;
+use std::cmp::Ordering;
+
+/// How a filter term matches lines
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterMode {
+    /// Any line containing the text
+    Literal(String),
+    /// Any line the regex matches
+    Regex(Pattern),
+    /// JSON lines where the expression holds (see JsonQuery)
+    Path(JsonQuery),
+}
+
+impl FilterMode {
+    /// A term taken as typed: a JSON path expression if it parses as one,
+    /// otherwise a substring
+    pub fn literal(text: String) -> Self {
+        match JsonQuery::parse(&text) {
+            Some(query) => FilterMode::Path(query),
+            None => FilterMode::Literal(text),
+        }
+    }
+
+    /// A term typed at the `/` prompt: a JSON path expression, or else a regex
+    pub fn pattern(text: &str) -> Result<Self, String> {
+        if let Some(query) = JsonQuery::parse(text) {
+            return Ok(FilterMode::Path(query));
+        }
+        Pattern::new(text)
+            .map(FilterMode::Regex)
+            .map_err(|e| {
+                // The last line of the error is the reason ("error: unclosed group")
+                let reason = e.to_string().lines().last().unwrap_or_default().to_string();
+                format!("Invalid regex: {}", reason.trim_start_matches("error: "))
+            })
+    }
+
+    /// The term's source text
+    pub fn text(&self) -> &str {
+        match self {
+            FilterMode::Literal(text) => text,
+            FilterMode::Regex(regex) => regex.as_str(),
+            FilterMode::Path(query) => &query.source,
+        }
+    }
+
+    /// The term as shown in view titles; regexes are written `/like this/`
+    pub fn label(&self) -> String {
+        match self {
+            FilterMode::Regex(regex) => format!("/{}/", regex.as_str()),
+            other => other.text().to_string(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text().is_empty()
+    }
+
+    /// Whether the term matches the line. With `ignore_case`, literals and
+    /// regexes compare lowercased; path expressions are unaffected.
+    pub fn is_match(&self, line: &str, ignore_case: bool) -> bool {
+        match self {
+            FilterMode::Literal(text) if ignore_case => find_ignore_case(line, text).is_some(),
+            FilterMode::Literal(text) => line.contains(text.as_str()),
+            FilterMode::Regex(pattern) => pattern.regex(ignore_case).is_match(line),
+            FilterMode::Path(query) => query.matches(line),
+        }
+    }
+
+    /// Byte range of the first non-empty match in `text`, if there is one.
+    /// Path expressions select whole lines and have nothing to highlight.
+    pub fn find(&self, text: &str, ignore_case: bool) -> Option<(usize, usize)> {
+        match self {
+            FilterMode::Literal(term) if term.is_empty() => None,
+            FilterMode::Literal(term) if ignore_case => find_ignore_case(text, term),
+            FilterMode::Literal(term) => text.find(term.as_str()).map(|start| (start, start + term.len())),
+            FilterMode::Regex(pattern) => pattern
+                .regex(ignore_case)
+                .find_iter(text)
+                .find(|m| !m.is_empty())
+                .map(|m| (m.start(), m.end())),
+            FilterMode::Path(_) => None,
+        }
+    }
+
+    /// Byte ranges of every non-empty match in `text`, in order and not
+    /// overlapping. Regexes search the whole text, so `^` and `\b` see what
+    /// comes before each match.
+    pub fn find_iter(&self, text: &str, ignore_case: bool) -> Vec<(usize, usize)> {
+        match self {
+            FilterMode::Literal(term) if term.is_empty() => Vec::new(),
+            FilterMode::Literal(term) if ignore_case => {
+                let mut found = Vec::new();
+                let mut pos = 0;
+                while let Some((start, end)) = find_ignore_case(&text[pos..], term) {
+                    found.push((pos + start, pos + end));
+                    pos += end;
+                }
+                found
+            }
+            FilterMode::Literal(term) => text.match_indices(term.as_str()).map(|(start, m)| (start, start + m.len())).collect(),
+            FilterMode::Regex(pattern) => pattern
+                .regex(ignore_case)
+                .find_iter(text)
+                .filter(|m| !m.is_empty())
+                .map(|m| (m.start(), m.end()))
+                .collect(),
+            FilterMode::Path(_) => Vec::new(),
+        }
+    }
+
+    /// Condition for the equivalent jq program (see terms_as_jq)
+    fn to_jq(&self, ignore_case: bool) -> String {
+        match self {
+            FilterMode::Literal(text) if ignore_case => {
+                format!("ascii_downcase | contains({})", Value::String(text.to_lowercase()))
+            }
+            FilterMode::Literal(text) => format!("contains({})", Value::String(text.clone())),
+            FilterMode::Regex(pattern) if ignore_case => {
+                format!("test({}; \"i\")", Value::String(pattern.as_str().to_string()))
+            }
+            FilterMode::Regex(pattern) => format!("test({})", Value::String(pattern.as_str().to_string())),
+            FilterMode::Path(query) => query.to_jq(),
+        }
+    }
+}
+
+/// A regex term, compiled both as typed and case-insensitively so toggling
+/// case sensitivity doesn't have to recompile the filter and pinned terms
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    exact: Regex,
+    folded: Regex,
+}
+
+impl Pattern {
+    fn new(text: &str) -> Result<Self, regex::Error> {
+        Ok(Self {
+            exact: Regex::new(text)?,
+            folded: RegexBuilder::new(text).case_insensitive(true).build()?,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.exact.as_str()
+    }
+
+    fn regex(&self, ignore_case: bool) -> &Regex {
+        if ignore_case {
+            &self.folded
+        } else {
+            &self.exact
+        }
+    }
+}
+
+/// Byte range of the first case-insensitive occurrence of `needle`, in
+/// positions of `haystack` itself, so highlighting lands on the original
+/// text even where lowercasing would change its length
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<(usize, usize)> {
+    let wanted: Vec<char> = needle.chars().flat_map(char::to_lowercase).collect();
+    if wanted.is_empty() {
+        return None;
+    }
+    'start: for (start, _) in haystack.char_indices() {
+        let mut remaining = wanted.iter().peekable();
+        for (offset, c) in haystack[start..].char_indices() {
+            for lower in c.to_lowercase() {
+                if remaining.next() != Some(&lower) {
+                    continue 'start;
+                }
+            }
+            if remaining.peek().is_none() {
+                return Some((start, start + offset + c.len_utf8()));
+            }
+        }
+        // Ran out of text; later starts are shorter still
+        return None;
+    }
+    None
+}
+
+/// Both compiled forms come from the same source text, so it decides equality
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+/// Comparison operators, longest first so `>=` isn't read as `>`
+const OPERATORS: &[(&str, Op)] = &[
+    ("==", Op::Eq),
+    ("!=", Op::Ne),
+    (">=", Op::Ge),
+    ("<=", Op::Le),
+    (">", Op::Gt),
+    ("<", Op::Lt),
+    ("=", Op::Eq),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl Op {
+    fn as_str(self) -> &'static str {
+        match self {
+            Op::Eq => "==",
+            Op::Ne => "!=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// A parsed `$.path [OP value]` expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonQuery {
+    source: String,
+    path: Vec<Segment>,
+    comparison: Option<(Op, Value)>,
+}
+
+impl JsonQuery {
+    /// Parse an expression. Returns None for text that isn't a path
+    /// expression, so callers can treat it as a plain substring instead.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if !text.starts_with('$') {
+            return None;
+        }
+        let (path_text, comparison) = match find_operator(text) {
+            Some((at, token, op)) => {
+                let value = parse_value(text[at + token.len()..].trim())?;
+                (text[..at].trim_end(), Some((op, value)))
+            }
+            None => (text, None),
+        };
+        let path = parse_path(&path_text[1..])?;
+        if path.is_empty() {
+            return None;
+        }
+        Some(Self {
+            source: text.to_string(),
+            path,
+            comparison,
+        })
+    }
+
+    /// Whether a log line matches. JSON lines are evaluated against the
+    /// path; anything else falls back to a substring match on the compared
+    /// value (or the last key, for an existence check).
+    pub fn matches(&self, line: &str) -> bool {
+        match serde_json::from_str::<Value>(line.trim()) {
+            Ok(json) if json.is_object() || json.is_array() => self.evaluate(&json),
+            _ => line.contains(&self.fallback_term()),
+        }
+    }
+
+    fn evaluate(&self, json: &Value) -> bool {
+        let mut current = json;
+        for segment in &self.path {
+            let next = match segment {
+                Segment::Key(key) => current.get(key.as_str()),
+                Segment::Index(i) => current.get(*i),
+            };
+            match next {
+                Some(value) => current = value,
+                None => return false,
+            }
+        }
+        match self.comparison {
+            None => !current.is_null(),
+            Some((op, ref expected)) => {
+                let ordering = compare(current, expected);
+                match op {
+                    Op::Eq => ordering == Some(Ordering::Equal),
+                    Op::Ne => ordering != Some(Ordering::Equal),
+                    Op::Gt => ordering == Some(Ordering::Greater),
+                    Op::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+                    Op::Lt => ordering == Some(Ordering::Less),
+                    Op::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                }
+            }
+        }
+    }
+
+    fn fallback_term(&self) -> String {
+        match self.comparison {
+            Some((_, Value::String(ref s))) => s.clone(),
+            Some((_, ref value)) => value.to_string(),
+            None => match self.path.last() {
+                Some(Segment::Key(key)) => key.clone(),
+                Some(Segment::Index(i)) => i.to_string(),
+                None => String::new(),
+            },
+        }
+    }
+
+    /// The equivalent jq condition, reading the input as raw lines (`jq -R`)
+    fn to_jq(&self) -> String {
+        let mut path = String::new();
+        for segment in &self.path {
+            match segment {
+                Segment::Key(key) if is_identifier(key) => path.push_str(&format!(".{}", key)),
+                Segment::Key(key) => path.push_str(&format!(".{}", Value::String(key.clone()))),
+                Segment::Index(i) => path.push_str(&format!(".[{}]", i)),
+            }
+        }
+        let condition = match self.comparison {
+            Some((op, ref value)) => format!("{} {} {}", path, op.as_str(), value),
+            None => format!("{} != null", path),
+        };
+        format!("((fromjson? | {}) // false)", condition)
+    }
+}
+
+/// clap value parser for `--filter`: path expressions are checked up front,
+/// anything else is kept as a plain substring
+pub fn parse_filter(text: &str) -> Result<FilterMode, String> {
+    if text.trim_start().starts_with('$') && JsonQuery::parse(text).is_none() {
+        return Err(format!(
+            "invalid JSON path expression '{}' (expected e.g. '$.request.status >= 500')",
+            text
+        ));
+    }
+    Ok(FilterMode::literal(text.to_string()))
+}
+
+/// A jq command applying the filter terms, or None if no term is a path
+/// expression (grep handles those)
+pub fn terms_as_jq<'a>(terms: impl Iterator<Item = &'a FilterMode> + Clone, ignore_case: bool) -> Option<String> {
+    if !terms.clone().any(|term| matches!(term, FilterMode::Path(_))) {
+        return None;
+    }
+    let conditions: Vec<String> = terms.map(|term| term.to_jq(ignore_case)).collect();
+    let program = format!("select({})", conditions.join(" or "));
+    Some(format!("jq -rR {}", utils::shell_quote(&program)))
+}
+
+/// Find the first comparison operator outside a quoted string
+fn find_operator(text: &str) -> Option<(usize, &'static str, Op)> {
+    let mut in_quotes = false;
+    for (i, c) in text.char_indices() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        }
+        if in_quotes {
+            continue;
+        }
+        if let Some(&(token, op)) = OPERATORS.iter().find(|(token, _)| text[i..].starts_with(token)) {
+            return Some((i, token, op));
+        }
+    }
+    None
+}
+
+/// Parse `.a.b[0]["odd key"]` into segments
+fn parse_path(mut rest: &str) -> Option<Vec<Segment>> {
+    let mut path = Vec::new();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            let key = after[..end].trim();
+            if key.is_empty() {
+                return None;
+            }
+            path.push(Segment::Key(key.to_string()));
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']')?;
+            let inner = after[..end].trim();
+            let segment = match inner.parse::<usize>() {
+                Ok(i) => Segment::Index(i),
+                Err(_) => match serde_json::from_str::<Value>(inner) {
+                    Ok(Value::String(key)) => Segment::Key(key),
+                    _ => return None,
+                },
+            };
+            path.push(segment);
+            rest = &after[end + 1..];
+        } else {
+            return None;
+        }
+    }
+    Some(path)
+}
+
+/// A JSON literal, or a bare word taken as a string
+fn parse_value(text: &str) -> Option<Value> {
+    if text.is_empty() {
+        return None;
+    }
+    Some(serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string())))
+}
+
+/// Order two JSON values: numbers numerically, strings lexically, and
+/// anything else only by equality
+fn compare(actual: &Value, expected: &Value) -> Option<Ordering> {
+    match (actual, expected) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        // Numbers logged as strings ("status": "503") still compare numerically
+        (Value::String(a), Value::Number(b)) => a.parse::<f64>().ok()?.partial_cmp(&b.as_f64()?),
+        _ if actual == expected => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(text: &str) -> JsonQuery {
+        JsonQuery::parse(text).unwrap_or_else(|| panic!("{} should parse", text))
+    }
+
+    #[test]
+    fn operators_compare_numbers() {
+        let line = r#"{"request":{"status":500}}"#;
+        for (text, expected) in [
+            ("$.request.status == 500", true),
+            ("$.request.status = 500", true),
+            ("$.request.status != 500", false),
+            ("$.request.status > 499", true),
+            ("$.request.status >= 500", true),
+            ("$.request.status < 500", false),
+            ("$.request.status <= 500", true),
+            ("$.request.status>=501", false),
+        ] {
+            assert_eq!(query(text).matches(line), expected, "{}", text);
+        }
+        // Numbers logged as strings still compare as numbers
+        assert!(query("$.status >= 500").matches(r#"{"status":"503"}"#));
+    }
+
+    #[test]
+    fn quoted_strings_and_keys() {
+        assert!(query(r#"$.user.name == "bob""#).matches(r#"{"user":{"name":"bob"}}"#));
+        assert!(query("$.user.name == bob").matches(r#"{"user":{"name":"bob"}}"#));
+        // An operator inside quotes is part of the value
+        assert!(query(r#"$.msg == "a>=b""#).matches(r#"{"msg":"a>=b"}"#));
+        assert!(query(r#"$["odd key"][1] == 2"#).matches(r#"{"odd key":[1,2]}"#));
+    }
+
+    #[test]
+    fn missing_paths_never_match() {
+        let line = r#"{"a":1,"error":null}"#;
+        assert!(!query("$.missing").matches(line));
+        assert!(!query("$.a.b").matches(line));
+        assert!(!query("$.missing != 1").matches(line));
+        assert!(!query("$.error").matches(line));
+        assert!(query("$.a").matches(line));
+    }
+
+    #[test]
+    fn non_json_lines_fall_back_to_a_substring() {
+        assert!(query("$.status == 500").matches("GET / status 500"));
+        assert!(query("$.timeout").matches("request timeout after 5s"));
+        assert!(!query("$.status == 500").matches("GET / status 200"));
+    }
+
+    #[test]
+    fn malformed_expressions_are_refused() {
+        for text in ["$", "$.", "$..a", "$.a ==", "$[0", "$.a[x]", "$a"] {
+            assert!(JsonQuery::parse(text).is_none(), "{} should not parse", text);
+        }
+        assert!(parse_filter("$.a >=").is_err());
+        assert_eq!(parse_filter("plain text"), Ok(FilterMode::Literal("plain text".to_string())));
+    }
+}
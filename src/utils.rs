@@ -1,10 +1,12 @@
-/// Utility functions for safe log handling
+//! Utility functions for safe log handling
 
 /// Sanitize a string for safe TUI display
+/// - Drops ANSI escape sequences (colors and the like)
 /// - Removes or replaces control characters
 /// - Truncates extremely long lines
 /// - Handles invalid UTF-8 gracefully
 pub fn sanitize_for_display(s: &str, max_len: usize) -> String {
+    let s = strip_ansi(s);
     let mut result = String::with_capacity(s.len().min(max_len));
     
     for ch in s.chars() {
@@ -38,11 +40,92 @@ pub fn sanitize_for_display(s: &str, max_len: usize) -> String {
     result
 }
 
+/// A piece of a line that may hold ANSI escape sequences
+#[derive(Debug)]
+pub enum AnsiPiece<'a> {
+    Text(&'a str),
+    /// Parameters of a color/attribute sequence (`ESC [ 1;32 m`); empty means reset
+    Sgr(Vec<u16>),
+}
+
+/// Split a line into plain text and color sequences. Other escape sequences
+/// (cursor movement, window titles) are dropped, as is a sequence cut off
+/// by the end of the line.
+pub fn split_ansi(s: &str) -> Vec<AnsiPiece<'_>> {
+    let mut pieces = Vec::new();
+    let mut rest = s;
+    while let Some(start) = rest.find('\x1b') {
+        if start > 0 {
+            pieces.push(AnsiPiece::Text(&rest[..start]));
+        }
+        let after = &rest[start + 1..];
+        let consumed = match after.as_bytes().first() {
+            // CSI: parameters and intermediates, then one final byte
+            Some(b'[') => {
+                let body = &after[1..];
+                match body.bytes().position(|b| (0x40..=0x7e).contains(&b)) {
+                    Some(end) => {
+                        if body.as_bytes()[end] == b'm' {
+                            let params = body[..end]
+                                .split(';')
+                                .filter_map(|p| if p.is_empty() { Some(0) } else { p.parse().ok() })
+                                .collect();
+                            pieces.push(AnsiPiece::Sgr(params));
+                        }
+                        1 + end + 1
+                    }
+                    None => after.len(),
+                }
+            }
+            // OSC: runs to BEL or ESC \
+            Some(b']') => match after.find(['\x07', '\x1b']) {
+                Some(end) if after[end..].starts_with('\x07') => end + 1,
+                Some(end) => (end + 2).min(after.len()),
+                None => after.len(),
+            },
+            Some(_) => after.chars().next().map_or(0, char::len_utf8),
+            None => 0,
+        };
+        rest = &after[consumed..];
+    }
+    if !rest.is_empty() {
+        pieces.push(AnsiPiece::Text(rest));
+    }
+    pieces
+}
+
+/// A line without its ANSI escape sequences
+pub fn strip_ansi(s: &str) -> std::borrow::Cow<'_, str> {
+    if !s.contains('\x1b') {
+        return std::borrow::Cow::Borrowed(s);
+    }
+    split_ansi(s)
+        .into_iter()
+        .filter_map(|piece| match piece {
+            AnsiPiece::Text(text) => Some(text),
+            AnsiPiece::Sgr(_) => None,
+        })
+        .collect::<String>()
+        .into()
+}
+
+/// Longest line shown on one row; anything past it is cut with `...`
+pub const MAX_DISPLAY_LEN: usize = 1000;
+
+/// Longest line shown when lines are wrapped, where there is room to read
+/// the whole of a long payload
+pub const MAX_WRAPPED_LEN: usize = 20_000;
+
 /// Safely convert a string to a display-safe version
 /// Handles any input including binary data, invalid UTF-8 sequences, etc.
 pub fn safe_string_display(s: &str) -> String {
+    safe_string_display_within(s, MAX_DISPLAY_LEN)
+}
+
+/// safe_string_display with a custom length limit
+pub fn safe_string_display_within(s: &str, max_len: usize) -> String {
     // First, try to sanitize the string
-    let sanitized = sanitize_for_display(s, 1000); // Max 1000 chars per line
+    let sanitized = sanitize_for_display(s, max_len);
     
     // If the string is empty after sanitization, show a placeholder
     if sanitized.trim().is_empty() {
@@ -52,34 +135,522 @@ pub fn safe_string_display(s: &str) -> String {
     sanitized
 }
 
+/// Render whitespace visibly for debugging whitespace-sensitive logs
+/// - Spaces become `·`, tabs become `→`
+/// - A `¶` marks the end of the line so trailing whitespace is obvious
+/// - Other control characters are sanitized as usual
+pub fn reveal_whitespace(s: &str, max_len: usize) -> String {
+    let mut result = String::with_capacity(s.len().min(max_len) + 3);
+    
+    for ch in s.chars() {
+        if result.len() >= max_len {
+            result.push_str("...");
+            break;
+        }
+        
+        match ch {
+            ' ' => result.push('·'),
+            '\t' => result.push('→'),
+            '\r' => result.push_str("\\r"),
+            '\n' => result.push_str("\\n"),
+            ch if ch.is_control() => result.push('?'),
+            _ => result.push(ch),
+        }
+    }
+    
+    result.push('¶');
+    result
+}
+
+/// Split a logfmt-style line on whitespace, except that a value starting
+/// with `{` or `[` (e.g. `data={"a": 1}`) runs to its balancing bracket so
+/// embedded JSON stays in one piece. An unbalanced value runs to end of line.
+fn logfmt_tokens(line: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut chars = line.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let mut end = line.len();
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+        let mut prev = '\0';
+        while let Some(&(i, c)) = chars.peek() {
+            if depth == 0 && c.is_whitespace() {
+                end = i;
+                break;
+            }
+            if in_string {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == '"' {
+                    in_string = false;
+                }
+            } else if depth > 0 {
+                match c {
+                    '"' => in_string = true,
+                    '{' | '[' => depth += 1,
+                    '}' | ']' => depth -= 1,
+                    _ => {}
+                }
+            } else if (c == '{' || c == '[') && prev == '=' {
+                depth = 1;
+            }
+            prev = c;
+            chars.next();
+        }
+        tokens.push(&line[start..end]);
+    }
+    tokens
+}
+
 /// Safely extract key-value pairs from a line
 /// Handles edge cases like empty keys, special characters, etc.
 pub fn extract_key_value_pairs(line: &str) -> Vec<(String, String)> {
     let mut pairs = Vec::new();
     
-    // Split by whitespace, but be careful with quoted values
-    for part in line.split_whitespace() {
-        // Skip empty parts
-        if part.is_empty() {
-            continue;
-        }
+    for (key, value) in raw_pairs(line) {
+        // Sanitize key and value (JSON values get more room so they still parse)
+        let key = sanitize_for_display(key, 100);
+        let max_value_len = if value.starts_with(['{', '[']) { 2000 } else { 200 };
+        let value = sanitize_for_display(&value, max_value_len);
         
-        // Try to split on '=' but handle edge cases
-        if let Some((key, value)) = part.split_once('=') {
-            // Sanitize key and value
-            let key = sanitize_for_display(key, 100);
-            let value = sanitize_for_display(value, 200);
-            
-            // Only add if key is not empty
-            if !key.is_empty() {
-                pairs.push((key, value));
-            }
+        // Only add if key is not empty
+        if !key.is_empty() {
+            pairs.push((key, value));
         }
     }
     
     pairs
 }
 
+/// The `key=value` and `key: value` pairs of a line, in order and unsanitized.
+/// A `key:` value runs over the following words up to the next key, so
+/// `level: info msg: server started` gives `msg` = `server started`.
+fn raw_pairs(line: &str) -> Vec<(&str, String)> {
+    let mut pairs = Vec::new();
+    // A `key:` still collecting its value
+    let mut open: Option<(&str, Vec<&str>)> = None;
+    for part in logfmt_tokens(line) {
+        let colon = colon_key(part);
+        let equals = part.split_once('=');
+        if colon.is_some() || equals.is_some() {
+            if let Some((key, words)) = open.take() {
+                if !words.is_empty() {
+                    pairs.push((key, words.join(" ")));
+                }
+            }
+        }
+        if let Some(key) = colon {
+            open = Some((key, Vec::new()));
+        } else if let Some((key, value)) = equals {
+            pairs.push((key, value.to_string()));
+        } else if let Some((_, words)) = open.as_mut() {
+            words.push(part);
+        }
+    }
+    if let Some((key, words)) = open {
+        if !words.is_empty() {
+            pairs.push((key, words.join(" ")));
+        }
+    }
+    pairs
+}
+
+/// The key of a `key:` token: a colon right before whitespace, after a name
+/// starting with a letter or `_`. Timestamps such as `12:34:56` never end in
+/// a colon or start with a letter, so they aren't taken for keys.
+fn colon_key(part: &str) -> Option<&str> {
+    let key = part.strip_suffix(':')?;
+    let mut chars = key.chars();
+    let valid = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    valid.then_some(key)
+}
+
+/// Extract the raw value of a named field from a JSON object or key=value line
+/// Returns None if the line has no such field
+pub fn extract_field(line: &str, key: &str) -> Option<String> {
+    // JSON object: top-level key only
+    if let Ok(serde_json::Value::Object(map)) = serde_json::from_str::<serde_json::Value>(line) {
+        return map.get(key).map(|value| match value {
+            serde_json::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        });
+    }
+
+    // key=value (logfmt-style) or key: value pairs, with optional quotes around the value
+    raw_pairs(line)
+        .into_iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| v.trim_matches('"').to_string())
+}
+
+/// Field names that carry a line's log level
+const LEVEL_KEYS: &[&str] = &["level", "lvl", "severity", "loglevel"];
+
+/// The value of a line's first level field, in LEVEL_KEYS order. The line
+/// is parsed once (as JSON, or else into pairs) rather than once per key.
+fn level_field(line: &str) -> Option<String> {
+    if let Ok(serde_json::Value::Object(map)) = serde_json::from_str::<serde_json::Value>(line) {
+        return LEVEL_KEYS.iter().find_map(|key| match map.get(*key)? {
+            serde_json::Value::String(s) => Some(s.clone()),
+            other => Some(other.to_string()),
+        });
+    }
+    let pairs = raw_pairs(line);
+    LEVEL_KEYS
+        .iter()
+        .find_map(|key| pairs.iter().find(|(k, _)| k == key))
+        .map(|(_, v)| v.trim_matches('"').to_string())
+}
+
+/// Level names counted as errors
+const ERROR_LEVELS: &[&str] = &["error", "err", "fatal", "critical", "crit", "panic", "alert", "emerg"];
+
+/// Level names counted as warnings
+const WARN_LEVELS: &[&str] = &["warn", "warning"];
+
+/// Log levels worth calling out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// Heuristic level of a log line: a structured level field (JSON or
+/// key=value) if there is one, otherwise an upper-case level word such as
+/// `ERROR`, `[FATAL]` or `WARN`. Lower-case "error" in free text is ignored
+/// since it shows up in plenty of harmless messages.
+pub fn line_severity(line: &str) -> Option<Severity> {
+    if let Some(level) = level_field(line) {
+        let level = level.to_ascii_lowercase();
+        if ERROR_LEVELS.contains(&level.as_str()) {
+            return Some(Severity::Error);
+        }
+        return WARN_LEVELS.contains(&level.as_str()).then_some(Severity::Warning);
+    }
+    let mut severity = None;
+    for word in line.split(|c: char| !c.is_ascii_alphabetic()) {
+        match word {
+            "ERROR" | "ERR" | "FATAL" | "CRITICAL" | "CRIT" | "PANIC" => return Some(Severity::Error),
+            "WARN" | "WARNING" => severity = Some(Severity::Warning),
+            _ => {}
+        }
+    }
+    severity
+}
+
+/// Log level of a line, for coloring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    /// Map a level name such as `ERROR`, `warning` or `dbg` (any case)
+    fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        if ERROR_LEVELS.contains(&name.as_str()) {
+            return Some(LogLevel::Error);
+        }
+        if WARN_LEVELS.contains(&name.as_str()) {
+            return Some(LogLevel::Warn);
+        }
+        match name.as_str() {
+            "info" | "information" | "notice" => Some(LogLevel::Info),
+            "debug" | "dbg" => Some(LogLevel::Debug),
+            "trace" => Some(LogLevel::Trace),
+            _ => None,
+        }
+    }
+}
+
+/// Log level of a line: a structured level field (JSON `"level":"error"` or
+/// logfmt `level=error`) if there is one, otherwise the first level word
+/// anywhere in the line, in any case. Unlike line_severity this also picks
+/// up lower-case words, since a wrong color costs less than a wrong count.
+pub fn detect_log_level(line: &str) -> Option<LogLevel> {
+    if let Some(level) = level_field(line) {
+        return LogLevel::from_name(&level);
+    }
+    line.split(|c: char| !c.is_ascii_alphabetic())
+        .filter(|word| !word.is_empty())
+        .find_map(LogLevel::from_name)
+}
+
+/// Field names that carry a line's time, in priority order
+const TIME_KEYS: &[&str] = &["time", "ts", "@timestamp", "timestamp"];
+
+/// When a line happened, in microseconds since the Unix epoch, from the first
+/// time field (JSON or key=value) that parses. Values may be ISO 8601
+/// (`2024-05-01T12:00:00.250Z`, `2024-05-01 12:00:00+02:00`) or epoch
+/// numbers in seconds, milliseconds, microseconds or nanoseconds.
+pub fn line_timestamp(line: &str) -> Option<i64> {
+    let values: Vec<String> = match serde_json::from_str::<serde_json::Value>(line) {
+        Ok(serde_json::Value::Object(map)) => TIME_KEYS
+            .iter()
+            .filter_map(|key| map.get(*key))
+            .map(|value| match value {
+                serde_json::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            })
+            .collect(),
+        _ => {
+            let pairs = raw_pairs(line);
+            TIME_KEYS
+                .iter()
+                .filter_map(|key| pairs.iter().find(|(k, _)| k == key))
+                .map(|(_, v)| v.trim_matches('"').to_string())
+                .collect()
+        }
+    };
+    values.iter().find_map(|value| parse_timestamp(value))
+}
+
+/// Parse an ISO 8601 date and time or an epoch number into microseconds.
+/// A time without a zone is taken as UTC.
+fn parse_timestamp(value: &str) -> Option<i64> {
+    let value = value.trim();
+    if let Ok(number) = value.parse::<f64>() {
+        // Guess the unit from the magnitude; seconds stay under 1e11 until the year 5138
+        let scale = match number.abs() {
+            n if n < 1e11 => 1e6,
+            n if n < 1e14 => 1e3,
+            n if n < 1e17 => 1.0,
+            _ => 1e-3,
+        };
+        return number.is_finite().then_some((number * scale) as i64);
+    }
+
+    let bytes = value.as_bytes();
+    if bytes.len() < 19
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || !matches!(bytes[10], b'T' | b't' | b' ')
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+    {
+        return None;
+    }
+    let digits = |range: std::ops::Range<usize>| -> Option<i64> {
+        let s = value.get(range)?;
+        s.bytes().all(|b| b.is_ascii_digit()).then(|| s.parse().ok())?
+    };
+    let (year, month, day) = (digits(0..4)?, digits(5..7)?, digits(8..10)?);
+    let (hour, minute, second) = (digits(11..13)?, digits(14..16)?, digits(17..19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    // Fractional seconds, to microsecond precision
+    let mut rest = &value[19..];
+    let mut micros = 0;
+    if let Some(fraction) = rest.strip_prefix(['.', ',']) {
+        let len = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        micros = format!("{:0<6}", &fraction[..len.min(6)]).parse().ok()?;
+        rest = &fraction[len..];
+    }
+
+    // Zone: Z, +HH:MM, +HHMM or +HH
+    let offset = match rest {
+        "" | "Z" | "z" => 0,
+        _ => {
+            let sign = match rest.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let zone = rest[1..].replace(':', "");
+            if !zone.bytes().all(|b| b.is_ascii_digit()) || !matches!(zone.len(), 2 | 4) {
+                return None;
+            }
+            let hours: i64 = zone[..2].parse().ok()?;
+            let minutes: i64 = zone[2..].parse().unwrap_or(0);
+            sign * (hours * 3600 + minutes * 60)
+        }
+    };
+
+    let seconds = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset;
+    Some(seconds * 1_000_000 + micros)
+}
+
+/// Seconds since the Unix epoch as a UTC `YYYYMMDD-HHMMSS` stamp, for file names
+pub fn format_utc_compact(seconds: i64) -> String {
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+    let time = seconds.rem_euclid(86400);
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// The proleptic Gregorian date that many days after 1970-01-01 (the inverse of days_from_civil)
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Months counted from March, as in days_from_civil
+    let march_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * march_month + 2) / 5 + 1;
+    let month = if march_month < 10 { march_month + 3 } else { march_month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Days from 1970-01-01 to a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // Count years from March, so the leap day falls at the end of the year
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Lines looked at when choosing between comma and tab separators
+pub const DELIMITER_SAMPLE_LINES: usize = 20;
+
+/// Field separator for CSV/TSV lines, by majority vote over the first few
+/// lines: a line votes for tab if it has more tabs than commas
+pub fn detect_delimiter<S: AsRef<str>>(lines: impl Iterator<Item = S>) -> u8 {
+    let (mut tabs, mut commas) = (0, 0);
+    for line in lines.take(DELIMITER_SAMPLE_LINES) {
+        let line = line.as_ref();
+        let tab_count = line.matches('\t').count();
+        let comma_count = line.matches(',').count();
+        if tab_count > comma_count {
+            tabs += 1;
+        } else if comma_count > 0 {
+            commas += 1;
+        }
+    }
+    if tabs > commas { b'\t' } else { b',' }
+}
+
+/// Split one CSV/TSV line into display-safe fields; quoted fields may hold
+/// the delimiter. Empty if the line isn't valid CSV (e.g. an unclosed quote).
+pub fn split_delimited(line: &str, delimiter: u8) -> Vec<String> {
+    csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(line.as_bytes())
+        .records()
+        .next()
+        .and_then(|record| record.ok())
+        .map(|record| record.iter().map(|field| sanitize_for_display(field, 200)).collect())
+        .unwrap_or_default()
+}
+
+/// One logfmt line: its `key=value` pairs in order, and any words that
+/// weren't pairs (a leading timestamp or level, say) joined by spaces.
+/// Everything is display-safe.
+pub struct LogfmtRecord {
+    pub text: String,
+    pub pairs: Vec<(String, String)>,
+}
+
+/// Parse a logfmt line such as `level=info msg="started up" dur=3ms`. Unlike
+/// extract_key_value_pairs, a quoted value may hold spaces and `\"`, `\\`,
+/// `\n` or `\t` escapes; an unclosed quote runs to the end of the line.
+/// None if the line has no `key=value` pair at all.
+pub fn parse_logfmt(line: &str) -> Option<LogfmtRecord> {
+    let mut words = Vec::new();
+    let mut pairs = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(&first) = chars.peek() else {
+            break;
+        };
+        if first == '"' {
+            words.push(logfmt_quoted(&mut chars));
+            continue;
+        }
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|&c| !c.is_whitespace() && c != '=') {
+            key.push(c);
+        }
+        if chars.next_if_eq(&'=').is_none() || key.is_empty() {
+            // A bare word, or a stray `=value`
+            if key.is_empty() {
+                while chars.next_if(|c| !c.is_whitespace()).is_some() {}
+            } else {
+                words.push(key);
+            }
+            continue;
+        }
+        let value = if chars.peek() == Some(&'"') {
+            logfmt_quoted(&mut chars)
+        } else {
+            let mut value = String::new();
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                value.push(c);
+            }
+            value
+        };
+        pairs.push((sanitize_for_display(&key, 100), sanitize_for_display(&value, 200)));
+    }
+    if pairs.is_empty() {
+        return None;
+    }
+    Some(LogfmtRecord {
+        text: sanitize_for_display(&words.join(" "), 200),
+        pairs,
+    })
+}
+
+/// A double-quoted logfmt string starting at `chars`, unescaped
+fn logfmt_quoted(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    chars.next();
+    let mut value = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => break,
+            '\\' => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some('r') => value.push('\r'),
+                Some(other) => value.push(other),
+                None => value.push('\\'),
+            },
+            _ => value.push(c),
+        }
+    }
+    value
+}
+
+/// For a JSON object line, re-serialize it with only the given top-level keys.
+/// Returns None for anything that isn't a JSON object.
+pub fn retain_json_keys(line: &str, keys: &[String]) -> Option<String> {
+    match serde_json::from_str::<serde_json::Value>(line) {
+        Ok(serde_json::Value::Object(mut map)) => {
+            map.retain(|key, _| keys.iter().any(|k| k == key));
+            Some(serde_json::Value::Object(map).to_string())
+        }
+        _ => None,
+    }
+}
+
 /// Safely format a JSON value for display
 pub fn safe_json_display(value: &serde_json::Value) -> String {
     match value {
@@ -104,3 +675,101 @@ pub fn safe_json_display(value: &serde_json::Value) -> String {
     }
 }
 
+/// Quote a string for safe use as a single POSIX shell word
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Escape POSIX extended-regex metacharacters so a string matches literally
+pub fn regex_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for ch in s.chars() {
+        if "\\.[]{}()*+?^$|".contains(ch) {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colon_keys_split_into_pairs() {
+        assert_eq!(
+            raw_pairs("level: info msg: started"),
+            vec![("level", "info".to_string()), ("msg", "started".to_string())]
+        );
+        assert_eq!(
+            raw_pairs("level: info msg: server started"),
+            vec![("level", "info".to_string()), ("msg", "server started".to_string())]
+        );
+    }
+
+    #[test]
+    fn times_and_urls_are_not_colon_keys() {
+        assert_eq!(colon_key("12:34:56"), None);
+        assert_eq!(colon_key("http://x"), None);
+        assert!(raw_pairs("12:34:56").is_empty());
+        assert!(raw_pairs("http://x").is_empty());
+        assert!(raw_pairs("12:34:56 GET http://x").is_empty());
+    }
+
+    #[test]
+    fn colon_key_needs_a_name() {
+        assert_eq!(colon_key("level:"), Some("level"));
+        assert_eq!(colon_key("_id:"), Some("_id"));
+        assert_eq!(colon_key(":"), None);
+        assert_eq!(colon_key("1st:"), None);
+    }
+
+    /// 2024-05-01T12:00:00Z in microseconds
+    const NOON: i64 = 1_714_564_800_000_000;
+
+    #[test]
+    fn timestamps_with_offsets_are_utc() {
+        assert_eq!(parse_timestamp("2024-05-01T12:00:00Z"), Some(NOON));
+        assert_eq!(parse_timestamp("2024-05-01 12:00:00"), Some(NOON));
+        assert_eq!(parse_timestamp("2024-05-01T14:00:00+02:00"), Some(NOON));
+        assert_eq!(parse_timestamp("2024-05-01T14:00:00+0200"), Some(NOON));
+        assert_eq!(parse_timestamp("2024-05-01T07:30:00-04:30"), Some(NOON));
+        assert_eq!(parse_timestamp("2024-05-01T12:00:00+2"), None);
+    }
+
+    #[test]
+    fn fractional_seconds_keep_microseconds() {
+        assert_eq!(parse_timestamp("2024-05-01T12:00:00.250Z"), Some(NOON + 250_000));
+        assert_eq!(parse_timestamp("2024-05-01 12:00:00,123"), Some(NOON + 123_000));
+        assert_eq!(parse_timestamp("2024-05-01T12:00:00.123456789Z"), Some(NOON + 123_456));
+    }
+
+    #[test]
+    fn epoch_units_are_told_apart_by_size() {
+        assert_eq!(parse_timestamp("1714564800"), Some(NOON));
+        assert_eq!(parse_timestamp("1714564800.5"), Some(NOON + 500_000));
+        assert_eq!(parse_timestamp("1714564800000"), Some(NOON));
+        assert_eq!(parse_timestamp("1714564800000000"), Some(NOON));
+        assert_eq!(parse_timestamp("1714564800000000000"), Some(NOON));
+    }
+
+    #[test]
+    fn line_timestamp_reads_json_and_key_value_fields() {
+        assert_eq!(line_timestamp(r#"{"ts":1714564800000,"msg":"hi"}"#), Some(NOON));
+        assert_eq!(line_timestamp(r#"{"time":"2024-05-01T12:00:00Z"}"#), Some(NOON));
+        assert_eq!(line_timestamp("time=2024-05-01T12:00:00Z level=info"), Some(NOON));
+        assert_eq!(line_timestamp("no time here"), None);
+    }
+
+    #[test]
+    fn level_fields_win_over_level_words() {
+        assert_eq!(line_severity(r#"{"level":"error","msg":"WARN ignored"}"#), Some(Severity::Error));
+        assert_eq!(line_severity(r#"{"severity":"warning"}"#), Some(Severity::Warning));
+        assert_eq!(line_severity("level=info msg=\"ERROR in a message\""), None);
+        assert_eq!(line_severity("lvl: warn msg: disk almost full"), Some(Severity::Warning));
+        assert_eq!(line_severity("2024-05-01 FATAL out of memory"), Some(Severity::Error));
+        assert_eq!(detect_log_level(r#"{"loglevel":"DEBUG"}"#), Some(LogLevel::Debug));
+        assert_eq!(detect_log_level("something failed with an error"), Some(LogLevel::Error));
+    }
+}
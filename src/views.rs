@@ -1,27 +1,170 @@
+use crate::query::FilterMode;
 use crate::utils;
 use ratatui::{
-    layout::{Constraint, Rect},
+    layout::{Alignment, Constraint, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, Paragraph, Row, Table},
+    text::{Line, Span, Text},
+    widgets::{Block, Borders, Cell, List, ListItem, Paragraph, Row, Table, TableState},
     Frame,
 };
 use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
+/// Colors used for the selected line and for filter-match highlighting
+#[derive(Clone, Copy)]
+pub struct Theme {
+    pub match_fg: Color,
+    pub match_bg: Color,
+    pub selected_fg: Color,
+    pub selected_bg: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            match_fg: Color::Yellow,
+            match_bg: Color::DarkGray,
+            selected_fg: Color::Yellow,
+            selected_bg: Color::DarkGray,
+        }
+    }
+}
+
+impl Theme {
+    /// Style for text matching the active filter
+    pub fn match_style(&self) -> Style {
+        Style::default()
+            .fg(self.match_fg)
+            .bg(self.match_bg)
+            .add_modifier(Modifier::BOLD)
+    }
+
+    /// Style for the currently selected line
+    pub fn selected_style(&self) -> Style {
+        Style::default().fg(self.selected_fg).bg(self.selected_bg)
+    }
+}
+
+/// Parse a built-in view name for command-line flags
+pub fn parse_builtin_view(name: &str) -> Result<ViewKind, String> {
+    ViewKind::from_name(name)
+        .ok_or_else(|| format!("unknown view '{}' (expected plain, keyvalue, logfmt, json, or csv)", name))
+}
+
+/// Parse a `FG[:BG]` color spec (names like `red`, indexes like `208`, or `#rrggbb`)
+pub fn parse_color_pair(spec: &str) -> Result<(Color, Option<Color>), String> {
+    let parse = |s: &str| {
+        s.trim()
+            .parse::<Color>()
+            .map_err(|_| format!("invalid color '{}'", s.trim()))
+    };
+    match spec.split_once(':') {
+        Some((fg, bg)) => Ok((parse(fg)?, Some(parse(bg)?))),
+        None => Ok((parse(spec)?, None)),
+    }
+}
+
+/// A `--highlight` rule: text matched by `regex` is drawn in `style`. If the
+/// regex has capture groups, only the captured text is colored.
 #[derive(Clone)]
+pub struct HighlightRule {
+    pub regex: regex::Regex,
+    pub style: Style,
+}
+
+/// Parse a `COLOR=REGEX` highlight rule, where COLOR is `FG[:BG]`
+pub fn parse_highlight_rule(spec: &str) -> Result<HighlightRule, String> {
+    let (color, pattern) = spec
+        .split_once('=')
+        .ok_or_else(|| format!("invalid highlight rule '{}' (expected COLOR=REGEX)", spec))?;
+    let (fg, bg) = parse_color_pair(color)?;
+    let regex = regex::Regex::new(pattern).map_err(|e| format!("invalid regex '{}': {}", pattern, e))?;
+    let mut style = Style::default().fg(fg);
+    if let Some(bg) = bg {
+        style = style.bg(bg);
+    }
+    Ok(HighlightRule { regex, style })
+}
+
+/// Lines sampled by ViewKind::guess
+const GUESS_SAMPLE_LINES: usize = 200;
+
+/// Widest a Csv or Logfmt view column gets, however long its longest field
+const MAX_CSV_COLUMN_WIDTH: usize = 40;
+
+#[derive(Clone, PartialEq)]
 pub enum ViewKind {
     Plain,
     KeyValue,
+    Logfmt,
     Json,
+    Csv,
+    Bookmarks, // Only the lines bookmarked with `m`, laid out like Plain
     ExternalTool(String), // Name of external tool (e.g., "jless", "visidata")
 }
 
 impl ViewKind {
+    /// Look up a built-in view by name (case-insensitive)
+    pub fn from_name(name: &str) -> Option<ViewKind> {
+        match name.to_ascii_lowercase().as_str() {
+            "plain" => Some(ViewKind::Plain),
+            "keyvalue" | "kv" => Some(ViewKind::KeyValue),
+            "logfmt" => Some(ViewKind::Logfmt),
+            "json" => Some(ViewKind::Json),
+            "csv" | "tsv" => Some(ViewKind::Csv),
+            _ => None,
+        }
+    }
+
+    /// Pick a view from the lines themselves, for when the AI can't be
+    /// reached: Json if most non-blank lines are JSON objects, KeyValue if
+    /// most have at least two key=value pairs (Logfmt if some of those have
+    /// quoted values, which KeyValue would split), Csv if they split into the
+    /// same number of comma- or tab-separated fields, otherwise Plain. Only
+    /// the newest GUESS_SAMPLE_LINES are looked at.
+    pub fn guess(lines: &[String]) -> ViewKind {
+        let lines: Vec<&str> = lines
+            .iter()
+            .rev()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .take(GUESS_SAMPLE_LINES)
+            .collect();
+        if lines.is_empty() {
+            return ViewKind::Plain;
+        }
+        let majority = |count: usize| count * 2 > lines.len();
+        let json = lines
+            .iter()
+            .filter(|line| serde_json::from_str::<Value>(line).is_ok_and(|v| v.is_object()))
+            .count();
+        if majority(json) {
+            return ViewKind::Json;
+        }
+        let key_value = lines
+            .iter()
+            .filter(|line| utils::extract_key_value_pairs(line).len() >= 2)
+            .count();
+        if majority(key_value) {
+            let quoted = lines.iter().any(|line| line.contains("=\""));
+            if quoted { ViewKind::Logfmt } else { ViewKind::KeyValue }
+        } else if looks_tabular(&lines) {
+            ViewKind::Csv
+        } else {
+            ViewKind::Plain
+        }
+    }
+
     pub fn name(&self) -> String {
         match self {
             ViewKind::Plain => "Plain".to_string(),
             ViewKind::KeyValue => "KeyValue".to_string(),
+            ViewKind::Logfmt => "Logfmt".to_string(),
             ViewKind::Json => "Json".to_string(),
+            ViewKind::Csv => "Csv".to_string(),
+            ViewKind::Bookmarks => "Bookmarks".to_string(),
             ViewKind::ExternalTool(name) => format!("External: {}", name),
         }
     }
@@ -32,10 +175,43 @@ impl ViewKind {
         area: Rect,
         app_state: &crate::app::AppState,
     ) {
+        // Views that scroll sideways measure their widest row as they draw
+        app_state.h_scroll_max.set(0);
+
+        // When the buffer has hit its cap, the top of the list is not the true
+        // start of the stream - say so when scrolled all the way up
+        let at_oldest = if app_state.reverse {
+            app_state.scroll_offset + 1 >= app_state.get_display_count()
+        } else {
+            app_state.scroll_offset == 0
+        };
+        let area = if app_state.dropped_lines > 0
+            && at_oldest
+            && !matches!(self, ViewKind::ExternalTool(_))
+        {
+            // The oldest line sits at the bottom when reversed
+            let (banner_idx, list_idx) = if app_state.reverse { (1, 0) } else { (0, 1) };
+            let mut constraints = [Constraint::Min(0); 2];
+            constraints[banner_idx] = Constraint::Length(1);
+            let chunks = Layout::default()
+                .constraints(constraints)
+                .split(area);
+            let banner = Paragraph::new("── oldest retained line (older lines dropped) ──")
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(Color::DarkGray).add_modifier(Modifier::DIM));
+            f.render_widget(banner, chunks[banner_idx]);
+            chunks[list_idx]
+        } else {
+            area
+        };
+
         match self {
             ViewKind::Plain => PlainView::render(f, area, app_state),
             ViewKind::KeyValue => KeyValueView::render(f, area, app_state),
+            ViewKind::Logfmt => LogfmtView::render(f, area, app_state),
             ViewKind::Json => JsonView::render(f, area, app_state),
+            ViewKind::Csv => CsvView::render(f, area, app_state),
+            ViewKind::Bookmarks => BookmarkView::render(f, area, app_state),
             ViewKind::ExternalTool(name) => {
                 // For external tools, show a message that it will launch
                 // The actual tool will be spawned separately
@@ -45,6 +221,31 @@ impl ViewKind {
     }
 }
 
+/// Whether lines read as CSV/TSV rows: at least three of them, and nearly
+/// all with the same number of fields. Prose with the odd comma varies too
+/// much to pass. Two fields only count under a header row, since Python
+/// and log4j timestamps (`12:00:00,123`) split every line in two.
+fn looks_tabular(lines: &[&str]) -> bool {
+    if lines.len() < 3 {
+        return false;
+    }
+    let delimiter = utils::detect_delimiter(lines.iter());
+    let mut counts: HashMap<usize, usize> = HashMap::new();
+    for line in lines {
+        *counts.entry(utils::split_delimited(line, delimiter).len()).or_default() += 1;
+    }
+    let (fields, rows) = counts.into_iter().max_by_key(|&(_, rows)| rows).unwrap_or_default();
+    let steady = rows * 10 >= lines.len() * 9;
+    steady && (fields >= 3 || (fields == 2 && looks_like_header(lines[0], delimiter)))
+}
+
+/// A first row of column names: every field non-empty and free of digits
+fn looks_like_header(line: &str, delimiter: u8) -> bool {
+    let fields = utils::split_delimited(line, delimiter);
+    !fields.is_empty()
+        && fields.iter().all(|field| !field.trim().is_empty() && !field.chars().any(|c| c.is_ascii_digit()))
+}
+
 pub struct PlainView;
 
 impl PlainView {
@@ -53,8 +254,27 @@ impl PlainView {
         area: Rect,
         app_state: &crate::app::AppState,
     ) {
-        let display_logs = app_state.get_display_logs();
+        let title = if let Some(filter) = app_state.filter_label() {
+            format!("Log Lines (filtered: '{}', {} matches{})", filter, app_state.filtered_indices.len(), context_note(app_state))
+        } else {
+            "Log Lines".to_string()
+        };
+        Self::render_titled(f, area, app_state, title);
+    }
+
+    /// The displayed lines as a list, under `title` and the usual markers
+    fn render_titled(
+        f: &mut Frame,
+        area: Rect,
+        app_state: &crate::app::AppState,
+        mut title: String,
+    ) {
+        let display_logs = app_state.get_display_runs();
         let display_count = display_logs.len();
+        let terms = filter_highlights(app_state);
+        let wrap_width = inner_width(area);
+        // Wrapped lines have the room to be shown (nearly) in full
+        let max_len = if app_state.wrap_lines { utils::MAX_WRAPPED_LEN } else { utils::MAX_DISPLAY_LEN };
         
         // Ensure scroll_offset is valid
         let scroll_offset = if display_count > 0 {
@@ -63,40 +283,79 @@ impl PlainView {
             0
         };
         
-        // Create items for all display_logs (ratatui List handles scrolling internally)
+        // Create items for all display_logs (ratatui List handles scrolling internally).
+        // A wrapped line is one multi-row item, so scroll_offset and the display
+        // count still count lines rather than screen rows, and the List keeps the
+        // whole of the current line in view where it fits.
         let items: Vec<ListItem> = display_logs
             .iter()
-            .enumerate()
-            .map(|(_display_idx, (original_idx, line))| {
+            .map(|(original_idx, line, repeats)| {
                 // Sanitize line for safe display
-                let safe_line = utils::safe_string_display(line);
+                let safe_line = if app_state.reveal_whitespace {
+                    utils::reveal_whitespace(line, max_len)
+                } else {
+                    utils::safe_string_display_within(line, max_len)
+                };
                 
                 // Highlight if selected or matches filter
-                let style = if app_state.selected_index == Some(*original_idx) {
-                    Style::default().fg(Color::Yellow).bg(Color::DarkGray)
-                } else if !app_state.filtered_indices.is_empty() {
+                let style = if app_state.is_highlighted(*original_idx) {
+                    app_state.theme.selected_style()
+                } else if app_state.is_stderr(*original_idx) {
+                    stderr_style()
+                } else if app_state.is_context(*original_idx) {
+                    context_style()
+                } else if app_state.has_filter() {
                     // Highlight filtered matches
                     Style::default().fg(Color::Cyan)
                 } else {
-                    Style::default().fg(Color::White)
+                    level_style(utils::detect_log_level(line))
                 };
                 
-                // Highlight filter text in the line if filtering
-                let content = if let Some(ref filter) = app_state.filter_text {
-                    highlight_filter_text(&safe_line, filter, style)
+                // Highlight filter text in the line if filtering. With ANSI colors
+                // kept, the line's own colors go over the level color, though not
+                // over the selection.
+                let line = if app_state.preserve_ansi && !app_state.reveal_whitespace && !app_state.is_highlighted(*original_idx) {
+                    ansi_line(line, max_len, &app_state.highlight_rules, &terms, style)
                 } else {
-                    Line::from(Span::styled(safe_line.clone(), style))
+                    highlight_line(&safe_line, &app_state.highlight_rules, &terms, style)
                 };
+                let prefix = line_prefix(app_state, *original_idx);
+                // A collapsed run (dedupe) says how many lines it stands for, up
+                // front where it stays visible however long the line is
+                let count = (*repeats > 1).then(|| format!("(×{}) ", repeats));
+                if !app_state.wrap_lines {
+                    let fixed: usize = prefix.iter().map(|span| span.content.width()).sum::<usize>()
+                        + count.as_ref().map_or(0, |count| count.width());
+                    note_overflow(app_state, &line, inner_width(area).saturating_sub(fixed));
+                }
+                let mut content = scroll_line(line, h_offset(app_state));
+                if let Some(count) = count {
+                    content.spans.insert(0, Span::styled(count, Style::default().fg(Color::DarkGray)));
+                }
                 
-                ListItem::new(content)
+                if app_state.wrap_lines {
+                    ListItem::new(Text::from(wrap_with_prefix(prefix, content, wrap_width)))
+                } else {
+                    content.spans.splice(0..0, prefix);
+                    ListItem::new(content)
+                }
             })
             .collect();
 
-        let title = if let Some(ref filter) = app_state.filter_text {
-            format!("Log Lines (filtered: '{}', {} matches)", filter, app_state.filtered_indices.len())
-        } else {
-            "Log Lines".to_string()
-        };
+        if app_state.reveal_whitespace {
+            title.push_str(" [whitespace]");
+        }
+        if app_state.wrap_lines {
+            title.push_str(" [wrap]");
+        }
+        if app_state.deduping() {
+            title.push_str(" [dedupe]");
+        }
+        if app_state.preserve_ansi {
+            title.push_str(" [ansi]");
+        }
+        push_scroll_marker(&mut title, app_state);
+        push_ai_note(&mut title, app_state);
 
         let list = List::new(items)
             .block(Block::default().borders(Borders::ALL).title(title))
@@ -116,27 +375,137 @@ impl PlainView {
     }
 }
 
-fn highlight_filter_text(line: &str, filter: &str, base_style: Style) -> Line<'static> {
-    let mut spans = Vec::new();
-    let mut remaining = line;
-    
-    while let Some(pos) = remaining.find(filter) {
-        // Add text before match
-        if pos > 0 {
-            spans.push(Span::styled(remaining[..pos].to_string(), base_style));
-        }
-        // Add highlighted match
-        spans.push(Span::styled(
-            remaining[pos..pos + filter.len()].to_string(),
-            Style::default().fg(Color::Yellow).bg(Color::DarkGray).add_modifier(ratatui::style::Modifier::BOLD),
+pub struct BookmarkView;
+
+impl BookmarkView {
+    pub fn render(
+        f: &mut Frame,
+        area: Rect,
+        app_state: &crate::app::AppState,
+    ) {
+        if app_state.bookmarks.is_empty() {
+            let paragraph = Paragraph::new("No bookmarks yet. Press 'm' on a line to bookmark it, then 'B' to come back here.")
+                .block(Block::default().borders(Borders::ALL).title("Bookmarks"))
+                .style(Style::default().fg(Color::DarkGray));
+            f.render_widget(paragraph, area);
+            return;
+        }
+        let mut title = format!("Bookmarks ({})", app_state.bookmarks.len());
+        if let Some(filter) = app_state.filter_label() {
+            title.push_str(&format!(" (filtered: '{}')", filter));
+        }
+        PlainView::render_titled(f, area, app_state, title);
+    }
+}
+
+/// Style for lines shown only as context around filter matches
+fn context_style() -> Style {
+    Style::default().fg(Color::DarkGray)
+}
+
+/// Style for lines that came from the command's stderr (`scry -- CMD`)
+fn stderr_style() -> Style {
+    Style::default().fg(Color::Red).add_modifier(Modifier::DIM)
+}
+
+/// `#<index>` prefix for filtered rows so they can be correlated with the full
+/// stream; None when the toggle is off or no filter is active
+fn index_prefix(app_state: &crate::app::AppState, original_idx: usize) -> Option<Span<'static>> {
+    if app_state.show_original_indices && app_state.has_filter() {
+        Some(Span::styled(
+            format!("#{} ", original_idx),
+            Style::default().fg(Color::DarkGray),
+        ))
+    } else {
+        None
+    }
+}
+
+/// What goes before a row in the Plain and KeyValue views: a `●` on
+/// bookmarked lines (once there are any), then the line-number gutter when
+/// it is on, otherwise the `#<index>` prefix. Numbers count from the first
+/// line received, evicted ones included, so they don't shift as the buffer
+/// fills; the gutter is as wide as the largest of them.
+fn line_prefix(app_state: &crate::app::AppState, original_idx: usize) -> Vec<Span<'static>> {
+    let mut prefix = Vec::new();
+    if !app_state.bookmarks.is_empty() {
+        prefix.push(if app_state.is_bookmarked(original_idx) {
+            Span::styled("● ", Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD))
+        } else {
+            Span::raw("  ")
+        });
+    }
+    if app_state.show_line_numbers {
+        let width = app_state.total_lines().to_string().len();
+        prefix.push(Span::styled(
+            format!("{:>width$} │ ", app_state.dropped_lines + original_idx + 1, width = width),
+            Style::default().fg(Color::DarkGray),
         ));
-        remaining = &remaining[pos + filter.len()..];
+    } else {
+        prefix.extend(index_prefix(app_state, original_idx));
+    }
+    prefix
+}
+
+/// Base style of an unselected, unfiltered line in the Plain view, by log level
+fn level_style(level: Option<utils::LogLevel>) -> Style {
+    match level {
+        Some(utils::LogLevel::Error) => Style::default().fg(Color::Red),
+        Some(utils::LogLevel::Warn) => Style::default().fg(Color::Yellow),
+        Some(utils::LogLevel::Debug) => Style::default().fg(Color::Cyan),
+        Some(utils::LogLevel::Trace) => Style::default().fg(Color::White).add_modifier(Modifier::DIM),
+        Some(utils::LogLevel::Info) | None => Style::default().fg(Color::White),
     }
-    // Add remaining text
-    if !remaining.is_empty() {
-        spans.push(Span::styled(remaining.to_string(), base_style));
+}
+
+/// Colors for pinned filter terms, cycled in the order they were pinned
+const PINNED_TERM_COLORS: [Color; 5] = [Color::Green, Color::Red, Color::Magenta, Color::Blue, Color::Cyan];
+
+/// The active filter terms, each with the style its matches are drawn in
+struct FilterHighlights<'a> {
+    terms: Vec<(&'a FilterMode, Style)>,
+    /// Matches ignore case (see AppState::ignore_case)
+    ignore_case: bool,
+}
+
+/// Every active filter term with its style: pinned terms cycle through
+/// PINNED_TERM_COLORS, the current term uses the theme
+fn filter_highlights(app_state: &crate::app::AppState) -> FilterHighlights<'_> {
+    let mut terms: Vec<(&FilterMode, Style)> = app_state
+        .pinned_terms
+        .iter()
+        .enumerate()
+        .map(|(i, term)| {
+            let style = Style::default()
+                .fg(PINNED_TERM_COLORS[i % PINNED_TERM_COLORS.len()])
+                .bg(app_state.theme.match_bg)
+                .add_modifier(Modifier::BOLD);
+            (term, style)
+        })
+        .collect();
+    if let Some(ref filter) = app_state.filter {
+        terms.push((filter, app_state.theme.match_style()));
+    }
+    // Path expressions select lines but have no text to highlight
+    terms.retain(|(term, _)| !term.is_empty() && !matches!(term, FilterMode::Path(_)));
+    FilterHighlights {
+        terms,
+        ignore_case: app_state.ignore_case,
     }
-    
+}
+
+/// Style of the first term found in `text`, if any
+fn matching_style(highlights: &FilterHighlights, text: &str) -> Option<Style> {
+    highlights
+        .terms
+        .iter()
+        .find(|(term, _)| term.find(text, highlights.ignore_case).is_some())
+        .map(|(_, style)| *style)
+}
+
+/// Split a line into spans, drawing each match of a term in that term's style
+fn highlight_filter_text(line: &str, highlights: &FilterHighlights, base_style: Style) -> Line<'static> {
+    let spans = match_spans(line, 0..line.len(), &filter_matches(line, highlights), base_style);
     if spans.is_empty() {
         Line::from(Span::styled(line.to_string(), base_style))
     } else {
@@ -144,6 +513,365 @@ fn highlight_filter_text(line: &str, filter: &str, base_style: Style) -> Line<'s
     }
 }
 
+/// Where each term matches in `line`, earliest first and never overlapping:
+/// of overlapping matches the earliest (then the longest) wins. Terms search
+/// the whole line, so anchors and word boundaries see its real neighbours.
+fn filter_matches(line: &str, highlights: &FilterHighlights) -> Vec<(usize, usize, Style)> {
+    let mut found: Vec<(usize, usize, Style)> = highlights
+        .terms
+        .iter()
+        .flat_map(|(term, style)| {
+            term.find_iter(line, highlights.ignore_case).into_iter().map(|(start, end)| (start, end, *style))
+        })
+        .collect();
+    found.sort_by_key(|&(start, end, _)| (start, std::cmp::Reverse(end)));
+    let mut taken_to = 0;
+    found.retain(|&(start, end, _)| {
+        let free = start >= taken_to;
+        if free {
+            taken_to = end;
+        }
+        free
+    });
+    found
+}
+
+/// Spans for `line[range]`: the parts under `matches` (see filter_matches)
+/// in their term's style, cut at the range's ends, and the rest in
+/// `base_style`. Match ranges are char boundaries, so no slice splits one.
+fn match_spans(line: &str, range: std::ops::Range<usize>, matches: &[(usize, usize, Style)], base_style: Style) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut pos = range.start;
+    for &(start, end, style) in matches {
+        let (start, end) = (start.max(range.start), end.min(range.end));
+        if start >= end {
+            continue;
+        }
+        if start > pos {
+            spans.push(Span::styled(line[pos..start].to_string(), base_style));
+        }
+        spans.push(Span::styled(line[start..end].to_string(), style));
+        pos = end;
+    }
+    if pos < range.end {
+        spans.push(Span::styled(line[pos..range.end].to_string(), base_style));
+    }
+    spans
+}
+
+/// Color a line with the highlight rules, then mark filter terms on top.
+/// Where rules overlap, the earlier rule wins.
+fn highlight_line(line: &str, rules: &[HighlightRule], terms: &FilterHighlights, base_style: Style) -> Line<'static> {
+    if rules.is_empty() {
+        return highlight_filter_text(line, terms, base_style);
+    }
+    // Byte ranges to color: each capture group, or the whole match if there are none
+    let mut ranges: Vec<(usize, usize, Style)> = Vec::new();
+    for rule in rules {
+        for captures in rule.regex.captures_iter(line) {
+            let spans: Vec<_> = if captures.len() > 1 {
+                captures.iter().skip(1).flatten().collect()
+            } else {
+                captures.get(0).into_iter().collect()
+            };
+            for m in spans.into_iter().filter(|m| !m.is_empty()) {
+                if !ranges.iter().any(|&(start, end, _)| m.start() < end && start < m.end()) {
+                    ranges.push((m.start(), m.end(), base_style.patch(rule.style)));
+                }
+            }
+        }
+    }
+    ranges.sort_by_key(|&(start, _, _)| start);
+
+    let matches = filter_matches(line, terms);
+    let mut spans = Vec::new();
+    let mut pos = 0;
+    for (start, end, style) in ranges {
+        if start > pos {
+            spans.extend(match_spans(line, pos..start, &matches, base_style));
+        }
+        spans.extend(match_spans(line, start..end, &matches, style));
+        pos = end;
+    }
+    if pos < line.len() || spans.is_empty() {
+        spans.extend(match_spans(line, pos..line.len(), &matches, base_style));
+    }
+    Line::from(spans)
+}
+
+/// A line holding ANSI color sequences, styled by them on top of
+/// `base_style`, with the text between them sanitized and highlighted as usual
+fn ansi_line(line: &str, max_len: usize, rules: &[HighlightRule], terms: &FilterHighlights, base_style: Style) -> Line<'static> {
+    let mut spans = Vec::new();
+    let mut style = base_style;
+    let mut budget = max_len;
+    for piece in utils::split_ansi(line) {
+        match piece {
+            utils::AnsiPiece::Sgr(params) => style = apply_sgr(style, base_style, &params),
+            utils::AnsiPiece::Text(text) if budget > 0 => {
+                let text = utils::sanitize_for_display(text, budget);
+                budget = budget.saturating_sub(text.len());
+                spans.extend(highlight_line(&text, rules, terms, style).spans);
+            }
+            utils::AnsiPiece::Text(_) => break,
+        }
+    }
+    if spans.iter().all(|span| span.content.trim().is_empty()) {
+        return Line::from(Span::styled("[empty line]", base_style));
+    }
+    Line::from(spans)
+}
+
+/// Apply one SGR sequence's parameters to `style`; resets go back to `base`
+fn apply_sgr(mut style: Style, base: Style, params: &[u16]) -> Style {
+    if params.is_empty() {
+        return base;
+    }
+    let mut i = 0;
+    while i < params.len() {
+        match params[i] {
+            0 => style = base,
+            1 => style = style.add_modifier(Modifier::BOLD),
+            2 => style = style.add_modifier(Modifier::DIM),
+            3 => style = style.add_modifier(Modifier::ITALIC),
+            4 => style = style.add_modifier(Modifier::UNDERLINED),
+            7 => style = style.add_modifier(Modifier::REVERSED),
+            9 => style = style.add_modifier(Modifier::CROSSED_OUT),
+            22 => style = style.remove_modifier(Modifier::BOLD | Modifier::DIM),
+            23 => style = style.remove_modifier(Modifier::ITALIC),
+            24 => style = style.remove_modifier(Modifier::UNDERLINED),
+            27 => style = style.remove_modifier(Modifier::REVERSED),
+            29 => style = style.remove_modifier(Modifier::CROSSED_OUT),
+            n @ 30..=37 => style.fg = Some(Color::Indexed((n - 30) as u8)),
+            n @ 90..=97 => style.fg = Some(Color::Indexed((n - 90 + 8) as u8)),
+            39 => style.fg = base.fg,
+            n @ 40..=47 => style.bg = Some(Color::Indexed((n - 40) as u8)),
+            n @ 100..=107 => style.bg = Some(Color::Indexed((n - 100 + 8) as u8)),
+            49 => style.bg = base.bg,
+            // Extended colors: 38;5;N / 38;2;R;G;B (48 for the background)
+            n @ (38 | 48) => {
+                let color = match params.get(i + 1) {
+                    Some(5) => params.get(i + 2).map(|&c| {
+                        i += 2;
+                        Color::Indexed(c as u8)
+                    }),
+                    Some(2) if params.len() > i + 4 => {
+                        let rgb = Color::Rgb(params[i + 2] as u8, params[i + 3] as u8, params[i + 4] as u8);
+                        i += 4;
+                        Some(rgb)
+                    }
+                    _ => None,
+                };
+                if n == 38 {
+                    style.fg = color.or(style.fg);
+                } else {
+                    style.bg = color.or(style.bg);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    style
+}
+
+/// Horizontal scroll for the current view; wrapped lines never need it
+fn h_offset(app_state: &crate::app::AppState) -> usize {
+    if app_state.wrap_lines {
+        0
+    } else {
+        app_state.horizontal_offset
+    }
+}
+
+/// Raise h_scroll_max to what a row scrolling sideways in `width` columns
+/// needs to bring its end into view
+fn note_overflow(app_state: &crate::app::AppState, content: &Line, width: usize) {
+    let len: usize = content.spans.iter().map(|span| span.content.chars().count()).sum();
+    let needed = len.saturating_sub(width);
+    if needed > app_state.h_scroll_max.get() {
+        app_state.h_scroll_max.set(needed);
+    }
+}
+
+/// Note a horizontal scroll in a view title
+fn push_scroll_marker(title: &mut String, app_state: &crate::app::AppState) {
+    let offset = h_offset(app_state);
+    if offset > 0 {
+        title.push_str(&format!(" [→{}]", offset));
+    }
+}
+
+/// `, ±N lines` for a filtered title while context lines are shown
+fn context_note(app_state: &crate::app::AppState) -> String {
+    if app_state.context_lines > 0 {
+        format!(", ±{} lines", app_state.context_lines)
+    } else {
+        String::new()
+    }
+}
+
+/// Say in a view title why the AI picked this view, if it did
+fn push_ai_note(title: &mut String, app_state: &crate::app::AppState) {
+    if let Some((ref view, ref note)) = app_state.ai_pick {
+        if *view == app_state.active_view {
+            title.push_str(" — ");
+            title.push_str(note);
+        }
+    }
+}
+
+/// Drop the first `offset` characters of a styled line, for horizontal scrolling
+fn scroll_line(line: Line<'static>, offset: usize) -> Line<'static> {
+    if offset == 0 {
+        return line;
+    }
+    let mut skip = offset;
+    let spans = line
+        .spans
+        .into_iter()
+        .filter_map(|span| {
+            let len = span.content.chars().count();
+            if skip >= len {
+                skip -= len;
+                return None;
+            }
+            let rest: String = span.content.chars().skip(skip).collect();
+            skip = 0;
+            Some(Span::styled(rest, span.style))
+        })
+        .collect::<Vec<_>>();
+    Line::from(spans)
+}
+
+/// Width available inside a bordered block
+fn inner_width(area: Rect) -> usize {
+    (area.width.saturating_sub(2) as usize).max(1)
+}
+
+/// Wrap a styled line into rows at most `width` columns wide, breaking after
+/// the last space that fits (or mid-word when a word is wider than a row).
+/// Widths are display columns, so wide characters such as CJK count double.
+fn wrap_line(line: Line<'static>, width: usize) -> Vec<Line<'static>> {
+    // A zero width would never consume any text
+    let width = width.max(1);
+    let chars: Vec<(char, Style)> = line
+        .spans
+        .iter()
+        .flat_map(|span| span.content.chars().map(move |c| (c, span.style)))
+        .collect();
+    let mut rows = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let mut used = 0;
+        let mut end = start;
+        let mut last_space = None;
+        while end < chars.len() {
+            let w = chars[end].0.width().unwrap_or(0);
+            // Always take at least one character, so nothing stalls
+            if used + w > width && end > start {
+                break;
+            }
+            if chars[end].0 == ' ' {
+                last_space = Some(end);
+            }
+            used += w;
+            end += 1;
+        }
+        if end < chars.len() {
+            if let Some(space) = last_space.filter(|&space| space > start) {
+                end = space + 1;
+            }
+        }
+        rows.push(styled_row(&chars[start..end]));
+        start = end;
+    }
+    if rows.is_empty() {
+        rows.push(Line::default());
+    }
+    rows
+}
+
+/// Spans for a run of styled characters, one per stretch of the same style
+fn styled_row(chars: &[(char, Style)]) -> Line<'static> {
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    for &(c, style) in chars {
+        match spans.last_mut() {
+            Some(span) if span.style == style => span.content.to_mut().push(c),
+            _ => spans.push(Span::styled(c.to_string(), style)),
+        }
+    }
+    Line::from(spans)
+}
+
+/// Wrap a row's content beside its gutter (line number, bookmark): the
+/// gutter goes on the first row only, and the rows below start under the
+/// content, leaving the gutter column blank
+fn wrap_with_prefix(prefix: Vec<Span<'static>>, content: Line<'static>, width: usize) -> Vec<Line<'static>> {
+    let prefix_width: usize = prefix.iter().map(|span| span.content.width()).sum();
+    if prefix_width * 2 > width {
+        // Too narrow to spare the room; wrap the gutter along with the text
+        let mut line = content;
+        line.spans.splice(0..0, prefix);
+        return wrap_line(line, width);
+    }
+    let mut rows = wrap_line(content, width - prefix_width);
+    for (i, row) in rows.iter_mut().enumerate() {
+        if i == 0 {
+            row.spans.splice(0..0, prefix.clone());
+        } else {
+            row.spans.insert(0, Span::raw(" ".repeat(prefix_width)));
+        }
+    }
+    rows
+}
+
+/// Pack `k: v` chunks into rows of at most `width` characters, breaking only
+/// between chunks. A chunk too wide for a row of its own is character-wrapped.
+fn wrap_pairs(chunks: Vec<Vec<Span<'static>>>, width: usize) -> Vec<Line<'static>> {
+    let mut rows = Vec::new();
+    let mut current: Vec<Span<'static>> = Vec::new();
+    let mut used = 0;
+    for chunk in chunks {
+        let chunk_width: usize = chunk.iter().map(|span| span.content.chars().count()).sum();
+        if used > 0 && used + chunk_width > width {
+            rows.push(Line::from(std::mem::take(&mut current)));
+            used = 0;
+        }
+        if chunk_width > width {
+            rows.extend(wrap_line(Line::from(chunk), width));
+        } else {
+            current.extend(chunk);
+            used += chunk_width;
+        }
+    }
+    if !current.is_empty() || rows.is_empty() {
+        rows.push(Line::from(current));
+    }
+    rows
+}
+
+/// Bottom-border summary of a structured view: entries shown, distinct keys,
+/// and lines the view couldn't parse, as a hint of how well it fits the data
+fn summary_footer(entries: usize, keys: usize, unparsed: usize) -> Line<'static> {
+    let muted = Style::default().fg(Color::DarkGray);
+    let unparsed_style = if unparsed > 0 {
+        Style::default().fg(Color::Yellow)
+    } else {
+        muted
+    };
+    Line::from(vec![
+        Span::styled(format!(" {} entries · {} keys · ", entries, keys), muted),
+        Span::styled(format!("{} unparsed ", unparsed), unparsed_style),
+    ])
+}
+
+/// A single-cell table row tall enough for all of its wrapped lines
+fn wrapped_row(lines: Vec<Line<'static>>) -> Row<'static> {
+    let height = lines.len() as u16;
+    Row::new(vec![Cell::from(Text::from(lines))]).height(height)
+}
+
 pub struct KeyValueView;
 
 impl KeyValueView {
@@ -153,39 +881,46 @@ impl KeyValueView {
         app_state: &crate::app::AppState,
     ) {
         let display_logs = app_state.get_display_logs();
+        let terms = filter_highlights(app_state);
+        let wrap_width = inner_width(area);
         let mut rows = Vec::new();
+        let mut keys_seen: HashSet<String> = HashSet::new();
+        let mut unparsed = 0;
 
-        for (_display_idx, (original_idx, line)) in display_logs.iter().enumerate() {
+        for (original_idx, line) in display_logs.iter() {
             // Skip items before scroll_offset (for virtual scrolling if needed)
             // For now, show all items and let ratatui handle scrolling
             // But we'll highlight the selected/filtered items
             
             // Safely extract key-value pairs - handles edge cases
             let pairs = utils::extract_key_value_pairs(line);
+            if pairs.is_empty() {
+                unparsed += 1;
+            }
+            keys_seen.extend(pairs.iter().map(|(k, _)| k.clone()));
 
             if !pairs.is_empty() {
                 // Highlight if selected or matches filter
-                let base_style = if app_state.selected_index == Some(*original_idx) {
-                    Style::default().fg(Color::Yellow).bg(Color::DarkGray)
-                } else if !app_state.filtered_indices.is_empty() {
+                let base_style = if app_state.is_highlighted(*original_idx) {
+                    app_state.theme.selected_style()
+                } else if app_state.is_stderr(*original_idx) {
+                    stderr_style()
+                } else if app_state.is_context(*original_idx) {
+                    context_style()
+                } else if app_state.has_filter() {
                     Style::default().fg(Color::Cyan)
                 } else {
                     Style::default().fg(Color::White)
                 };
                 
-                let cells: Vec<Span> = pairs
+                let prefix = line_prefix(app_state, *original_idx);
+                let mut chunks: Vec<Vec<Span>> = pairs
                     .iter()
-                    .flat_map(|(k, v)| {
-                        // Highlight filter text in values if filtering
-                        let (k_style, v_style) = if let Some(ref filter) = app_state.filter_text {
-                            if k.contains(filter) || v.contains(filter) {
-                                (Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD), base_style)
-                            } else {
-                                (base_style, base_style)
-                            }
-                        } else {
-                            (base_style, base_style)
-                        };
+                    .map(|(k, v)| {
+                        // Mark the key of any pair mentioning a filter term in that term's color
+                        let k_style = matching_style(&terms, k)
+                            .or_else(|| matching_style(&terms, v))
+                            .unwrap_or(base_style);
                         
                         vec![
                             Span::styled(
@@ -193,56 +928,83 @@ impl KeyValueView {
                                 k_style,
                             ),
                             Span::styled(
-                                format!("{} ", v),
-                                v_style,
+                                format!("{} ", format_kv_value(v, app_state.json_max_depth)),
+                                base_style,
                             ),
                         ]
                     })
                     .collect();
-                rows.push(Row::new(cells));
+                if app_state.wrap_lines {
+                    // Break between pairs so a key is never split from its value
+                    if !prefix.is_empty() {
+                        chunks.insert(0, prefix);
+                    }
+                    rows.push(wrapped_row(wrap_pairs(chunks, wrap_width)));
+                } else {
+                    // All pairs share the single table column, so keep them on one line.
+                    // The prefix stays put while the pairs scroll sideways.
+                    let pairs_line = Line::from(chunks.concat());
+                    let prefix_width: usize = prefix.iter().map(|span| span.content.width()).sum();
+                    note_overflow(app_state, &pairs_line, wrap_width.saturating_sub(prefix_width));
+                    let mut content = scroll_line(pairs_line, app_state.horizontal_offset);
+                    content.spans.splice(0..0, prefix);
+                    rows.push(Row::new(vec![content]));
+                }
             } else {
                 // Fallback: show the sanitized raw line
                 let safe_line = utils::safe_string_display(line);
                 
                 // Highlight if selected or matches filter
-                let style = if app_state.selected_index == Some(*original_idx) {
-                    Style::default().fg(Color::Yellow).bg(Color::DarkGray)
-                } else if !app_state.filtered_indices.is_empty() {
+                let style = if app_state.is_highlighted(*original_idx) {
+                    app_state.theme.selected_style()
+                } else if app_state.is_stderr(*original_idx) {
+                    stderr_style()
+                } else if app_state.is_context(*original_idx) {
+                    context_style()
+                } else if app_state.has_filter() {
                     Style::default().fg(Color::Cyan)
                 } else {
                     Style::default().fg(Color::White)
                 };
                 
                 // Highlight filter text if filtering
-                let content_spans: Vec<Span> = if let Some(ref filter) = app_state.filter_text {
-                    // Extract spans from the Line
-                    highlight_filter_text(&safe_line, filter, style)
-                        .spans
-                        .iter()
-                        .cloned()
-                        .collect()
-                } else {
-                    vec![Span::styled(safe_line.clone(), style)]
-                };
+                let highlighted = highlight_line(&safe_line, &app_state.highlight_rules, &terms, style);
+                let prefix = line_prefix(app_state, *original_idx);
+                if !app_state.wrap_lines {
+                    let prefix_width: usize = prefix.iter().map(|span| span.content.width()).sum();
+                    note_overflow(app_state, &highlighted, wrap_width.saturating_sub(prefix_width));
+                }
+                let mut content = scroll_line(highlighted, h_offset(app_state));
                 
-                rows.push(Row::new(content_spans));
+                if app_state.wrap_lines {
+                    rows.push(wrapped_row(wrap_with_prefix(prefix, content, wrap_width)));
+                } else {
+                    content.spans.splice(0..0, prefix);
+                    rows.push(Row::new(vec![content]));
+                }
             }
         }
 
-        let title = if let Some(ref filter) = app_state.filter_text {
-            format!("Key-Value Pairs (filtered: '{}', {} matches)", filter, app_state.filtered_indices.len())
+        let mut title = if let Some(filter) = app_state.filter_label() {
+            format!("Key-Value Pairs (filtered: '{}', {} matches{})", filter, app_state.filtered_indices.len(), context_note(app_state))
         } else {
             "Key-Value Pairs".to_string()
         };
+        if app_state.wrap_lines {
+            title.push_str(" [wrap]");
+        }
+        push_scroll_marker(&mut title, app_state);
+        push_ai_note(&mut title, app_state);
+        let footer = summary_footer(display_logs.len() - unparsed, keys_seen.len(), unparsed);
 
         if rows.is_empty() {
             let msg = Paragraph::new("No key-value pairs found")
-                .block(Block::default().borders(Borders::ALL).title(title))
+                .block(Block::default().borders(Borders::ALL).title(title).title_bottom(footer))
                 .style(Style::default().fg(Color::Yellow));
             f.render_widget(msg, area);
         } else {
             let table = Table::new(rows, &[Constraint::Percentage(100)])
-                .block(Block::default().borders(Borders::ALL).title(title));
+                .block(Block::default().borders(Borders::ALL).title(title).title_bottom(footer));
 
             f.render_widget(table, area);
         }
@@ -257,159 +1019,143 @@ impl JsonView {
         area: Rect,
         app_state: &crate::app::AppState,
     ) {
+        if app_state.json_tree {
+            return Self::render_tree(f, area, app_state);
+        }
         let display_logs = app_state.get_display_logs();
+        let terms = filter_highlights(app_state);
+        // Width of the value column (70% of the table, less the column gap)
+        let value_width = (inner_width(area).saturating_sub(1) * 70 / 100).max(1);
         let mut rows = Vec::new();
+        let mut keys_seen: HashSet<String> = HashSet::new();
+        let mut unparsed = 0;
+
+        for (original_idx, line) in display_logs.iter() {
+            // The selected line shows its values in full, wrapped; others stay compact
+            let expanded = app_state.selected_index == Some(*original_idx);
 
-        for (_display_idx, (original_idx, line)) in display_logs.iter().enumerate() {
             // Determine base style for this log entry
-            let is_selected = app_state.selected_index == Some(*original_idx);
+            let is_selected = app_state.is_highlighted(*original_idx);
             let base_key_style = if is_selected {
-                Style::default().fg(Color::Yellow).bg(Color::DarkGray)
-            } else if !app_state.filtered_indices.is_empty() {
+                app_state.theme.selected_style()
+            } else if app_state.is_stderr(*original_idx) {
+                stderr_style()
+            } else if app_state.is_context(*original_idx) {
+                context_style()
+            } else if app_state.has_filter() {
                 Style::default().fg(Color::Cyan)
             } else {
                 Style::default().fg(Color::Green)
             };
             
             let base_value_style = if is_selected {
-                Style::default().fg(Color::Yellow).bg(Color::DarkGray)
-            } else if !app_state.filtered_indices.is_empty() {
+                app_state.theme.selected_style()
+            } else if app_state.is_stderr(*original_idx) {
+                stderr_style()
+            } else if app_state.is_context(*original_idx) {
+                context_style()
+            } else if app_state.has_filter() {
                 Style::default().fg(Color::Cyan)
             } else {
                 Style::default().fg(Color::White)
             };
             
-            // Try to parse JSON - handle any errors gracefully
-            match serde_json::from_str::<Value>(line) {
-                Ok(json) => {
-                    if let Value::Object(map) = json {
+            // Try to parse JSON (cached across frames) - handle any errors gracefully
+            let parsed = app_state.json_cache.borrow_mut().get_or_parse(app_state.dropped_lines + original_idx, line);
+            match parsed {
+                Some(json) => {
+                    if let Value::Object(map) = json.as_ref() {
+                        // Flattened, nested objects become one row per leaf value
+                        let pairs: Vec<(String, &Value)> = if app_state.json_flatten {
+                            let mut leaves = Vec::new();
+                            flatten_json("", json.as_ref(), &mut leaves);
+                            leaves
+                        } else {
+                            map.iter().map(|(key, value)| (key.clone(), value)).collect()
+                        };
+                        keys_seen.extend(pairs.iter().map(|(key, _)| key.clone()));
                         // Create one row per key-value pair for better readability
                         // Show ALL keys - don't limit, be resilient and show all data
-                        for (key, value) in map.iter() {
+                        for (pair_idx, (key, value)) in pairs.into_iter().enumerate() {
                             // Sanitize key for safe display (but allow longer keys)
-                            let safe_key = utils::sanitize_for_display(key, 100);
-                            
-                            // Check if key or value matches filter for highlighting
-                            let key_matches_filter = if let Some(ref filter) = app_state.filter_text {
-                                key.contains(filter)
-                            } else {
-                                false
-                            };
+                            let safe_key = utils::sanitize_for_display(&key, 100);
                             
                             // Format value properly - show full values, truncate only if extremely long
-                            let (value_str, value_matches_filter) = match value {
-                                Value::String(s) => {
-                                    let sanitized = utils::sanitize_for_display(s, 500);
-                                    let matches = if let Some(ref filter) = app_state.filter_text {
-                                        s.contains(filter)
-                                    } else {
-                                        false
-                                    };
-                                    (sanitized, matches)
-                                }
-                                Value::Number(n) => (n.to_string(), false),
-                                Value::Bool(b) => (b.to_string(), false),
-                                Value::Null => ("null".to_string(), false),
-                                Value::Array(arr) => {
-                                    if arr.is_empty() {
-                                        ("[]".to_string(), false)
-                                    } else {
-                                        // Show array contents if small, otherwise count
-                                        if arr.len() <= 5 {
-                                            let items: Vec<String> = arr.iter()
-                                                .map(|v| match v {
-                                                    Value::String(s) => format!("\"{}\"", utils::sanitize_for_display(s, 50)),
-                                                    Value::Number(n) => n.to_string(),
-                                                    Value::Bool(b) => b.to_string(),
-                                                    Value::Null => "null".to_string(),
-                                                    _ => format!("{:?}", v),
-                                                })
-                                                .collect();
-                                            (format!("[{}]", items.join(", ")), false)
-                                        } else {
-                                            (format!("[{} items]", arr.len()), false)
-                                        }
-                                    }
-                                }
-                                Value::Object(obj) => {
-                                    if obj.is_empty() {
-                                        ("{}".to_string(), false)
-                                    } else {
-                                        // For nested objects, show key count but also try to show some content
-                                        if obj.len() <= 3 {
-                                            let pairs: Vec<String> = obj.iter()
-                                                .take(3)
-                                                .map(|(k, v)| {
-                                                    let v_str = match v {
-                                                        Value::String(s) => format!("\"{}\"", utils::sanitize_for_display(s, 30)),
-                                                        Value::Number(n) => n.to_string(),
-                                                        Value::Bool(b) => b.to_string(),
-                                                        _ => format!("{:?}", v),
-                                                    };
-                                                    format!("{}: {}", k, v_str)
-                                                })
-                                                .collect();
-                                            (format!("{{{}}}", pairs.join(", ")), false)
-                                        } else {
-                                            (format!("{{{} keys}}", obj.len()), false)
-                                        }
-                                    }
-                                }
-                            };
-                            
-                            // Apply filter highlighting
-                            let key_style = if key_matches_filter {
-                                Style::default().fg(Color::Yellow).bg(Color::DarkGray).add_modifier(Modifier::BOLD)
+                            let value_str = if expanded {
+                                full_json_value(value)
                             } else {
-                                base_key_style
+                                format_json_value(value, app_state.json_max_depth)
                             };
-                            
-                            let value_style = if value_matches_filter {
-                                Style::default().fg(Color::Yellow).bg(Color::DarkGray).add_modifier(Modifier::BOLD)
-                            } else {
-                                base_value_style
+
+                            // Check if key or value matches a filter term for highlighting
+                            let key_style = matching_style(&terms, &key).unwrap_or(base_key_style);
+                            let value_match = match value {
+                                Value::String(s) => matching_style(&terms, s),
+                                _ => None,
                             };
+                            let value_style = value_match.unwrap_or(base_value_style);
                             
                             // Create a row with key and value as separate cells
-                            let key_cell = Line::from(vec![Span::styled(safe_key, key_style)]);
-                            
-                            // For value, highlight filter text if present
-                            let value_cell = if let Some(ref filter) = app_state.filter_text {
-                                if value_matches_filter {
-                                    // Value contains filter - highlight the filter text within it
-                                    highlight_filter_text(&value_str, filter, value_style)
-                                } else {
-                                    Line::from(vec![Span::styled(value_str, value_style)])
+                            let mut key_cell = Line::from(vec![Span::styled(safe_key, key_style)]);
+                            if pair_idx == 0 {
+                                if let Some(prefix) = index_prefix(app_state, *original_idx) {
+                                    key_cell.spans.insert(0, prefix);
                                 }
+                            }
+                            
+                            // For value, highlight the filter terms within it
+                            let value_cell = if value_match.is_some() {
+                                highlight_filter_text(&value_str, &terms, value_style)
                             } else {
                                 Line::from(vec![Span::styled(value_str, value_style)])
                             };
                             
-                            rows.push(Row::new(vec![key_cell, value_cell]));
+                            if expanded {
+                                let lines = wrap_line(value_cell, value_width);
+                                let height = lines.len() as u16;
+                                rows.push(Row::new(vec![Cell::from(key_cell), Cell::from(Text::from(lines))]).height(height));
+                            } else {
+                                note_overflow(app_state, &value_cell, value_width);
+                                let value_cell = scroll_line(value_cell, app_state.horizontal_offset);
+                                rows.push(Row::new(vec![key_cell, value_cell]));
+                            }
                         }
                     } else {
                         // Non-object JSON - display safely
-                        let json_str = utils::safe_json_display(&json);
-                        let json_cell = Line::from(vec![Span::styled(json_str, base_value_style)]);
+                        let json_str = utils::safe_json_display(json.as_ref());
+                        let mut json_cell = Line::from(vec![Span::styled(json_str, base_value_style)]);
+                        if let Some(prefix) = index_prefix(app_state, *original_idx) {
+                            json_cell.spans.insert(0, prefix);
+                        }
                         rows.push(Row::new(vec![json_cell, Line::from("")]));
                     }
                 }
-                Err(_) => {
-                    // Not valid JSON - silently skip (this is expected for mixed log formats)
+                None => {
+                    // Not valid JSON - skip it, but count it in the footer
+                    // (this is expected for mixed log formats)
+                    unparsed += 1;
                     continue;
                 }
             }
         }
 
-        let title = if let Some(ref filter) = app_state.filter_text {
-            format!("JSON Logs (filtered: '{}', {} matches)", filter, app_state.filtered_indices.len())
+        let mut title = if let Some(filter) = app_state.filter_label() {
+            format!("JSON Logs (filtered: '{}', {} matches{})", filter, app_state.filtered_indices.len(), context_note(app_state))
         } else {
             "JSON Logs".to_string()
         };
+        if app_state.json_flatten {
+            title.push_str(" [flat]");
+        } else {
+            title.push_str(&format!(" [depth {}]", app_state.json_max_depth));
+        }
+        push_scroll_marker(&mut title, app_state);
+        push_ai_note(&mut title, app_state);
+        let footer = summary_footer(display_logs.len() - unparsed, keys_seen.len(), unparsed);
 
         if rows.is_empty() {
             let msg = Paragraph::new("No valid JSON logs found")
-                .block(Block::default().borders(Borders::ALL).title(title))
+                .block(Block::default().borders(Borders::ALL).title(title).title_bottom(footer))
                 .style(Style::default().fg(Color::Yellow));
             f.render_widget(msg, area);
         } else {
@@ -419,13 +1165,615 @@ impl JsonView {
                 Constraint::Percentage(30),  // Key column
                 Constraint::Percentage(70),  // Value column
             ])
-                .block(Block::default().borders(Borders::ALL).title(title));
+                .block(Block::default().borders(Borders::ALL).title(title).title_bottom(footer));
 
             f.render_widget(table, area);
         }
     }
 }
 
+impl JsonView {
+    /// Tree mode (`t`): every line as an indented tree of its keys, with
+    /// objects and arrays folded and unfolded by Space. The cursor is
+    /// `scroll_offset`, counted in visible rows (see json_tree_rows).
+    fn render_tree(f: &mut Frame, area: Rect, app_state: &crate::app::AppState) {
+        let terms = filter_highlights(app_state);
+        // Width of the value column (60% of the table, less the column gap)
+        let value_width = (inner_width(area).saturating_sub(1) * 60 / 100).max(1);
+        let summary_style = Style::default().fg(Color::DarkGray);
+        let mut rows = Vec::new();
+        let mut keys_seen: HashSet<String> = HashSet::new();
+        let unparsed = walk_json_tree(app_state, &mut |row, value| {
+            let (key_style, value_style) = if app_state.is_stderr(row.index) {
+                (stderr_style(), stderr_style())
+            } else if app_state.is_context(row.index) {
+                (context_style(), context_style())
+            } else if app_state.has_filter() {
+                (Style::default().fg(Color::Cyan), Style::default().fg(Color::Cyan))
+            } else {
+                (Style::default().fg(Color::Green), Style::default().fg(Color::White))
+            };
+            let marker = match row.open {
+                Some(true) => "▾ ",
+                Some(false) => "▸ ",
+                None => "  ",
+            };
+            let mut key_cell = Line::from(Span::raw(format!("{}{}", "  ".repeat(row.path.len()), marker)));
+            let summary = match value {
+                Value::Object(map) => format!("{{{} keys}}", map.len()),
+                Value::Array(items) => format!("[{} items]", items.len()),
+                _ => String::new(),
+            };
+            let value_cell = match row.path.last() {
+                // A line's root: the summary stands in for the key
+                None => {
+                    if let Some(prefix) = index_prefix(app_state, row.index) {
+                        key_cell.spans.push(prefix);
+                    }
+                    if row.open.is_some() {
+                        key_cell.spans.push(Span::styled(summary, summary_style));
+                    }
+                    match row.open {
+                        Some(true) => Line::from(""),
+                        _ => highlight_filter_text(&format_json_value(value, app_state.json_max_depth), &terms, value_style),
+                    }
+                }
+                Some(key) => {
+                    if row.path.len() == 1 {
+                        keys_seen.insert(key.clone());
+                    }
+                    let style = matching_style(&terms, key).unwrap_or(key_style);
+                    key_cell.spans.push(Span::styled(utils::sanitize_for_display(key, 100), style));
+                    match row.open {
+                        Some(true) => Line::from(Span::styled(summary, summary_style)),
+                        _ => highlight_filter_text(&format_json_value(value, app_state.json_max_depth), &terms, value_style),
+                    }
+                }
+            };
+            note_overflow(app_state, &value_cell, value_width);
+            rows.push(Row::new(vec![key_cell, scroll_line(value_cell, app_state.horizontal_offset)]));
+        });
+
+        let mut title = if let Some(filter) = app_state.filter_label() {
+            format!("JSON Logs (filtered: '{}', {} matches{})", filter, app_state.filtered_indices.len(), context_note(app_state))
+        } else {
+            "JSON Logs".to_string()
+        };
+        title.push_str(" [tree]");
+        push_scroll_marker(&mut title, app_state);
+        push_ai_note(&mut title, app_state);
+        let footer = summary_footer(app_state.get_display_count() - unparsed, keys_seen.len(), unparsed);
+
+        if rows.is_empty() {
+            let msg = Paragraph::new("No valid JSON logs found")
+                .block(Block::default().borders(Borders::ALL).title(title).title_bottom(footer))
+                .style(Style::default().fg(Color::Yellow));
+            f.render_widget(msg, area);
+            return;
+        }
+
+        let selected = app_state.scroll_offset.min(rows.len() - 1);
+        let table = Table::new(rows, &[Constraint::Percentage(40), Constraint::Percentage(60)])
+            .block(Block::default().borders(Borders::ALL).title(title).title_bottom(footer))
+            .highlight_style(app_state.theme.selected_style());
+        let mut table_state = TableState::default().with_selected(Some(selected));
+        f.render_stateful_widget(table, area, &mut table_state);
+    }
+}
+
+/// One visible row of the Json view's tree mode
+#[derive(Debug, Clone)]
+pub struct JsonTreeRow {
+    /// Buffer index of the line the row belongs to
+    pub index: usize,
+    /// Keys from the line's root (array items as `[i]`); empty for the root.
+    /// Paths are shared by every line, so folding one folds it everywhere.
+    pub path: Vec<String>,
+    /// Whether an object or array is unfolded; None for values without children
+    pub open: Option<bool>,
+}
+
+/// What a set of tree rows was built from: the lines shown (buffer indices
+/// shift as lines are dropped), the folded paths and the depth
+#[derive(PartialEq)]
+struct JsonTreeKey {
+    dropped_lines: usize,
+    shown: Vec<usize>,
+    collapsed: HashSet<Vec<String>>,
+    depth: usize,
+}
+
+/// json_tree_rows' last result, reused while its key still holds
+#[derive(Default)]
+pub struct JsonTreeCache {
+    key: Option<JsonTreeKey>,
+    rows: Rc<Vec<JsonTreeRow>>,
+}
+
+/// The tree mode's visible rows, in display order, which is what the cursor
+/// and Space move through. Built once per change to the lines shown, the
+/// folds or the depth, since every key press asks for them.
+pub fn json_tree_rows(app_state: &crate::app::AppState) -> Rc<Vec<JsonTreeRow>> {
+    let key = JsonTreeKey {
+        dropped_lines: app_state.dropped_lines,
+        shown: app_state.get_display_logs().iter().map(|&(idx, _)| idx).collect(),
+        collapsed: app_state.json_collapsed.clone(),
+        depth: app_state.json_max_depth,
+    };
+    let mut cache = app_state.json_tree_cache.borrow_mut();
+    if cache.key.as_ref() != Some(&key) {
+        let mut rows = Vec::new();
+        walk_json_tree(app_state, &mut |row, _| rows.push(row.clone()));
+        *cache = JsonTreeCache { key: Some(key), rows: Rc::new(rows) };
+    }
+    Rc::clone(&cache.rows)
+}
+
+/// Visit each visible tree row of the displayed JSON lines with the value it
+/// stands for. Returns how many lines weren't JSON.
+fn walk_json_tree(app_state: &crate::app::AppState, visit: &mut dyn FnMut(&JsonTreeRow, &Value)) -> usize {
+    let mut unparsed = 0;
+    for (original_idx, line) in app_state.get_display_logs() {
+        // Let go of the cache before visiting, which may want it too
+        let parsed = app_state.json_cache.borrow_mut().get_or_parse(app_state.dropped_lines + original_idx, line);
+        match parsed {
+            Some(json) => walk_json_node(original_idx, &mut Vec::new(), json.as_ref(), &app_state.json_collapsed, visit),
+            None => unparsed += 1,
+        }
+    }
+    unparsed
+}
+
+fn walk_json_node(
+    index: usize,
+    path: &mut Vec<String>,
+    value: &Value,
+    collapsed: &HashSet<Vec<String>>,
+    visit: &mut dyn FnMut(&JsonTreeRow, &Value),
+) {
+    let has_children = match value {
+        Value::Object(map) => !map.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        _ => false,
+    };
+    let open = has_children.then(|| !collapsed.contains(path));
+    visit(&JsonTreeRow { index, path: path.clone(), open }, value);
+    if open != Some(true) {
+        return;
+    }
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                path.push(key.clone());
+                walk_json_node(index, path, child, collapsed, visit);
+                path.pop();
+            }
+        }
+        Value::Array(items) => {
+            for (i, child) in items.iter().enumerate() {
+                path.push(format!("[{}]", i));
+                walk_json_node(index, path, child, collapsed, visit);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Collect the leaf values under `value` with dotted paths from `prefix`:
+/// `{"request":{"headers":{"host":"a"}}}` gives `request.headers.host`, array
+/// items are indexed as `tags[0]`. Empty objects and arrays count as leaves.
+fn flatten_json<'a>(prefix: &str, value: &'a Value, out: &mut Vec<(String, &'a Value)>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, child) in map {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten_json(&path, child, out);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (i, child) in items.iter().enumerate() {
+                flatten_json(&format!("{}[{}]", prefix, i), child, out);
+            }
+        }
+        _ => out.push((prefix.to_string(), value)),
+    }
+}
+
+pub struct CsvView;
+
+impl CsvView {
+    pub fn render(
+        f: &mut Frame,
+        area: Rect,
+        app_state: &crate::app::AppState,
+    ) {
+        let display_logs = app_state.get_display_logs();
+        let terms = filter_highlights(app_state);
+        let delimiter = app_state.csv_delimiter();
+        let header = app_state
+            .first_line
+            .as_deref()
+            .map(|line| utils::split_delimited(line, delimiter))
+            .unwrap_or_default();
+        // The header line isn't repeated as a data row while it's still buffered
+        let header_index = (app_state.dropped_lines == 0).then_some(0);
+        let mut widths: Vec<usize> = header.iter().map(|field| field.chars().count()).collect();
+        let mut rows = Vec::new();
+        let mut row_indices = Vec::new();
+        let mut unparsed = 0;
+
+        for (original_idx, line) in display_logs.iter() {
+            if Some(*original_idx) == header_index {
+                continue;
+            }
+            let fields = utils::split_delimited(line, delimiter);
+            if fields.is_empty() {
+                unparsed += 1;
+                continue;
+            }
+            let base_style = if app_state.is_highlighted(*original_idx) {
+                app_state.theme.selected_style()
+            } else if app_state.is_stderr(*original_idx) {
+                stderr_style()
+            } else if app_state.is_context(*original_idx) {
+                context_style()
+            } else if app_state.has_filter() {
+                Style::default().fg(Color::Cyan)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            let prefix = index_prefix(app_state, *original_idx);
+            let prefix_width = prefix.as_ref().map_or(0, |span| span.content.chars().count());
+            let mut cells = Vec::with_capacity(fields.len());
+            for (column, field) in fields.iter().enumerate() {
+                let mut cell = match matching_style(&terms, field) {
+                    Some(_) => highlight_filter_text(field, &terms, base_style),
+                    None => Line::from(Span::styled(field.clone(), base_style)),
+                };
+                let mut width = field.chars().count();
+                if column == 0 {
+                    if let Some(ref prefix) = prefix {
+                        cell.spans.insert(0, prefix.clone());
+                        width += prefix_width;
+                    }
+                }
+                match widths.get_mut(column) {
+                    Some(max) => *max = (*max).max(width),
+                    None => widths.push(width),
+                }
+                cells.push(Cell::from(cell));
+            }
+            rows.push(Row::new(cells));
+            row_indices.push(*original_idx);
+        }
+
+        let kind = if delimiter == b'\t' { "TSV" } else { "CSV" };
+        let mut title = if let Some(filter) = app_state.filter_label() {
+            format!("{} Rows (filtered: '{}', {} matches{})", kind, filter, app_state.filtered_indices.len(), context_note(app_state))
+        } else {
+            format!("{} Rows", kind)
+        };
+        push_scroll_marker(&mut title, app_state);
+        push_ai_note(&mut title, app_state);
+        let footer = summary_footer(rows.len(), widths.len(), unparsed);
+
+        if rows.is_empty() {
+            let msg = Paragraph::new("No CSV/TSV rows found")
+                .block(Block::default().borders(Borders::ALL).title(title).title_bottom(footer))
+                .style(Style::default().fg(Color::Yellow));
+            f.render_widget(msg, area);
+            return;
+        }
+
+        // Keep the current line in view; rows skipped above (the header and
+        // unparsable lines) mean it can't be looked up by position alone
+        let current = app_state.current_index();
+        let selected = row_indices
+            .iter()
+            .position(|&idx| Some(idx) == current)
+            .unwrap_or_else(|| app_state.scroll_offset.min(rows.len() - 1));
+        let constraints: Vec<Constraint> = widths
+            .iter()
+            .map(|&width| Constraint::Length(width.clamp(1, MAX_CSV_COLUMN_WIDTH) as u16))
+            .collect();
+        let header_row = Row::new(header).style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
+        let table = Table::new(rows, constraints)
+            .header(header_row)
+            .block(Block::default().borders(Borders::ALL).title(title).title_bottom(footer));
+        let mut table_state = TableState::default().with_selected(Some(selected));
+        f.render_stateful_widget(table, area, &mut table_state);
+    }
+}
+
+pub struct LogfmtView;
+
+impl LogfmtView {
+    pub fn render(
+        f: &mut Frame,
+        area: Rect,
+        app_state: &crate::app::AppState,
+    ) {
+        let display_logs = app_state.get_display_logs();
+        let terms = filter_highlights(app_state);
+        let records: Vec<(usize, utils::LogfmtRecord)> = display_logs
+            .iter()
+            .filter_map(|(idx, line)| Some((*idx, utils::parse_logfmt(line)?)))
+            .collect();
+        let unparsed = display_logs.len() - records.len();
+        let columns = logfmt_columns(records.iter().map(|(_, record)| record));
+        let mut widths: Vec<usize> = columns.iter().map(|key| key.chars().count()).collect();
+        let mut rows = Vec::new();
+
+        for (original_idx, record) in records.iter() {
+            let base_style = if app_state.is_highlighted(*original_idx) {
+                app_state.theme.selected_style()
+            } else if app_state.is_stderr(*original_idx) {
+                stderr_style()
+            } else if app_state.is_context(*original_idx) {
+                context_style()
+            } else if app_state.has_filter() {
+                Style::default().fg(Color::Cyan)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            let prefix = index_prefix(app_state, *original_idx);
+            let mut cells = Vec::with_capacity(columns.len());
+            for (column, value) in logfmt_cells(record, &columns).into_iter().enumerate() {
+                let mut width = value.chars().count();
+                let mut cell = match matching_style(&terms, &value) {
+                    Some(_) => highlight_filter_text(&value, &terms, base_style),
+                    None => Line::from(Span::styled(value, base_style)),
+                };
+                if column == 0 {
+                    if let Some(ref prefix) = prefix {
+                        width += prefix.content.chars().count();
+                        cell.spans.insert(0, prefix.clone());
+                    }
+                }
+                widths[column] = widths[column].max(width);
+                cells.push(Cell::from(cell));
+            }
+            rows.push(Row::new(cells));
+        }
+
+        let mut title = if let Some(filter) = app_state.filter_label() {
+            format!("Logfmt Records (filtered: '{}', {} matches{})", filter, app_state.filtered_indices.len(), context_note(app_state))
+        } else {
+            "Logfmt Records".to_string()
+        };
+        push_scroll_marker(&mut title, app_state);
+        push_ai_note(&mut title, app_state);
+        let keys = columns.iter().filter(|key| !key.is_empty()).count();
+        let footer = summary_footer(rows.len(), keys, unparsed);
+
+        if rows.is_empty() {
+            let msg = Paragraph::new("No logfmt records found")
+                .block(Block::default().borders(Borders::ALL).title(title).title_bottom(footer))
+                .style(Style::default().fg(Color::Yellow));
+            f.render_widget(msg, area);
+            return;
+        }
+
+        // Keep the current line in view; unparsable lines aren't rows, so it
+        // can't be looked up by position alone
+        let current = app_state.current_index();
+        let selected = records
+            .iter()
+            .position(|(idx, _)| Some(*idx) == current)
+            .unwrap_or_else(|| app_state.scroll_offset.min(rows.len() - 1));
+        let constraints: Vec<Constraint> = widths
+            .iter()
+            .map(|&width| Constraint::Length(width.clamp(1, MAX_CSV_COLUMN_WIDTH) as u16))
+            .collect();
+        let header_row = Row::new(columns).style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
+        let table = Table::new(rows, constraints)
+            .header(header_row)
+            .block(Block::default().borders(Borders::ALL).title(title).title_bottom(footer));
+        let mut table_state = TableState::default().with_selected(Some(selected));
+        f.render_stateful_widget(table, area, &mut table_state);
+    }
+}
+
+/// Columns of the Logfmt view: an unnamed one for words outside any pair,
+/// if a record has some, then every key in order of first appearance
+fn logfmt_columns<'a>(records: impl Iterator<Item = &'a utils::LogfmtRecord>) -> Vec<String> {
+    let mut has_text = false;
+    let mut keys: Vec<String> = Vec::new();
+    let mut seen: HashSet<&str> = HashSet::new();
+    for record in records {
+        has_text |= !record.text.is_empty();
+        for (key, _) in &record.pairs {
+            if seen.insert(key) {
+                keys.push(key.clone());
+            }
+        }
+    }
+    if has_text {
+        keys.insert(0, String::new());
+    }
+    keys
+}
+
+/// A record's values under `columns`, blank where it lacks a key
+fn logfmt_cells(record: &utils::LogfmtRecord, columns: &[String]) -> Vec<String> {
+    columns
+        .iter()
+        .map(|column| {
+            if column.is_empty() {
+                return record.text.clone();
+            }
+            record
+                .pairs
+                .iter()
+                .find(|(key, _)| key == column)
+                .map(|(_, value)| value.clone())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Lines as aligned logfmt columns under a header of keys, for `--render
+/// logfmt`. Lines without pairs are skipped, same as LogfmtView.
+fn render_logfmt_text(lines: &[String]) -> Vec<String> {
+    let records: Vec<utils::LogfmtRecord> = lines.iter().filter_map(|line| utils::parse_logfmt(line)).collect();
+    let columns = logfmt_columns(records.iter());
+    let rows: Vec<Vec<String>> = std::iter::once(columns.clone())
+        .chain(records.iter().map(|record| logfmt_cells(record, &columns)))
+        .collect();
+    align_columns(&rows)
+}
+
+/// Lines as aligned CSV/TSV columns, for `--render csv`. Lines that don't
+/// parse are skipped, same as CsvView.
+fn render_csv_text(lines: &[String]) -> Vec<String> {
+    let delimiter = utils::detect_delimiter(lines.iter());
+    let rows: Vec<Vec<String>> = lines
+        .iter()
+        .map(|line| utils::split_delimited(line, delimiter))
+        .filter(|fields| !fields.is_empty())
+        .collect();
+    align_columns(&rows)
+}
+
+/// Rows of fields padded into columns two spaces apart, each as wide as its
+/// widest field (up to MAX_CSV_COLUMN_WIDTH)
+fn align_columns(rows: &[Vec<String>]) -> Vec<String> {
+    let mut widths: Vec<usize> = Vec::new();
+    for fields in rows {
+        for (column, field) in fields.iter().enumerate() {
+            let width = field.chars().count().min(MAX_CSV_COLUMN_WIDTH);
+            match widths.get_mut(column) {
+                Some(max) => *max = (*max).max(width),
+                None => widths.push(width),
+            }
+        }
+    }
+    rows.iter()
+        .map(|fields| {
+            let padded: Vec<String> = fields
+                .iter()
+                .enumerate()
+                .map(|(column, field)| format!("{:<width$}", field, width = widths[column]))
+                .collect();
+            padded.join("  ").trim_end().to_string()
+        })
+        .collect()
+}
+
+/// The complete, untruncated value of a field, for the expanded selected row
+fn full_json_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => utils::sanitize_for_display(s, usize::MAX),
+        other => utils::sanitize_for_display(&other.to_string(), usize::MAX),
+    }
+}
+
+/// Format a JSON value for a single table cell: full strings and scalars,
+/// small arrays/objects inline, larger ones summarized. Containers nested
+/// more than `max_depth` levels collapse to a `…` summary.
+fn format_json_value(value: &Value, max_depth: usize) -> String {
+    match value {
+        Value::String(s) => utils::sanitize_for_display(s, 500),
+        _ => format_json_inline(value, max_depth),
+    }
+}
+
+/// Compact inline form used inside arrays and objects
+fn format_json_inline(value: &Value, depth: usize) -> String {
+    match value {
+        Value::String(s) => format!("\"{}\"", utils::sanitize_for_display(s, 50)),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Null => "null".to_string(),
+        Value::Array(arr) => {
+            if arr.is_empty() {
+                "[]".to_string()
+            } else if depth == 0 {
+                format!("[…{} items]", arr.len())
+            } else if arr.len() <= 5 {
+                // Show array contents if small, otherwise count
+                let items: Vec<String> = arr.iter()
+                    .map(|v| format_json_inline(v, depth - 1))
+                    .collect();
+                format!("[{}]", items.join(", "))
+            } else {
+                format!("[{} items]", arr.len())
+            }
+        }
+        Value::Object(obj) => {
+            if obj.is_empty() {
+                "{}".to_string()
+            } else if depth == 0 {
+                format!("{{…{} keys}}", obj.len())
+            } else if obj.len() <= 3 {
+                // For nested objects, show key count but also try to show some content
+                let pairs: Vec<String> = obj.iter()
+                    .map(|(k, v)| format!("{}: {}", k, format_json_inline(v, depth - 1)))
+                    .collect();
+                format!("{{{}}}", pairs.join(", "))
+            } else {
+                format!("{{{} keys}}", obj.len())
+            }
+        }
+    }
+}
+
+/// Format a key=value value, rendering embedded JSON (`data={...}`) like the
+/// Json view does, so `>`/`<` control how much of it is expanded
+fn format_kv_value(value: &str, max_depth: usize) -> String {
+    if value.starts_with(['{', '[']) {
+        if let Ok(json) = serde_json::from_str::<Value>(value) {
+            return format_json_inline(&json, max_depth);
+        }
+    }
+    value.to_string()
+}
+
+/// Format lines as plain text using a built-in view's layout, without any
+/// terminal UI. Used by `--render` so the formatters work in pipes and scripts.
+pub fn render_plain_text(view: &ViewKind, lines: &[String], json_max_depth: usize) -> Vec<String> {
+    // Columns are aligned across all lines, so CSV and logfmt can't go line by line
+    match view {
+        ViewKind::Csv => return render_csv_text(lines),
+        ViewKind::Logfmt => return render_logfmt_text(lines),
+        _ => {}
+    }
+    let mut out = Vec::new();
+    for line in lines {
+        match view {
+            ViewKind::KeyValue => {
+                let pairs = utils::extract_key_value_pairs(line);
+                if pairs.is_empty() {
+                    out.push(utils::safe_string_display(line));
+                } else {
+                    let formatted: Vec<String> = pairs
+                        .iter()
+                        .map(|(k, v)| format!("{}: {}", k, format_kv_value(v, json_max_depth)))
+                        .collect();
+                    out.push(formatted.join(" "));
+                }
+            }
+            ViewKind::Json => match serde_json::from_str::<Value>(line) {
+                Ok(Value::Object(map)) => {
+                    // Align keys like the table's key column
+                    let width = map.keys().map(|k| k.chars().count().min(100)).max().unwrap_or(0);
+                    for (key, value) in map.iter() {
+                        let safe_key = utils::sanitize_for_display(key, 100);
+                        out.push(format!("{:<width$}  {}", safe_key, format_json_value(value, json_max_depth), width = width));
+                    }
+                    out.push(String::new());
+                }
+                Ok(json) => out.push(utils::safe_json_display(&json)),
+                // Not valid JSON - skipped, same as JsonView
+                Err(_) => {}
+            },
+            ViewKind::Plain | ViewKind::Logfmt | ViewKind::Csv | ViewKind::Bookmarks | ViewKind::ExternalTool(_) => out.push(utils::safe_string_display(line)),
+        }
+    }
+    out
+}
+
 pub struct ExternalToolView;
 
 impl ExternalToolView {
@@ -444,3 +1792,118 @@ impl ExternalToolView {
         f.render_widget(paragraph, area);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn python_logging_timestamps_are_not_tabular() {
+        let lines = [
+            "2024-01-01 12:00:00,123 - INFO - starting worker",
+            "2024-01-01 12:00:01,456 - INFO - connected to db",
+            "2024-01-01 12:00:02,789 - WARNING - slow query",
+            "2024-01-01 12:00:03,012 - ERROR - lost connection",
+        ];
+        assert!(!looks_tabular(&lines));
+    }
+
+    #[test]
+    fn two_columns_under_a_header_are_tabular() {
+        assert!(looks_tabular(&["name,count", "apples,3", "pears,5", "plums,7"]));
+        assert!(!looks_tabular(&["apples,3", "pears,5", "plums,7"]));
+    }
+
+    #[test]
+    fn three_steady_columns_are_tabular() {
+        assert!(looks_tabular(&["1,2,3", "4,5,6", "7,8,9"]));
+        assert!(looks_tabular(&["a\tb\tc", "1\t2\t3", "4\t5\t6"]));
+    }
+
+    /// Characters that trip up byte slicing and column counting: multibyte,
+    /// double-width, zero-width, combining, and ones whose lowercase is longer
+    const TRICKY_CHARS: &[char] = &['a', 'B', ' ', 'é', 'ß', 'İ', 'Ω', '日', '本', '🎉', '\u{301}', '\u{200b}', '\t'];
+
+    /// A small xorshift generator, so the cases are the same on every run
+    struct Xorshift(u64);
+
+    impl Xorshift {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+
+        fn text(&mut self, max_len: usize) -> String {
+            (0..self.below(max_len + 1)).map(|_| TRICKY_CHARS[self.below(TRICKY_CHARS.len())]).collect()
+        }
+    }
+
+    fn joined(line: &Line) -> String {
+        line.spans.iter().map(|span| span.content.as_ref()).collect()
+    }
+
+    #[test]
+    fn highlighting_keeps_every_character() {
+        let mut rng = Xorshift(0x5eed);
+        let patterns: Vec<FilterMode> =
+            ["^.", "\\b\\w", "日+|é", ".\u{301}", "$"].iter().map(|source| FilterMode::pattern(source).unwrap()).collect();
+        for _ in 0..2000 {
+            let line = rng.text(40);
+            let mut terms = vec![FilterMode::Literal(rng.text(3))];
+            // Part of the line itself, so literal matches aren't rare
+            let start = rng.below(line.chars().count() + 1);
+            terms.push(FilterMode::Literal(line.chars().skip(start).take(rng.below(4)).collect()));
+            terms.extend(patterns.iter().cloned());
+            let styles = [Style::default().fg(Color::Red), Style::default().fg(Color::Blue)];
+            for ignore_case in [false, true] {
+                let highlights = FilterHighlights {
+                    terms: terms.iter().zip(styles.iter().cycle()).map(|(term, style)| (term, *style)).collect(),
+                    ignore_case,
+                };
+                let highlighted = highlight_filter_text(&line, &highlights, Style::default());
+                assert_eq!(joined(&highlighted), line);
+            }
+        }
+    }
+
+    #[test]
+    fn wrapping_keeps_every_character_within_the_width() {
+        let mut rng = Xorshift(0xc0ffee);
+        for _ in 0..2000 {
+            let text = rng.text(60);
+            let width = rng.below(12);
+            let split = text.char_indices().nth(rng.below(text.chars().count() + 1)).map_or(text.len(), |(i, _)| i);
+            let line = Line::from(vec![
+                Span::raw(text[..split].to_string()),
+                Span::styled(text[split..].to_string(), Style::default().fg(Color::Red)),
+            ]);
+            let rows = wrap_line(line, width);
+            assert_eq!(rows.iter().map(joined).collect::<String>(), text);
+            for row in &rows {
+                let widths: Vec<usize> = joined(row).chars().map(|c| c.width().unwrap_or(0)).collect();
+                // Only a character wider than a whole row may overflow it
+                let columns: usize = widths.iter().sum();
+                assert!(columns <= width.max(1) || widths[0] > width.max(1), "{:?} in {} columns", joined(row), width);
+            }
+        }
+    }
+
+    #[test]
+    fn anchors_see_the_whole_line() {
+        let red = Style::default().fg(Color::Red);
+        for (source, line, expected) in [
+            ("^a", "aaa", vec!["a"]),
+            ("\\bfoo", "xfoo foo", vec!["foo"]),
+            ("o$", "foo", vec!["o"]),
+        ] {
+            let term = FilterMode::pattern(source).unwrap();
+            let highlights = FilterHighlights { terms: vec![(&term, red)], ignore_case: false };
+            let highlighted = highlight_filter_text(line, &highlights, Style::default());
+            let marked: Vec<&str> =
+                highlighted.spans.iter().filter(|span| span.style == red).map(|span| span.content.as_ref()).collect();
+            assert_eq!(marked, expected, "{} in {:?}", source, line);
+        }
+    }
+}